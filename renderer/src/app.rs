@@ -1,10 +1,11 @@
+use crate::input::InputEvent;
+use crate::replay::{InputRecorder, InputReplay};
 use crate::state::State;
 use std::sync::Arc;
 use winit::{
     application::ApplicationHandler,
     event::*,
-    event_loop::ActiveEventLoop,
-    keyboard::PhysicalKey,
+    event_loop::{ActiveEventLoop, ControlFlow},
     window::Window,
 };
 use instant::Instant;
@@ -14,9 +15,13 @@ pub struct App {
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>,
     last_render: Instant,
+    recorder: Option<InputRecorder>,
+    // While replaying, live input is ignored and `dt` comes from the recording
+    replay: Option<InputReplay>,
 }
 
 impl App {
+    #[allow(clippy::new_without_default)]
     pub fn new(#[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
@@ -25,8 +30,35 @@ impl App {
             #[cfg(target_arch = "wasm32")]
             proxy,
             last_render: Instant::now(),
+            recorder: None,
+            replay: None,
         }
     }
+
+    // Writes every input event and frame time to `recorder`
+    pub fn record_input(mut self, recorder: InputRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    // Runs the recorded session instead of live input, exits when it ends
+    pub fn replay_input(mut self, replay: InputReplay) -> Self {
+        self.replay = Some(replay);
+        self
+    }
+
+    fn handle_input(&mut self, event: InputEvent) {
+        let Some(state) = self.state.as_mut() else {
+            return;
+        };
+        if self.replay.is_some() {
+            return;
+        }
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(&event);
+        }
+        state.input(&event);
+    }
 }
 
 impl ApplicationHandler<State> for App {
@@ -81,11 +113,9 @@ impl ApplicationHandler<State> for App {
         // This is where proxy.send_event() ends up
         #[cfg(target_arch = "wasm32")]
         {
-            event.window.request_redraw();
-            event.resize(
-                event.window.inner_size().width,
-                event.window.inner_size().height,
-            );
+            event.request_redraw();
+            let size = event.window_size();
+            event.resize(size.width, size.height);
         }
         self.state = Some(event);
     }
@@ -96,16 +126,24 @@ impl ApplicationHandler<State> for App {
         _window_id: winit::window::WindowId,
         event: WindowEvent,
     ) {
+        if let Some(input) = InputEvent::from_window_event(&event) {
+            self.handle_input(input);
+        }
+
         let state = match &mut self.state {
             Some(canvas) => canvas,
             None => return,
         };
 
-        let _event_handled_by_camera = state.input(&event);
-
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::Resized(size) => {
+                // Most platforms report minimizing as a zero size
+                state.frame_limiter().set_minimized(size.width == 0 || size.height == 0);
+                state.resize(size.width, size.height);
+            }
+            WindowEvent::Occluded(occluded) => state.frame_limiter().set_minimized(occluded),
+            WindowEvent::Focused(focused) => state.frame_limiter().set_focused(focused),
             WindowEvent::RedrawRequested => match state.render() {
                 Ok(_) => {}
                 Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -116,39 +154,74 @@ impl ApplicationHandler<State> for App {
                     log::error!("Unable to render { }", e);
                 }
             },
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(code),
-                        state: key_state,
-                        ..
-                    },
-                ..
-            } => state.handle_key(event_loop, code, key_state.is_pressed()),
             _ => {}
         }
     }
 
     fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
-        let state = match &mut self.state {
-            Some(canvas) => canvas,
-            None => return,
-        };
-
-        let _device_event = state.device_input(&event);
+        if let Some(input) = InputEvent::from_device_event(&event) {
+            self.handle_input(input);
+        }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
         let state = match &mut self.state {
             Some(canvas) => canvas,
             None => return,
         };
 
+        // Sleep until the limiter lets the next frame through, new events wake us earlier
+        if let Some(wake_at) = state.frame_limiter().poll(Instant::now()) {
+            event_loop.set_control_flow(ControlFlow::WaitUntil(wake_at));
+            return;
+        }
+
         let now = Instant::now();
-        let dt = now - self.last_render;
+        let mut dt = now - self.last_render;
         self.last_render = now;
 
+        let replayed = match self.replay.as_mut() {
+            Some(replay) => {
+                let Some(frame) = replay.next_frame() else {
+                    log::info!("Replay finished after {} frames, {} diverged", replay.frame(), replay.diverged());
+                    event_loop.exit();
+                    return;
+                };
+                for event in &frame.events {
+                    state.input(event);
+                }
+                dt = frame.dt;
+                Some(frame)
+            }
+            None => {
+                for event in state.poll_gamepad() {
+                    if let Some(recorder) = self.recorder.as_mut() {
+                        recorder.record(&event);
+                    }
+                    state.input(&event);
+                }
+                None
+            }
+        };
+
         state.update(dt);
-        state.window.request_redraw();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.end_frame(dt, state.camera_pose());
+        }
+        if let (Some(replay), Some(frame)) = (self.replay.as_mut(), replayed) {
+            replay.check(&frame, state.camera_pose());
+        }
+        if state.exit_requested() {
+            event_loop.exit();
+            return;
+        }
+        if !state.frame_limiter().is_minimized() {
+            state.request_redraw();
+        }
+        // Without a redraw nothing else would wake a minimized window for its next update
+        event_loop.set_control_flow(match state.frame_limiter().wake_at() {
+            Some(wake_at) => ControlFlow::WaitUntil(wake_at),
+            None => ControlFlow::Wait,
+        });
     }
 }
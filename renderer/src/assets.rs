@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use crate::model::Model;
+use crate::resources;
+use crate::texture::{ColorSpace, MipmapGenerator, Texture, TextureSettings};
+
+// Typed reference to an asset stored in `Assets<T>`. Cloning a handle bumps the
+// reference count, the asset is freed by `AssetServer::free_unused` once the last
+// handle outside the server is dropped.
+pub struct Handle<T> {
+    id: u64,
+    refs: Arc<()>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            refs: self.refs.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> std::hash::Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> std::fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle<{}>({})", std::any::type_name::<T>(), self.id)
+    }
+}
+
+struct Entry<T> {
+    asset: T,
+    // The server's own reference, a count of 1 means no handles are left
+    refs: Arc<()>,
+}
+
+pub struct Assets<T> {
+    entries: HashMap<u64, Entry<T>>,
+    next_id: u64,
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl<T> Assets<T> {
+    pub fn insert(&mut self, asset: T) -> Handle<T> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let refs = Arc::new(());
+        self.entries.insert(id, Entry { asset, refs: refs.clone() });
+        Handle { id, refs, _marker: PhantomData }
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entries.get(&handle.id).map(|entry| &entry.asset)
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entries.get_mut(&handle.id).map(|entry| &mut entry.asset)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // New handle to a stored asset, used to hand out cached entries
+    fn handle(&self, id: u64) -> Option<Handle<T>> {
+        self.entries.get(&id).map(|entry| Handle {
+            id,
+            refs: entry.refs.clone(),
+            _marker: PhantomData,
+        })
+    }
+
+    // Drops every asset without outstanding handles, returns how many were freed
+    fn remove_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| Arc::strong_count(&entry.refs) > 1);
+        before - self.entries.len()
+    }
+}
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+struct TextureKey {
+    path: String,
+    color_space: ColorSpace,
+    mipmaps: bool,
+}
+
+// Loads assets from the res directory, caching them by path so a file is only
+// read and uploaded once while any handle to it is alive.
+#[derive(Default)]
+pub struct AssetServer {
+    pub textures: Assets<Texture>,
+    pub models: Assets<Model>,
+    // Used for the textures and samplers of models loaded afterwards
+    pub texture_settings: TextureSettings,
+    texture_paths: HashMap<TextureKey, u64>,
+    model_paths: HashMap<String, u64>,
+    // Created with the first texture that needs mips
+    mipmaps: Option<MipmapGenerator>,
+}
+
+impl AssetServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn texture_settings(mut self, settings: TextureSettings) -> Self {
+        self.texture_settings = settings;
+        self
+    }
+
+    fn mipmap_generator(&mut self, device: &wgpu::Device) -> &mut MipmapGenerator {
+        self.mipmaps.get_or_insert_with(|| MipmapGenerator::new(device))
+    }
+
+    pub async fn load_texture(
+        &mut self,
+        file_name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color_space: ColorSpace,
+        generate_mipmaps: bool,
+    ) -> anyhow::Result<Handle<Texture>> {
+        let key = TextureKey {
+            path: file_name.to_string(),
+            color_space,
+            mipmaps: generate_mipmaps,
+        };
+        if let Some(handle) = self.texture_paths.get(&key).and_then(|id| self.textures.handle(*id)) {
+            return Ok(handle);
+        }
+
+        let mipmaps = generate_mipmaps.then(|| self.mipmap_generator(device));
+        let texture = resources::load_texture(file_name, device, queue, color_space, mipmaps).await?;
+        let handle = self.textures.insert(texture);
+        self.texture_paths.insert(key, handle.id);
+        Ok(handle)
+    }
+
+    // Cached texture for a built-in key (e.g. fallbacks), created on first use
+    pub fn texture_or_insert_with(&mut self, name: &str, create: impl FnOnce() -> Texture) -> Handle<Texture> {
+        let key = TextureKey {
+            path: format!("builtin:{name}"),
+            color_space: ColorSpace::Linear,
+            mipmaps: false,
+        };
+        if let Some(handle) = self.texture_paths.get(&key).and_then(|id| self.textures.handle(*id)) {
+            return handle;
+        }
+
+        let handle = self.textures.insert(create());
+        self.texture_paths.insert(key, handle.id);
+        handle
+    }
+
+    pub async fn load_model(
+        &mut self,
+        file_name: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> anyhow::Result<Handle<Model>> {
+        if let Some(handle) = self.model_paths.get(file_name).and_then(|id| self.models.handle(*id)) {
+            return Ok(handle);
+        }
+
+        let model = resources::load_model(file_name, device, queue, layout, self).await?;
+        let handle = self.models.insert(model);
+        self.model_paths.insert(file_name.to_string(), handle.id);
+        Ok(handle)
+    }
+
+    // Reloads assets whose files changed (paths relative to the res directory),
+    // replacing them in place so existing handles stay valid. Assets that fail to
+    // reload keep their previous contents.
+    pub async fn reload_changed(
+        &mut self,
+        changed: &HashSet<String>,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) {
+        let mut reloaded_textures = HashSet::new();
+        let texture_paths = self.texture_paths.clone();
+        for (key, id) in texture_paths {
+            if !changed.contains(&key.path) {
+                continue;
+            }
+            let mipmaps = key.mipmaps.then(|| self.mipmap_generator(device));
+            match resources::load_texture(&key.path, device, queue, key.color_space, mipmaps).await {
+                Ok(texture) => {
+                    if let Some(entry) = self.textures.entries.get_mut(&id) {
+                        entry.asset = texture;
+                        reloaded_textures.insert(id);
+                        log::info!("Reloaded texture {:?}", key.path);
+                    }
+                }
+                Err(e) => log::warn!("Unable to reload texture {:?}: {}", key.path, e),
+            }
+        }
+
+        // Material libraries aren't tracked per model, so any MTL edit reloads every model
+        let mtl_changed = changed.iter().any(|path| path.to_lowercase().ends_with(".mtl"));
+        let model_paths = self.model_paths.clone();
+        for (path, id) in model_paths {
+            if mtl_changed || changed.contains(&path) {
+                let Some(layout) = self.models.entries.get(&id).and_then(|entry| entry.asset.materials.first()).map(|m| m.layout.clone()) else {
+                    continue;
+                };
+                match resources::load_model(&path, device, queue, &layout, self).await {
+                    Ok(model) => {
+                        if let Some(entry) = self.models.entries.get_mut(&id) {
+                            entry.asset = model;
+                            log::info!("Reloaded model {:?}", path);
+                        }
+                    }
+                    Err(e) => log::warn!("Unable to reload model {:?}: {}", path, e),
+                }
+            } else if !reloaded_textures.is_empty() {
+                let Some(entry) = self.models.entries.get_mut(&id) else {
+                    continue;
+                };
+                for material in entry.asset.materials.iter_mut() {
+                    if reloaded_textures.iter().any(|texture| material.uses_texture(*texture)) {
+                        material.rebuild_bind_group(device, &self.textures);
+                    }
+                }
+            }
+        }
+    }
+
+    // Frees assets that are no longer referenced. Models go first since their
+    // materials hold texture handles.
+    pub fn free_unused(&mut self) -> usize {
+        let freed = self.models.remove_unused() + self.textures.remove_unused();
+        if freed > 0 {
+            let models = &self.models;
+            let textures = &self.textures;
+            self.model_paths.retain(|_, id| models.entries.contains_key(id));
+            self.texture_paths.retain(|_, id| textures.entries.contains_key(id));
+            log::info!("Freed {freed} unused assets");
+        }
+        freed
+    }
+}
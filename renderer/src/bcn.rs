@@ -0,0 +1,770 @@
+// CPU decompression of BC1-BC5 and BC7 blocks into RGBA8, used when the adapter
+// lacks TEXTURE_COMPRESSION_BC. Signed BC4/BC5 decode to RGBA8 snorm bytes. BC6H
+// is HDR and left to the caller to reject.
+
+pub fn decompress(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+
+    let block_size = match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb | Bc4RUnorm | Bc4RSnorm => 8,
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb | Bc3RgbaUnorm | Bc3RgbaUnormSrgb | Bc5RgUnorm | Bc5RgSnorm => 16,
+        Bc7RgbaUnorm | Bc7RgbaUnormSrgb => 16,
+        _ => return None,
+    };
+    decompress_blocks(width, height, data, block_size, |block, texels| match format {
+        Bc1RgbaUnorm | Bc1RgbaUnormSrgb => decode_color(block, true, texels),
+        Bc2RgbaUnorm | Bc2RgbaUnormSrgb => {
+            decode_color(&block[8..], false, texels);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                let nibble = (block[i / 2] >> ((i % 2) * 4)) & 0xF;
+                texel[3] = nibble * 17;
+            }
+        }
+        Bc3RgbaUnorm | Bc3RgbaUnormSrgb => {
+            decode_color(&block[8..], false, texels);
+            let alpha = decode_channel(&block[..8]);
+            for (texel, a) in texels.iter_mut().zip(alpha) {
+                texel[3] = a;
+            }
+        }
+        Bc4RUnorm => {
+            for (texel, r) in texels.iter_mut().zip(decode_channel(block)) {
+                *texel = [r, 0, 0, 255];
+            }
+        }
+        Bc5RgUnorm => {
+            let red = decode_channel(&block[..8]);
+            let green = decode_channel(&block[8..]);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                *texel = [red[i], green[i], 0, 255];
+            }
+        }
+        Bc4RSnorm => {
+            for (texel, r) in texels.iter_mut().zip(decode_signed_channel(block)) {
+                *texel = [r as u8, 0, 0, 127];
+            }
+        }
+        Bc5RgSnorm => {
+            let red = decode_signed_channel(&block[..8]);
+            let green = decode_signed_channel(&block[8..]);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                *texel = [red[i] as u8, green[i] as u8, 0, 127];
+            }
+        }
+        Bc7RgbaUnorm | Bc7RgbaUnormSrgb => decode_bc7(block, texels),
+        _ => unreachable!(),
+    })
+}
+
+// Runs `decode` over every 4x4 block and writes the row-major texels it
+// produces into an RGBA8 image, cropping blocks that hang over the edge.
+// `None` when `data` is too short for the image.
+pub(crate) fn decompress_blocks(
+    width: u32,
+    height: u32,
+    data: &[u8],
+    block_size: usize,
+    mut decode: impl FnMut(&[u8], &mut [[u8; 4]; 16]),
+) -> Option<Vec<u8>> {
+    let blocks_wide = width.div_ceil(4) as usize;
+    let blocks_high = height.div_ceil(4) as usize;
+    if data.len() < blocks_wide * blocks_high * block_size {
+        return None;
+    }
+
+    let mut rgba = vec![0u8; width as usize * height as usize * 4];
+    let mut texels = [[0u8; 4]; 16];
+    for by in 0..blocks_high {
+        for bx in 0..blocks_wide {
+            let offset = (by * blocks_wide + bx) * block_size;
+            decode(&data[offset..offset + block_size], &mut texels);
+
+            for (i, texel) in texels.iter().enumerate() {
+                let x = bx * 4 + i % 4;
+                let y = by * 4 + i / 4;
+                if x < width as usize && y < height as usize {
+                    let dst = (y * width as usize + x) * 4;
+                    rgba[dst..dst + 4].copy_from_slice(texel);
+                }
+            }
+        }
+    }
+
+    Some(rgba)
+}
+
+fn unpack_565(color: u16) -> [u8; 4] {
+    let r = ((color >> 11) & 0x1F) as u32;
+    let g = ((color >> 5) & 0x3F) as u32;
+    let b = (color & 0x1F) as u32;
+    [
+        ((r << 3) | (r >> 2)) as u8,
+        ((g << 2) | (g >> 4)) as u8,
+        ((b << 3) | (b >> 2)) as u8,
+        255,
+    ]
+}
+
+fn mix(a: [u8; 4], b: [u8; 4], wa: u32, wb: u32) -> [u8; 4] {
+    let total = wa + wb;
+    let mut out = [255u8; 4];
+    for c in 0..3 {
+        out[c] = ((a[c] as u32 * wa + b[c] as u32 * wb + total / 2) / total) as u8;
+    }
+    out
+}
+
+// 8 byte BC1 color block. BC2/BC3 always use the four color mode.
+fn decode_color(block: &[u8], punch_through: bool, texels: &mut [[u8; 4]; 16]) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let e0 = unpack_565(c0);
+    let e1 = unpack_565(c1);
+
+    let palette = if c0 > c1 || !punch_through {
+        [e0, e1, mix(e0, e1, 2, 1), mix(e0, e1, 1, 2)]
+    } else {
+        [e0, e1, mix(e0, e1, 1, 1), [0, 0, 0, 0]]
+    };
+
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, texel) in texels.iter_mut().enumerate() {
+        *texel = palette[((indices >> (i * 2)) & 0x3) as usize];
+    }
+}
+
+// Integer division rounding half away from zero
+pub(crate) fn div_round(numerator: i32, denominator: i32) -> i32 {
+    (numerator + numerator.signum() * (denominator / 2)) / denominator
+}
+
+// 8 byte BC3 alpha / BC4 channel block
+fn decode_channel(block: &[u8]) -> [u8; 16] {
+    decode_channel_values(block, block[0] as i32, block[1] as i32, 0, 255).map(|v| v as u8)
+}
+
+// Signed BC4/BC5 channel. -128 is an alias of -127 so both ends reach -1.0 and 1.0.
+fn decode_signed_channel(block: &[u8]) -> [i8; 16] {
+    let endpoint = |byte: u8| (byte as i8).max(-127) as i32;
+    decode_channel_values(block, endpoint(block[0]), endpoint(block[1]), -127, 127).map(|v| v as i8)
+}
+
+// Eight interpolated values when e0 > e1, otherwise six plus the range limits
+fn decode_channel_values(block: &[u8], e0: i32, e1: i32, min: i32, max: i32) -> [i32; 16] {
+    let mut palette = [e0, e1, 0, 0, 0, 0, 0, 0];
+    if e0 > e1 {
+        for i in 1..7 {
+            palette[i + 1] = div_round((7 - i as i32) * e0 + i as i32 * e1, 7);
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = div_round((5 - i as i32) * e0 + i as i32 * e1, 5);
+        }
+        palette[6] = min;
+        palette[7] = max;
+    }
+
+    let mut bits = 0u64;
+    for (i, byte) in block[2..8].iter().enumerate() {
+        bits |= (*byte as u64) << (i * 8);
+    }
+
+    let mut out = [0; 16];
+    for (i, value) in out.iter_mut().enumerate() {
+        *value = palette[((bits >> (i * 3)) & 0x7) as usize];
+    }
+    out
+}
+
+// Two subset partitions, one bit per texel set for the second subset
+const BC7_PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80,
+    0xC800, 0xFFEC, 0xFE80, 0xE800, 0xFFE8, 0xFF00, 0xFFF0, 0xF000,
+    0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C,
+    0xAAAA, 0xF0F0, 0x5A5A, 0x33CC, 0x3C3C, 0x55AA, 0x9696, 0xA55A,
+    0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C,
+    0x9336, 0x9CC6, 0x817E, 0xE718, 0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+// Three subset partitions, two bits per texel
+const BC7_PARTITIONS_3: [u32; 64] = [
+    0xAA685050, 0x6A5A5040, 0x5A5A4200, 0x5450A0A8, 0xA5A50000, 0xA0A05050, 0x5555A0A0, 0x5A5A5050,
+    0xAA550000, 0xAA555500, 0xAAAA5500, 0x90909090, 0x94949494, 0xA4A4A4A4, 0xA9A59450, 0x2A0A4250,
+    0xA5945040, 0x0A425054, 0xA5A5A500, 0x55A0A0A0, 0xA8A85454, 0x6A6A4040, 0xA4A45000, 0x1A1A0500,
+    0x0050A4A4, 0xAAA59090, 0x14696914, 0x69691400, 0xA08585A0, 0xAA821414, 0x50A4A450, 0x6A5A0200,
+    0xA9A58000, 0x5090A0A8, 0xA8A09050, 0x24242424, 0x00AA5500, 0x24924924, 0x24499224, 0x50A50A50,
+    0x500AA550, 0xAAAA4444, 0x66660000, 0xA5A0A5A0, 0x50A050A0, 0x69286928, 0x44AAAA44, 0x66666600,
+    0xAA444444, 0x54A854A8, 0x95809580, 0x96969600, 0xA85454A8, 0x80959580, 0xAA141414, 0x96960000,
+    0xAAAA1414, 0xA05050A0, 0xA0A5A5A0, 0x96000000, 0x40804080, 0xA9A8A9A8, 0xAAAAAA44, 0x2A4A5254,
+];
+
+// Anchor texels store their index with one bit less. Subset 0 is always anchored at texel 0.
+const BC7_ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+const BC7_ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+    3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+    8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+    3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+];
+const BC7_ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+    15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+    15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+    15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+const BC7_WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const BC7_WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const BC7_WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    // P-bits per endpoint, or one shared by both endpoints of a subset
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    secondary_index_bits: u32,
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: true,
+        index_bits: 3,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 3,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_pbits: false,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 2,
+    },
+    Bc7Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 4,
+        secondary_index_bits: 0,
+    },
+    Bc7Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_pbits: true,
+        shared_pbits: false,
+        index_bits: 2,
+        secondary_index_bits: 0,
+    },
+];
+
+// Reads a 128 bit block LSB first
+struct BitReader {
+    bits: u128,
+}
+
+impl BitReader {
+    fn read(&mut self, count: u32) -> u32 {
+        let value = (self.bits & ((1u128 << count) - 1)) as u32;
+        self.bits >>= count;
+        value
+    }
+}
+
+fn bc7_interpolate(e0: u8, e1: u8, weight: u32) -> u8 {
+    (((64 - weight) * e0 as u32 + weight * e1 as u32 + 32) >> 6) as u8
+}
+
+fn bc7_weight(bits: u32, index: u32) -> u32 {
+    match bits {
+        2 => BC7_WEIGHTS_2[index as usize],
+        3 => BC7_WEIGHTS_3[index as usize],
+        _ => BC7_WEIGHTS_4[index as usize],
+    }
+}
+
+// Expands a quantized endpoint to 8 bits by repeating its high bits
+fn bc7_unquantize(value: u32, bits: u32) -> u8 {
+    let value = value << (8 - bits);
+    (value | (value >> bits)) as u8
+}
+
+// 16 byte BC7 block
+fn decode_bc7(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let mut reader = BitReader {
+        bits: u128::from_le_bytes(block.try_into().unwrap()),
+    };
+    let Some(mode_index) = (0..8).find(|_| reader.read(1) == 1) else {
+        // Reserved mode, decodes to transparent black
+        *texels = [[0; 4]; 16];
+        return;
+    };
+    let mode = &BC7_MODES[mode_index];
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    // Endpoints are stored channel by channel: all reds, then greens, blues and alphas
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..4 {
+        let bits = if channel < 3 { mode.color_bits } else { mode.alpha_bits };
+        for endpoint in endpoints.iter_mut().take(endpoint_count) {
+            endpoint[channel] = reader.read(bits);
+        }
+    }
+
+    let mut color_bits = mode.color_bits;
+    let mut alpha_bits = mode.alpha_bits;
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for pbit in pbits.iter_mut().take(endpoint_count) {
+                *pbit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+        for (endpoint, pbit) in endpoints.iter_mut().zip(pbits).take(endpoint_count) {
+            for value in endpoint.iter_mut() {
+                *value = (*value << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+
+    let mut colors = [[0u8; 4]; 6];
+    for (color, endpoint) in colors.iter_mut().zip(endpoints).take(endpoint_count) {
+        for channel in 0..3 {
+            color[channel] = bc7_unquantize(endpoint[channel], color_bits);
+        }
+        color[3] = if alpha_bits > 0 { bc7_unquantize(endpoint[3], alpha_bits) } else { 255 };
+    }
+
+    let subset_of = |texel: usize| -> usize {
+        match mode.subsets {
+            2 => ((BC7_PARTITIONS_2[partition] >> texel) & 1) as usize,
+            3 => ((BC7_PARTITIONS_3[partition] >> (texel * 2)) & 3) as usize,
+            _ => 0,
+        }
+    };
+    let is_anchor = |texel: usize| -> bool {
+        texel == 0
+            || match mode.subsets {
+                2 => texel == BC7_ANCHORS_2[partition] as usize,
+                3 => texel == BC7_ANCHORS_3_SECOND[partition] as usize || texel == BC7_ANCHORS_3_THIRD[partition] as usize,
+                _ => false,
+            }
+    };
+
+    let mut indices = [0u32; 16];
+    for (texel, index) in indices.iter_mut().enumerate() {
+        let bits = if is_anchor(texel) { mode.index_bits - 1 } else { mode.index_bits };
+        *index = reader.read(bits);
+    }
+    // Single subset modes only, anchored at texel 0
+    let mut secondary = [0u32; 16];
+    if mode.secondary_index_bits > 0 {
+        for (texel, index) in secondary.iter_mut().enumerate() {
+            let bits = if texel == 0 { mode.secondary_index_bits - 1 } else { mode.secondary_index_bits };
+            *index = reader.read(bits);
+        }
+    }
+
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let subset = subset_of(texel);
+        let (e0, e1) = (colors[subset * 2], colors[subset * 2 + 1]);
+        // Modes 4 and 5 interpolate color and alpha with separate indices, mode 4
+        // can swap which set is used for which
+        let (color_weight, alpha_weight) = if mode.secondary_index_bits > 0 {
+            let primary = bc7_weight(mode.index_bits, indices[texel]);
+            let secondary = bc7_weight(mode.secondary_index_bits, secondary[texel]);
+            if index_selection == 1 { (secondary, primary) } else { (primary, secondary) }
+        } else {
+            let weight = bc7_weight(mode.index_bits, indices[texel]);
+            (weight, weight)
+        };
+        for channel in 0..3 {
+            out[channel] = bc7_interpolate(e0[channel], e1[channel], color_weight);
+        }
+        out[3] = bc7_interpolate(e0[3], e1[3], alpha_weight);
+        // Rotation swaps alpha with one of the color channels
+        if rotation > 0 {
+            out.swap(3, rotation as usize - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::TextureFormat::*;
+
+    // Expected texels were read back from Mesa's decoders unless noted. Decoders
+    // may round interpolated values differently, `tolerance` absorbs that.
+    fn assert_block(format: wgpu::TextureFormat, block: &[u8], expected: [[i32; 4]; 16], tolerance: i32) {
+        let signed = matches!(format, Bc4RSnorm | Bc5RgSnorm);
+        let decoded = decompress(format, 4, 4, block).unwrap();
+        for (i, (texel, expected)) in decoded.chunks(4).zip(expected).enumerate() {
+            let texel = texel.iter().map(|&c| if signed { c as i8 as i32 } else { c as i32 });
+            for (channel, (actual, expected)) in texel.zip(expected).enumerate() {
+                assert!(
+                    (actual - expected).abs() <= tolerance,
+                    "{format:?} texel {i} channel {channel}: {actual} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bc1_four_color_block() {
+        assert_block(
+            Bc1RgbaUnorm,
+            &[0x00, 0xF8, 0x1F, 0x00, 0xE4, 0xE4, 0xE4, 0xE4],
+            [
+                [255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 84, 255], [85, 0, 169, 255],
+                [255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 84, 255], [85, 0, 169, 255],
+                [255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 84, 255], [85, 0, 169, 255],
+                [255, 0, 0, 255], [0, 0, 255, 255], [170, 0, 84, 255], [85, 0, 169, 255],
+            ],
+            1,
+        );
+    }
+
+    // c0 <= c1 selects the midpoint and transparent black
+    #[test]
+    fn bc1_three_color_block_punches_through() {
+        assert_block(
+            Bc1RgbaUnorm,
+            &[0x1F, 0x00, 0x00, 0xF8, 0xE4, 0xE4, 0xE4, 0xE4],
+            [
+                [0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0],
+                [0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0],
+                [0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0],
+                [0, 0, 255, 255], [255, 0, 0, 255], [128, 0, 128, 255], [0, 0, 0, 0],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn bc4_eight_value_palette() {
+        assert_block(
+            Bc4RUnorm,
+            &[0xC8, 0x28, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA],
+            [
+                [200, 0, 0, 255], [40, 0, 0, 255], [177, 0, 0, 255], [155, 0, 0, 255],
+                [131, 0, 0, 255], [109, 0, 0, 255], [86, 0, 0, 255], [63, 0, 0, 255],
+                [200, 0, 0, 255], [40, 0, 0, 255], [177, 0, 0, 255], [155, 0, 0, 255],
+                [131, 0, 0, 255], [109, 0, 0, 255], [86, 0, 0, 255], [63, 0, 0, 255],
+            ],
+            1,
+        );
+    }
+
+    #[test]
+    fn bc4_six_value_palette_ends_at_the_range_limits() {
+        assert_block(
+            Bc4RUnorm,
+            &[0x28, 0xC8, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA],
+            [
+                [40, 0, 0, 255], [200, 0, 0, 255], [71, 0, 0, 255], [103, 0, 0, 255],
+                [135, 0, 0, 255], [167, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255],
+                [40, 0, 0, 255], [200, 0, 0, 255], [71, 0, 0, 255], [103, 0, 0, 255],
+                [135, 0, 0, 255], [167, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255],
+            ],
+            1,
+        );
+    }
+
+    #[test]
+    fn bc5_channels_pick_their_palettes_independently() {
+        assert_block(
+            Bc5RgUnorm,
+            &[
+                0xC8, 0x28, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA,
+                0x28, 0xC8, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA,
+            ],
+            [
+                [200, 40, 0, 255], [40, 200, 0, 255], [177, 71, 0, 255], [155, 103, 0, 255],
+                [131, 135, 0, 255], [109, 167, 0, 255], [86, 0, 0, 255], [63, 255, 0, 255],
+                [200, 40, 0, 255], [40, 200, 0, 255], [177, 71, 0, 255], [155, 103, 0, 255],
+                [131, 135, 0, 255], [109, 167, 0, 255], [86, 0, 0, 255], [63, 255, 0, 255],
+            ],
+            1,
+        );
+    }
+
+    // Mesa is off by two on the six value palette, those values follow the spec
+    // formula with -128 read as -127
+    #[test]
+    fn signed_bc4_palettes() {
+        assert_block(
+            Bc4RSnorm,
+            &[0x64, 0xB0, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA],
+            [
+                [100, 0, 0, 127], [-80, 0, 0, 127], [74, 0, 0, 127], [49, 0, 0, 127],
+                [23, 0, 0, 127], [-2, 0, 0, 127], [-28, 0, 0, 127], [-54, 0, 0, 127],
+                [100, 0, 0, 127], [-80, 0, 0, 127], [74, 0, 0, 127], [49, 0, 0, 127],
+                [23, 0, 0, 127], [-2, 0, 0, 127], [-28, 0, 0, 127], [-54, 0, 0, 127],
+            ],
+            1,
+        );
+        assert_block(
+            Bc4RSnorm,
+            &[0x80, 0x64, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA],
+            [
+                [-127, 0, 0, 127], [100, 0, 0, 127], [-82, 0, 0, 127], [-36, 0, 0, 127],
+                [9, 0, 0, 127], [55, 0, 0, 127], [-127, 0, 0, 127], [127, 0, 0, 127],
+                [-127, 0, 0, 127], [100, 0, 0, 127], [-82, 0, 0, 127], [-36, 0, 0, 127],
+                [9, 0, 0, 127], [55, 0, 0, 127], [-127, 0, 0, 127], [127, 0, 0, 127],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn signed_bc5() {
+        assert_block(
+            Bc5RgSnorm,
+            &[
+                0x64, 0xB0, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA,
+                0xB0, 0x64, 0x88, 0xC6, 0xFA, 0x88, 0xC6, 0xFA,
+            ],
+            [
+                [100, -80, 0, 127], [-80, 100, 0, 127], [74, -45, 0, 127], [49, -9, 0, 127],
+                [23, 27, 0, 127], [-2, 63, 0, 127], [-28, -127, 0, 127], [-54, 127, 0, 127],
+                [100, -80, 0, 127], [-80, 100, 0, 127], [74, -45, 0, 127], [49, -9, 0, 127],
+                [23, 27, 0, 127], [-2, 63, 0, 127], [-28, -127, 0, 127], [-54, 127, 0, 127],
+            ],
+            1,
+        );
+    }
+
+    // Modes 0-3 and 7 split the block into two or three subsets
+    #[test]
+    fn bc7_partitioned_modes() {
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x21, 0x90, 0xAF, 0xCC, 0x41, 0xB9, 0xDE, 0x07,
+                0x05, 0x11, 0x63, 0xC5, 0x88, 0x11, 0xE3, 0x82,
+            ],
+            [
+                [16, 231, 231, 255], [65, 134, 154, 255], [144, 194, 67, 255], [198, 165, 132, 255],
+                [116, 32, 75, 255], [16, 231, 231, 255], [187, 171, 119, 255], [134, 200, 54, 255],
+                [16, 231, 231, 255], [91, 112, 139, 255], [98, 225, 133, 255], [198, 165, 132, 255],
+                [99, 247, 132, 255], [93, 134, 138, 255], [90, 90, 140, 255], [93, 134, 138, 255],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0xEE, 0xF8, 0xB5, 0x28, 0x80, 0x07, 0x41, 0x7F,
+                0x29, 0x63, 0x2F, 0x65, 0x81, 0x10, 0x22, 0x5C,
+            ],
+            [
+                [171, 53, 211, 255], [190, 36, 226, 255], [190, 36, 226, 255], [45, 66, 188, 255],
+                [44, 66, 159, 255], [208, 19, 240, 255], [227, 2, 255, 255], [190, 36, 226, 255],
+                [46, 66, 203, 255], [45, 66, 188, 255], [44, 66, 142, 255], [227, 2, 255, 255],
+                [208, 19, 240, 255], [44, 66, 142, 255], [44, 66, 159, 255], [45, 66, 188, 255],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x2C, 0x24, 0x70, 0x40, 0x36, 0x4C, 0xB6, 0xFD,
+                0x42, 0x9A, 0xF5, 0xEC, 0xAB, 0x4C, 0x18, 0x84,
+            ],
+            [
+                [99, 144, 116, 255], [49, 87, 81, 255], [38, 194, 112, 255], [115, 222, 90, 255],
+                [49, 87, 81, 255], [99, 144, 116, 255], [38, 194, 112, 255], [115, 222, 90, 255],
+                [148, 198, 148, 255], [0, 33, 49, 255], [148, 255, 181, 255], [148, 255, 181, 255],
+                [49, 87, 81, 255], [148, 198, 148, 255], [148, 255, 181, 255], [135, 177, 162, 255],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x58, 0x61, 0x21, 0xAD, 0x02, 0x42, 0x04, 0xB6,
+                0x27, 0xB3, 0x72, 0x80, 0x15, 0x9F, 0x22, 0x9E,
+            ],
+            [
+                [129, 33, 157, 255], [80, 52, 168, 255], [176, 16, 146, 255], [80, 52, 168, 255],
+                [10, 236, 0, 255], [33, 69, 179, 255], [176, 16, 146, 255], [129, 33, 157, 255],
+                [64, 207, 154, 255], [91, 193, 229, 255], [80, 52, 168, 255], [176, 16, 146, 255],
+                [37, 222, 75, 255], [10, 236, 0, 255], [64, 207, 154, 255], [80, 52, 168, 255],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x80, 0x6E, 0xE6, 0x77, 0xCF, 0x82, 0xFD, 0xB0,
+                0x05, 0xB9, 0x60, 0xD8, 0xF7, 0x06, 0x78, 0x20,
+            ],
+            [
+                [215, 121, 79, 83], [212, 231, 61, 85], [223, 247, 69, 101], [215, 121, 79, 83],
+                [231, 44, 182, 12], [190, 199, 44, 52], [190, 199, 44, 52], [207, 158, 28, 117],
+                [190, 199, 44, 52], [231, 44, 182, 12], [231, 44, 182, 12], [190, 199, 44, 52],
+                [190, 199, 44, 52], [207, 158, 28, 117], [215, 121, 79, 83], [190, 199, 44, 52],
+            ],
+            0,
+        );
+    }
+
+    // Modes 4 and 5, with and without index selection and rotation
+    #[test]
+    fn bc7_separate_alpha_modes() {
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x90, 0xBA, 0x77, 0x17, 0x3F, 0x9D, 0xFA, 0x6F,
+                0x89, 0x8E, 0x4B, 0x2B, 0xB1, 0xAC, 0x93, 0xE4,
+            ],
+            [
+                [218, 222, 156, 211], [218, 222, 156, 166], [232, 150, 223, 166], [232, 150, 223, 166],
+                [221, 204, 172, 166], [221, 204, 172, 196], [228, 167, 206, 166], [232, 150, 223, 181],
+                [228, 167, 206, 211], [232, 150, 223, 196], [235, 132, 239, 211], [218, 222, 156, 196],
+                [218, 222, 156, 166], [218, 222, 156, 196], [218, 222, 156, 211], [239, 115, 255, 166],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0xD0, 0xBA, 0x77, 0x17, 0x3F, 0x9D, 0xFA, 0x6F,
+                0x89, 0x8E, 0x4B, 0x2B, 0xB1, 0xAC, 0x93, 0xE4,
+            ],
+            [
+                [218, 211, 156, 222], [218, 166, 156, 222], [232, 166, 223, 150], [232, 166, 223, 150],
+                [221, 166, 172, 204], [221, 196, 172, 204], [228, 166, 206, 167], [232, 181, 223, 150],
+                [228, 211, 206, 167], [232, 196, 223, 150], [235, 211, 239, 132], [218, 196, 156, 222],
+                [218, 166, 156, 222], [218, 196, 156, 222], [218, 211, 156, 222], [239, 166, 255, 115],
+            ],
+            0,
+        );
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0x60, 0x75, 0x1B, 0xCD, 0xFA, 0xC7, 0x0E, 0xBE,
+                0x4D, 0x0F, 0x87, 0x1C, 0x88, 0xE5, 0x8E, 0xF5,
+            ],
+            [
+                [131, 127, 229, 193], [118, 127, 229, 193], [131, 150, 203, 150], [118, 150, 203, 150],
+                [124, 173, 177, 108], [124, 127, 229, 193], [118, 104, 255, 235], [111, 150, 203, 150],
+                [118, 173, 177, 108], [111, 104, 255, 235], [131, 104, 255, 235], [118, 127, 229, 193],
+                [124, 150, 203, 150], [124, 173, 177, 108], [111, 104, 255, 235], [111, 104, 255, 235],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn bc7_mode_6() {
+        assert_block(
+            Bc7RgbaUnorm,
+            &[
+                0xC0, 0xE5, 0xF0, 0x4F, 0x3D, 0x7B, 0x3F, 0xF5,
+                0x6E, 0xE4, 0x53, 0xF7, 0xF0, 0xC5, 0x3E, 0xA0,
+            ],
+            [
+                [143, 214, 198, 143], [144, 220, 199, 132], [146, 232, 202, 108], [135, 173, 189, 223],
+                [148, 237, 203, 98], [145, 226, 201, 119], [143, 214, 198, 143], [134, 168, 188, 234],
+                [151, 255, 207, 63], [134, 168, 188, 234], [145, 226, 201, 119], [137, 186, 192, 199],
+                [135, 173, 189, 223], [148, 237, 203, 98], [151, 255, 207, 63], [140, 197, 194, 178],
+            ],
+            0,
+        );
+    }
+}
@@ -7,15 +7,15 @@ use cgmath::{
     InnerSpace,
     Rad,
 };
-use winit::{
-    event::*,
-    keyboard::{KeyCode, PhysicalKey},
-    dpi::PhysicalPosition,
-};
 use instant::Duration;
+use crate::input::InputMap;
 use std::f32::consts::FRAC_PI_2;
 
 const HALF_PI: f32 = FRAC_PI_2 - 0.0001;
+// Mouse look rotation per pixel at sensitivity 1
+pub const RADIANS_PER_PIXEL: f32 = 0.004;
+// Gamepad look rotation at full stick deflection and sensitivity 1
+pub const STICK_RADIANS_PER_SECOND: f32 = 2.5;
 
 #[rustfmt::skip]
 const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::from_cols(
@@ -25,6 +25,24 @@ const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::from_cols(
     Vector4::new(0.0, 0.0, 0.5, 1.0),
 );
 
+// Maps depth 0..1 to 1..0, far values end up near zero where floats are densest
+#[rustfmt::skip]
+const REVERSE_Z_MATRIX: Matrix4<f32> = Matrix4::from_cols(
+    Vector4::new(1.0, 0.0, 0.0, 0.0),
+    Vector4::new(0.0, 1.0, 0.0, 0.0),
+    Vector4::new(0.0, 0.0, -1.0, 0.0),
+    Vector4::new(0.0, 0.0, 1.0, 1.0),
+);
+
+// Anything that turns view space into wgpu clip space
+pub trait CameraProjection {
+    fn resize(&mut self, width: u32, height: u32);
+    fn calc_matrix(&self) -> Matrix4<f32>;
+    // Depth is 1 at the near plane and 0 at the far plane, so pipelines must
+    // compare with `Greater` and depth is cleared to 0
+    fn is_reverse_z(&self) -> bool;
+}
+
 #[derive(Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
@@ -32,25 +50,45 @@ pub struct Camera {
     pitch: Rad<f32>,
 }
 
+// Perspective projection, `zfar` is ignored when `infinite_far` is set
+#[derive(Clone)]
 pub struct Projection {
     aspect: f32,
     fovy: Rad<f32>,
     znear: f32,
     zfar: f32,
+    reverse_z: bool,
+    infinite_far: bool,
 }
 
+// Parallel projection showing `view_height` world units vertically
+pub struct OrthographicProjection {
+    aspect: f32,
+    view_height: f32,
+    znear: f32,
+    zfar: f32,
+    reverse_z: bool,
+}
+
+// Fly camera. Velocity eases towards the input direction instead of jumping,
+// and mouse look is spread over a few frames.
 pub struct CameraController {
     speed: f32,
     sensitivity: f32,
-    amt_left: f32,
-    amt_right: f32,
-    amt_forward: f32,
-    amt_backward: f32,
-    amt_up: f32,
-    amt_down: f32,
+    // Rate (1/s) at which velocity approaches the target speed while a key is held
+    acceleration: f32,
+    // Rate (1/s) at which velocity decays once keys are released
+    damping: f32,
+    // Seconds for mouse look to catch up with ~63% of the movement, 0 disables smoothing
+    smoothing: f32,
+    sprint_multiplier: f32,
+    slow_multiplier: f32,
+    min_speed: f32,
+    max_speed: f32,
+    velocity: Vector3<f32>,
+    // Mouse movement not yet applied to the camera
     rotate_horizontal: f32,
     rotate_vertical: f32,
-    scroll: f32,
 }
 
 #[repr(C)]
@@ -77,19 +115,45 @@ impl Camera {
         }
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    pub fn set_orientation<Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(&mut self, yaw: Y, pitch: P) {
+        self.yaw = yaw.into();
+        self.pitch = pitch.into();
+        self.pitch.0 = self.pitch.0.clamp(-HALF_PI, HALF_PI);
+    }
+
+    // Turns the camera towards `target` without moving it
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.position;
+        if direction.magnitude2() > f32::EPSILON {
+            let direction = direction.normalize();
+            self.set_orientation(Rad(direction.z.atan2(direction.x)), Rad(direction.y.asin()));
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.forward().cross(Vector3::unit_y()).normalize()
+    }
 
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(
-                cos_pitch * cos_yaw,
-                sin_pitch,
-                cos_pitch * sin_yaw
-            ).normalize(),
-            Vector3::unit_y(),
-        )
+    pub fn up(&self) -> Vector3<f32> {
+        self.right().cross(self.forward())
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.forward(), Vector3::unit_y())
     }
 }
 
@@ -106,15 +170,124 @@ impl Projection {
             fovy: fovy.into(),
             znear,
             zfar,
+            reverse_z: false,
+            infinite_far: false,
         }
     }
 
+    pub fn reverse_z(mut self, enabled: bool) -> Self {
+        self.reverse_z = enabled;
+        self
+    }
+
+    pub fn infinite_far(mut self, enabled: bool) -> Self {
+        self.infinite_far = enabled;
+        self
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
     }
 
+    pub fn fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        self.fovy = fovy.into();
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        let projection = if self.infinite_far {
+            // Limit of the regular matrix as zfar goes to infinity
+            let f = 1.0 / (self.fovy.0 * 0.5).tan();
+            Matrix4::new(
+                f / self.aspect, 0.0, 0.0, 0.0,
+                0.0, f, 0.0, 0.0,
+                0.0, 0.0, -1.0, -1.0,
+                0.0, 0.0, -self.znear, 0.0,
+            )
+        } else {
+            OPENGL_TO_WGPU_MATRIX * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        };
+        if self.reverse_z {
+            REVERSE_Z_MATRIX * projection
+        } else {
+            projection
+        }
+    }
+}
+
+impl CameraProjection for Projection {
+    fn resize(&mut self, width: u32, height: u32) {
+        Projection::resize(self, width, height);
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        Projection::calc_matrix(self)
+    }
+
+    fn is_reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+}
+
+impl OrthographicProjection {
+    pub fn new(width: u32, height: u32, view_height: f32, znear: f32, zfar: f32) -> Self {
+        Self {
+            aspect: width as f32 / height as f32,
+            view_height,
+            znear,
+            zfar,
+            reverse_z: false,
+        }
+    }
+
+    pub fn reverse_z(mut self, enabled: bool) -> Self {
+        self.reverse_z = enabled;
+        self
+    }
+
+    pub fn view_height(&self) -> f32 {
+        self.view_height
+    }
+
+    pub fn set_view_height(&mut self, view_height: f32) {
+        self.view_height = view_height;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.aspect = width as f32 / height as f32;
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        let half_height = self.view_height * 0.5;
+        let half_width = half_height * self.aspect;
+        let projection = OPENGL_TO_WGPU_MATRIX
+            * cgmath::ortho(-half_width, half_width, -half_height, half_height, self.znear, self.zfar);
+        if self.reverse_z {
+            REVERSE_Z_MATRIX * projection
+        } else {
+            projection
+        }
+    }
+}
+
+impl CameraProjection for OrthographicProjection {
+    fn resize(&mut self, width: u32, height: u32) {
+        OrthographicProjection::resize(self, width, height);
+    }
+
+    fn calc_matrix(&self) -> Matrix4<f32> {
+        OrthographicProjection::calc_matrix(self)
+    }
+
+    fn is_reverse_z(&self) -> bool {
+        self.reverse_z
     }
 }
 
@@ -132,7 +305,7 @@ impl CameraUniform {
         }
     } 
 
-    pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
+    pub fn update_view_proj(&mut self, camera: &Camera, projection: &dyn CameraProjection) {
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
         self.view = camera.calc_matrix().into();
     }
@@ -143,97 +316,104 @@ impl CameraController {
         Self {
             speed,
             sensitivity,
-            amt_left: 0.0,
-            amt_right: 0.0,
-            amt_forward: 0.0,
-            amt_backward: 0.0,
-            amt_up: 0.0,
-            amt_down: 0.0,
+            acceleration: 10.0,
+            damping: 8.0,
+            smoothing: 0.03,
+            sprint_multiplier: 3.0,
+            slow_multiplier: 0.25,
+            min_speed: speed * 0.05,
+            max_speed: speed * 50.0,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
-            scroll: 0.0,
-        }
-    }
-
-    pub fn process_events(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state,
-                        physical_key: PhysicalKey::Code(keycode),
-                        ..
-                    },
-                ..
-            } => {
-                let is_pressed = if *state == ElementState::Pressed {1.0} else {0.0};
-                match keycode {
-                    KeyCode::KeyW | KeyCode::ArrowUp => {
-                        self.amt_forward = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyA | KeyCode::ArrowLeft => {
-                        self.amt_left = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyS | KeyCode::ArrowDown => {
-                        self.amt_backward = is_pressed;
-                        true
-                    }
-                    KeyCode::KeyD | KeyCode::ArrowRight => {
-                        self.amt_right = is_pressed;
-                        true
-                    }
-                    KeyCode::Space => {
-                        self.amt_up = is_pressed;
-                        true
-                    }
-                    KeyCode::ShiftLeft => {
-                        self.amt_down = is_pressed;
-                        true
-                    }
-                    _ => false,
-                }
-            } _ => false,
-        }
-    }
-
-    pub fn handle_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
-    }
-
-    pub fn handle_mouse_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = match delta {
-            // Assuming a line is about 100 pixels
-            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
-            MouseScrollDelta::PixelDelta(PhysicalPosition {
-                y: scroll,
-                ..
-            }) => *scroll as f32,
-        };
+        }
+    }
+
+    pub fn acceleration(mut self, acceleration: f32) -> Self {
+        self.acceleration = acceleration;
+        self
+    }
+
+    pub fn damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn smoothing(mut self, smoothing: f32) -> Self {
+        self.smoothing = smoothing;
+        self
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+    pub fn speed_multipliers(mut self, sprint: f32, slow: f32) -> Self {
+        self.sprint_multiplier = sprint;
+        self.slow_multiplier = slow;
+        self
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    // Moves along the `move_*` axes, looks around while `look` is held and the
+    // `zoom` axis (the wheel) changes the speed by 10% per line
+    pub fn update_camera(&mut self, camera: &mut Camera, input: &InputMap, dt: Duration) {
         let dt = dt.as_secs_f32();
+
+        let scroll = input.axis("zoom");
+        if scroll != 0.0 {
+            self.speed = (self.speed * 1.1f32.powf(scroll)).clamp(self.min_speed, self.max_speed);
+        }
+
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
         let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amt_forward - self.amt_backward) * self.speed * dt;
-        camera.position += right * (self.amt_right - self.amt_left) * self.speed * dt;
+        let mut direction = forward * input.axis("move_forward")
+            + right * input.axis("move_right")
+            + Vector3::unit_y() * input.axis("move_up");
+        if direction.magnitude2() > 1.0 {
+            direction = direction.normalize();
+        }
+        let multiplier = match (input.held("sprint"), input.held("slow")) {
+            (true, false) => self.sprint_multiplier,
+            (false, true) => self.slow_multiplier,
+            _ => 1.0,
+        };
+        let target = direction * self.speed * multiplier;
+        let rate = if direction.magnitude2() > 0.0 { self.acceleration } else { self.damping };
 
-        let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
-        let scrollward = Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
+        // Exact solution of dv/dt = rate * (target - v) over dt, so the path
+        // is the same no matter how the time is split into frames
+        let decay = (-rate * dt).exp();
+        let offset = self.velocity - target;
+        camera.position += target * dt + offset * ((1.0 - decay) / rate.max(f32::EPSILON));
+        self.velocity = target + offset * decay;
+        if self.velocity.magnitude2() < 1e-8 {
+            self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        }
 
-        camera.position.y += (self.amt_up - self.amt_down) * self.speed * dt;
+        if input.mouse_look() {
+            let (dx, dy) = input.mouse_delta();
+            self.rotate_horizontal += dx as f32;
+            self.rotate_vertical += dy as f32;
+        }
+        // Apply the same share of the pending mouse movement per unit of time
+        let consumed = if self.smoothing > 0.0 { 1.0 - (-dt / self.smoothing).exp() } else { 1.0 };
+        let horizontal = self.rotate_horizontal * consumed;
+        let vertical = self.rotate_vertical * consumed;
+        self.rotate_horizontal -= horizontal;
+        self.rotate_vertical -= vertical;
 
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        camera.yaw += Rad(horizontal * self.sensitivity * RADIANS_PER_PIXEL);
+        camera.pitch += Rad(-vertical * self.sensitivity * RADIANS_PER_PIXEL);
 
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+        // Sticks already move smoothly, they rotate at a rate instead of by a distance
+        let stick = self.sensitivity * STICK_RADIANS_PER_SECOND * dt;
+        camera.yaw += Rad(input.axis("look_x") * stick);
+        camera.pitch += Rad(input.axis("look_y") * stick);
 
         if camera.pitch < -Rad(HALF_PI) {
             camera.pitch = -Rad(HALF_PI);
@@ -242,3 +422,85 @@ impl CameraController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::{Binding, InputEvent};
+    use cgmath::Deg;
+    use winit::event::MouseButton;
+    use winit::keyboard::KeyCode;
+
+    const EPSILON: f32 = 1e-3;
+
+    // `count` steps of uneven length that add up to exactly `total`
+    fn uneven_steps(total: Duration, count: u32) -> Vec<Duration> {
+        let weights = (0..count).map(|i| (i % 3 + 1) as u128).collect::<Vec<_>>();
+        let sum = weights.iter().sum::<u128>();
+        let mut elapsed = 0;
+        let mut boundary = 0;
+        weights
+            .iter()
+            .map(|weight| {
+                elapsed += weight;
+                let next = total.as_nanos() * elapsed / sum;
+                let step = Duration::from_nanos((next - boundary) as u64);
+                boundary = next;
+                step
+            })
+            .collect()
+    }
+
+    // Holds forward for a second split into `held` steps, then lets go and
+    // coasts for 300 ms split into `coasting` steps
+    fn fly(held: u32, coasting: u32) -> (Camera, CameraController) {
+        let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(-90.0), Deg(0.0));
+        let mut controller = CameraController::new(4.0, 1.0);
+        let mut input = InputMap::default();
+        let phases = [(true, Duration::from_secs(1), held), (false, Duration::from_millis(300), coasting)];
+        for (pressed, duration, steps) in phases {
+            input.handle_event(&InputEvent::Button(Binding::Key(KeyCode::KeyW), pressed));
+            for dt in uneven_steps(duration, steps) {
+                controller.update_camera(&mut camera, &input, dt);
+                input.end_step();
+            }
+        }
+        (camera, controller)
+    }
+
+    #[test]
+    fn movement_is_frame_rate_independent() {
+        let (reference, reference_controller) = fly(1, 1);
+        assert!(reference.position.z < -1.0, "the camera should have moved forward");
+        assert!(reference_controller.velocity().magnitude() > 0.1, "the camera should still be coasting");
+        for (held, coasting) in [(60, 18), (144, 43)] {
+            let (camera, controller) = fly(held, coasting);
+            let position = (camera.position - reference.position).magnitude();
+            let velocity = (controller.velocity() - reference_controller.velocity()).magnitude();
+            assert!(position < EPSILON, "{} steps end {} away", held, position);
+            assert!(velocity < EPSILON, "{} steps end {} off in velocity", held, velocity);
+        }
+    }
+
+    #[test]
+    fn mouse_look_is_frame_rate_independent() {
+        let look = |steps: u32| {
+            let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(-90.0), Deg(0.0));
+            let mut controller = CameraController::new(4.0, 1.0).smoothing(0.2);
+            let mut input = InputMap::default();
+            input.handle_event(&InputEvent::Button(Binding::Mouse(MouseButton::Left), true));
+            input.handle_event(&InputEvent::MouseMotion(150.0, -40.0));
+            for dt in uneven_steps(Duration::from_secs(1), steps) {
+                controller.update_camera(&mut camera, &input, dt);
+                input.end_step();
+            }
+            (camera.yaw.0, camera.pitch.0)
+        };
+        let (yaw, pitch) = look(1);
+        for steps in [60, 144] {
+            let (other_yaw, other_pitch) = look(steps);
+            assert!((other_yaw - yaw).abs() < EPSILON, "{} steps: yaw {} vs {}", steps, other_yaw, yaw);
+            assert!((other_pitch - pitch).abs() < EPSILON, "{} steps: pitch {} vs {}", steps, other_pitch, pitch);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::BTreeMap;
+use cgmath::{Angle, Deg, EuclideanSpace, InnerSpace, Point3, Quaternion, Rad, Rotation, Rotation3, Vector3};
+use instant::Duration;
+use crate::camera::{Camera, Projection};
+
+// Everything needed to put the view back where it was
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+    pub fovy: Rad<f32>,
+}
+
+impl CameraPose {
+    pub fn capture(camera: &Camera, projection: &Projection) -> Self {
+        Self {
+            position: camera.position,
+            yaw: camera.yaw(),
+            pitch: camera.pitch(),
+            fovy: projection.fovy(),
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera, projection: &mut Projection) {
+        camera.position = self.position;
+        camera.set_orientation(self.yaw, self.pitch);
+        projection.set_fovy(self.fovy);
+    }
+
+    // Straight line between the poses, yaw the short way around
+    pub fn lerp(&self, other: &CameraPose, t: f32) -> Self {
+        let yaw = Rad(self.yaw.0 + (other.yaw - self.yaw).normalize_signed().0 * t);
+        Self {
+            position: self.position + (other.position - self.position) * t,
+            yaw,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            fovy: self.fovy + (other.fovy - self.fovy) * t,
+        }
+    }
+
+    // Rotation taking +x to the view direction, see `Camera::forward`
+    fn orientation(&self) -> Quaternion<f32> {
+        Quaternion::from_angle_y(-self.yaw) * Quaternion::from_angle_z(self.pitch)
+    }
+
+    fn from_orientation(position: Point3<f32>, orientation: Quaternion<f32>, fovy: Rad<f32>) -> Self {
+        let forward = orientation.rotate_vector(Vector3::unit_x()).normalize();
+        Self {
+            position,
+            yaw: Rad(forward.z.atan2(forward.x)),
+            pitch: Rad(forward.y.clamp(-1.0, 1.0).asin()),
+            fovy,
+        }
+    }
+
+    // `x y z yaw pitch fovy` with angles in degrees
+    pub fn to_line(self) -> String {
+        format!(
+            "{} {} {} {} {} {}",
+            self.position.x,
+            self.position.y,
+            self.position.z,
+            Deg::from(self.yaw).0,
+            Deg::from(self.pitch).0,
+            Deg::from(self.fovy).0,
+        )
+    }
+
+    pub fn parse(fields: &[&str]) -> anyhow::Result<Self> {
+        let [x, y, z, yaw, pitch, fovy] = fields else {
+            anyhow::bail!("expected `x y z yaw pitch fovy`, got {:?}", fields.join(" "));
+        };
+        Ok(Self {
+            position: Point3::new(x.parse()?, y.parse()?, z.parse()?),
+            yaw: Deg(yaw.parse::<f32>()?).into(),
+            pitch: Deg(pitch.parse::<f32>()?).into(),
+            fovy: Deg(fovy.parse::<f32>()?).into(),
+        })
+    }
+}
+
+// Numbered camera poses, stored one per line as `slot x y z yaw pitch fovy`
+#[derive(Debug, Default, Clone)]
+pub struct Bookmarks {
+    poses: BTreeMap<u8, CameraPose>,
+}
+
+impl Bookmarks {
+    pub fn get(&self, slot: u8) -> Option<&CameraPose> {
+        self.poses.get(&slot)
+    }
+
+    pub fn set(&mut self, slot: u8, pose: CameraPose) {
+        self.poses.insert(slot, pose);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.poses.is_empty()
+    }
+
+    // In slot order
+    pub fn iter(&self) -> impl Iterator<Item = (u8, &CameraPose)> {
+        self.poses.iter().map(|(slot, pose)| (*slot, pose))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut poses = BTreeMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let Some((slot, pose)) = fields.split_first() else {
+                continue;
+            };
+            let slot = slot.parse::<u8>().map_err(|e| anyhow::anyhow!("bookmark line {}: {}", number + 1, e))?;
+            let pose = CameraPose::parse(pose).map_err(|e| anyhow::anyhow!("bookmark line {}: {}", number + 1, e))?;
+            poses.insert(slot, pose);
+        }
+        Ok(Self { poses })
+    }
+
+    pub fn serialize(&self) -> String {
+        self.poses
+            .iter()
+            .map(|(slot, pose)| format!("{} {}\n", slot, pose.to_line()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe {
+    // Seconds from the start of the path
+    pub time: f32,
+    pub pose: CameraPose,
+}
+
+// Catmull-Rom spline through the keyframe positions, orientation slerped between keys
+#[derive(Debug, Default, Clone)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + std::ops::Add<Output = T> + std::ops::Sub<Output = T> + std::ops::Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0 + (p2 - p0) * t + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Keeps keyframes sorted by time
+    pub fn add(&mut self, time: f32, pose: CameraPose) {
+        let index = self.keyframes.partition_point(|key| key.time <= time);
+        self.keyframes.insert(index, Keyframe { time, pose });
+    }
+
+    // Visits the bookmarks in slot order, `seconds` apart
+    pub fn from_bookmarks(bookmarks: &Bookmarks, seconds: f32) -> Self {
+        let mut path = Self::new();
+        for (index, (_, pose)) in bookmarks.iter().enumerate() {
+            path.add(index as f32 * seconds, *pose);
+        }
+        path
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |key| key.time)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let keys = &self.keyframes;
+        let first = keys.first()?;
+        let last = keys.last()?;
+        if time <= first.time {
+            return Some(first.pose);
+        }
+        if time >= last.time {
+            return Some(last.pose);
+        }
+
+        let next = keys.partition_point(|key| key.time <= time);
+        let (i1, i2) = (next - 1, next);
+        // End keys are repeated so the curve still passes through them
+        let i0 = i1.saturating_sub(1);
+        let i3 = (i2 + 1).min(keys.len() - 1);
+        let span = keys[i2].time - keys[i1].time;
+        let t = if span > 0.0 { (time - keys[i1].time) / span } else { 1.0 };
+
+        let [p0, p1, p2, p3] = [i0, i1, i2, i3].map(|i| keys[i].pose.position.to_vec());
+        let position = Point3::from_vec(catmull_rom(p0, p1, p2, p3, t));
+        let fovy = Rad(catmull_rom(keys[i0].pose.fovy.0, keys[i1].pose.fovy.0, keys[i2].pose.fovy.0, keys[i3].pose.fovy.0, t));
+
+        let from = keys[i1].pose.orientation();
+        let mut to = keys[i2].pose.orientation();
+        // Take the short way around
+        if from.dot(to) < 0.0 {
+            to = -to;
+        }
+        Some(CameraPose::from_orientation(position, from.slerp(to, t), fovy))
+    }
+
+    // Poses at fixed frame times, one captured image each, see `State::capture_path`
+    pub fn frames(&self, fps: f32) -> anyhow::Result<impl Iterator<Item = CameraPose> + '_> {
+        if !(fps > 0.0 && fps.is_finite()) {
+            anyhow::bail!("capture frame rate must be positive, got {}", fps);
+        }
+        let count = (self.duration() * fps).floor() as usize + 1;
+        Ok((0..count).filter_map(move |frame| self.sample(frame as f32 / fps)))
+    }
+
+    // One `time x y z yaw pitch fovy` line per keyframe
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut path = Self::new();
+        for (number, line) in text.lines().enumerate() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let Some((time, pose)) = fields.split_first() else {
+                continue;
+            };
+            let time = time.parse::<f32>().map_err(|e| anyhow::anyhow!("path line {}: {}", number + 1, e))?;
+            let pose = CameraPose::parse(pose).map_err(|e| anyhow::anyhow!("path line {}: {}", number + 1, e))?;
+            path.add(time, pose);
+        }
+        Ok(path)
+    }
+
+    pub fn serialize(&self) -> String {
+        self.keyframes
+            .iter()
+            .map(|key| format!("{} {}\n", key.time, key.pose.to_line()))
+            .collect()
+    }
+}
+
+// Plays a path back in real time
+#[derive(Debug, Clone)]
+pub struct PathPlayback {
+    path: CameraPath,
+    time: f32,
+    pub looping: bool,
+    pub speed: f32,
+}
+
+impl PathPlayback {
+    pub fn new(path: CameraPath, looping: bool) -> Self {
+        Self { path, time: 0.0, looping, speed: 1.0 }
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.looping && self.time >= self.path.duration()
+    }
+
+    // Pose after moving `dt` along the path
+    pub fn advance(&mut self, dt: Duration) -> Option<CameraPose> {
+        let duration = self.path.duration();
+        self.time += dt.as_secs_f32() * self.speed;
+        if self.looping && duration > 0.0 {
+            self.time %= duration;
+        } else {
+            self.time = self.time.min(duration);
+        }
+        self.path.sample(self.time)
+    }
+}
@@ -0,0 +1,203 @@
+use cgmath::{InnerSpace, Point3, Rad, Vector3};
+use instant::Duration;
+use crate::camera::{Camera, CameraController, Projection, RADIANS_PER_PIXEL, STICK_RADIANS_PER_SECOND};
+use crate::input::InputMap;
+
+// Shared by every camera controller so `State` can swap them at runtime. Input
+// comes from the actions and axes of `InputMap`, see its default config.
+pub trait CameraControl {
+    fn update_camera(&mut self, camera: &mut Camera, projection: &Projection, input: &InputMap, dt: Duration);
+    // Moves the camera so the sphere fills the view
+    fn frame(&mut self, camera: &mut Camera, projection: &Projection, center: Point3<f32>, radius: f32);
+    // Picks up where the previous controller left the camera
+    fn sync(&mut self, _camera: &Camera) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControllerKind {
+    Fly,
+    Orbit,
+    FirstPerson,
+}
+
+impl ControllerKind {
+    pub fn next(self) -> Self {
+        match self {
+            ControllerKind::Fly => ControllerKind::Orbit,
+            ControllerKind::Orbit => ControllerKind::FirstPerson,
+            ControllerKind::FirstPerson => ControllerKind::Fly,
+        }
+    }
+
+    pub fn create(self, speed: f32, sensitivity: f32) -> Box<dyn CameraControl> {
+        match self {
+            ControllerKind::Fly => Box::new(CameraController::new(speed, sensitivity)),
+            ControllerKind::Orbit => Box::new(OrbitController::new(sensitivity)),
+            ControllerKind::FirstPerson => Box::new(FirstPersonController::new(speed, sensitivity)),
+        }
+    }
+}
+
+// Distance at which a sphere of `radius` fits inside both the vertical and horizontal field of view
+pub fn framing_distance(projection: &Projection, radius: f32) -> f32 {
+    let half_fovy = projection.fovy().0 * 0.5;
+    let half_fovx = (half_fovy.tan() * projection.aspect()).atan();
+    radius / half_fovy.min(half_fovx).sin()
+}
+
+impl CameraControl for CameraController {
+    fn update_camera(&mut self, camera: &mut Camera, _projection: &Projection, input: &InputMap, dt: Duration) {
+        CameraController::update_camera(self, camera, input, dt);
+    }
+
+    fn frame(&mut self, camera: &mut Camera, projection: &Projection, center: Point3<f32>, radius: f32) {
+        // Turn towards the center, then back off along the view direction
+        camera.look_at(center);
+        camera.position = center - camera.forward() * framing_distance(projection, radius);
+    }
+}
+
+// Rotates around a focus point. Dragging with `look` orbits, with `pan` pans and
+// the `zoom` axis zooms towards whatever is under the cursor.
+pub struct OrbitController {
+    sensitivity: f32,
+    target: Point3<f32>,
+    distance: f32,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+}
+
+impl OrbitController {
+    const MIN_DISTANCE: f32 = 0.05;
+
+    pub fn new(sensitivity: f32) -> Self {
+        Self {
+            sensitivity,
+            target: Point3::new(0.0, 0.0, 0.0),
+            distance: 10.0,
+            yaw: Rad(0.0),
+            pitch: Rad(0.0),
+        }
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Point3<f32>) {
+        self.target = target;
+    }
+
+    fn apply(&self, camera: &mut Camera) {
+        camera.set_orientation(self.yaw, self.pitch);
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+impl CameraControl for OrbitController {
+    fn update_camera(&mut self, camera: &mut Camera, projection: &Projection, input: &InputMap, dt: Duration) {
+        let stick = self.sensitivity * STICK_RADIANS_PER_SECOND * dt.as_secs_f32();
+        self.yaw += Rad(input.axis("look_x") * stick);
+        self.pitch += Rad(input.axis("look_y") * stick);
+
+        let (dx, dy) = input.mouse_delta();
+        let (dx, dy) = (dx as f32, dy as f32);
+        if input.mouse_look() {
+            // Mouse deltas are already per frame, scaling them by dt would make rotation framerate dependent
+            self.yaw += Rad(dx * self.sensitivity * RADIANS_PER_PIXEL);
+            self.pitch -= Rad(dy * self.sensitivity * RADIANS_PER_PIXEL);
+        }
+        self.pitch.0 = self.pitch.0.clamp(-1.55, 1.55);
+        self.apply(camera);
+
+        if input.held("pan") && !input.mouse_look() {
+            // One pixel of mouse movement moves the target about one pixel on screen
+            let half_height = self.distance * (projection.fovy().0 * 0.5).tan();
+            let pan_scale = half_height * 0.002 * self.sensitivity;
+            self.target += camera.up() * dy * pan_scale - camera.right() * dx * pan_scale;
+        }
+
+        let scroll = input.axis("zoom");
+        if scroll != 0.0 {
+            let factor = (-scroll * 0.1 * self.sensitivity).exp();
+            let cursor = input.cursor_ndc();
+            // Point under the cursor on the plane through the target facing the camera
+            let (tan_y, tan_x) = {
+                let tan = (projection.fovy().0 * 0.5).tan();
+                (tan * cursor.1, tan * projection.aspect() * cursor.0)
+            };
+            let ray = (camera.forward() + camera.right() * tan_x + camera.up() * tan_y).normalize();
+            let hit = camera.position + ray * (self.distance / ray.dot(camera.forward()));
+            // Keep `hit` at the same spot on screen while the distance shrinks
+            self.target += (hit - self.target) * (1.0 - factor);
+            self.distance = (self.distance * factor).max(Self::MIN_DISTANCE);
+        }
+        self.apply(camera);
+    }
+
+    fn frame(&mut self, camera: &mut Camera, projection: &Projection, center: Point3<f32>, radius: f32) {
+        self.target = center;
+        self.distance = framing_distance(projection, radius).max(Self::MIN_DISTANCE);
+        self.apply(camera);
+    }
+
+    fn sync(&mut self, camera: &Camera) {
+        // Orbit around the point the camera was looking at, keeping it in place
+        self.yaw = camera.yaw();
+        self.pitch = camera.pitch();
+        self.target = camera.position + camera.forward() * self.distance;
+    }
+}
+
+// Walks on a horizontal plane at a fixed eye height, no flying
+pub struct FirstPersonController {
+    speed: f32,
+    sensitivity: f32,
+    eye_height: f32,
+}
+
+impl FirstPersonController {
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        Self {
+            speed,
+            sensitivity,
+            eye_height: 1.7,
+        }
+    }
+}
+
+impl CameraControl for FirstPersonController {
+    fn update_camera(&mut self, camera: &mut Camera, _projection: &Projection, input: &InputMap, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        let (yaw_sin, yaw_cos) = camera.yaw().0.sin_cos();
+        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin);
+        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos);
+        camera.position += forward * input.axis("move_forward").clamp(-1.0, 1.0) * self.speed * dt;
+        camera.position += right * input.axis("move_right").clamp(-1.0, 1.0) * self.speed * dt;
+        camera.position.y = self.eye_height;
+
+        let (mut yaw, mut pitch) = (camera.yaw(), camera.pitch());
+        if input.mouse_look() {
+            let (dx, dy) = input.mouse_delta();
+            yaw += Rad(dx as f32 * self.sensitivity * RADIANS_PER_PIXEL);
+            pitch -= Rad(dy as f32 * self.sensitivity * RADIANS_PER_PIXEL);
+        }
+        let stick = self.sensitivity * STICK_RADIANS_PER_SECOND * dt;
+        yaw += Rad(input.axis("look_x") * stick);
+        pitch += Rad(input.axis("look_y") * stick);
+        camera.set_orientation(yaw, pitch);
+    }
+
+    fn frame(&mut self, camera: &mut Camera, projection: &Projection, center: Point3<f32>, radius: f32) {
+        // Step back on the ground plane, then look at the center
+        let (yaw_sin, yaw_cos) = camera.yaw().0.sin_cos();
+        let flat = Vector3::new(yaw_cos, 0.0, yaw_sin);
+        camera.position = center - flat * framing_distance(projection, radius);
+        camera.position.y = self.eye_height;
+        camera.look_at(center);
+    }
+
+    fn sync(&mut self, camera: &Camera) {
+        self.eye_height = camera.position.y;
+    }
+}
@@ -0,0 +1,349 @@
+use std::mem;
+use cgmath::prelude::*;
+use cgmath::{Matrix4, Point3, Vector3, Vector4};
+use instant::Duration;
+use crate::pipeline::{PipelineCache, RenderPipelineBuilder};
+use crate::shader::{ShaderCache, ShaderDefines, ShaderError};
+
+pub const SHADER: &str = "debug_lines.wgsl";
+const CIRCLE_SEGMENTS: usize = 32;
+
+pub const RED: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+pub const GREEN: [f32; 4] = [0.2, 1.0, 0.2, 1.0];
+pub const BLUE: [f32; 4] = [0.3, 0.4, 1.0, 1.0];
+pub const YELLOW: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+pub const GRAY: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DebugVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+}
+
+impl DebugVertex {
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<DebugVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+struct Item {
+    // Line list, two vertices per segment
+    vertices: Vec<DebugVertex>,
+    depth_test: bool,
+    // `None` lives for the current frame only
+    remaining: Option<Duration>,
+}
+
+// Options for the shape that was just added
+pub struct Shape<'a>(&'a mut Item);
+
+impl Shape<'_> {
+    // Keep drawing the shape for `duration` instead of a single frame
+    pub fn lifetime(self, duration: Duration) -> Self {
+        self.0.remaining = Some(duration);
+        self
+    }
+
+    // Hidden parts are drawn too when disabled
+    pub fn depth_test(self, enabled: bool) -> Self {
+        self.0.depth_test = enabled;
+        self
+    }
+}
+
+// Immediate-mode lines and wire shapes. Shapes can be added at any point
+// during `State::update` and are drawn over the scene that frame, or for as
+// long as their lifetime says.
+pub struct DebugDraw {
+    items: Vec<Item>,
+    depth_tested: wgpu::RenderPipeline,
+    overlay: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    // Vertex counts uploaded by `prepare`: depth tested first, then overlay
+    counts: (u32, u32),
+}
+
+impl DebugDraw {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_layout: &wgpu::BindGroupLayout,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<Self, ShaderError> {
+        let (depth_tested, overlay) = Self::create_pipelines(device, format, sample_count, camera_layout, shader_cache, pipeline_cache)?;
+        let capacity = 1024;
+        Ok(Self {
+            items: Vec::new(),
+            depth_tested,
+            overlay,
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            counts: (0, 0),
+        })
+    }
+
+    // Swaps in pipelines built from the current debug_lines.wgsl, queued shapes are kept
+    pub fn reload(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_layout: &wgpu::BindGroupLayout,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<(), ShaderError> {
+        (self.depth_tested, self.overlay) = Self::create_pipelines(device, format, sample_count, camera_layout, shader_cache, pipeline_cache)?;
+        Ok(())
+    }
+
+    // Depth tested and overlay pipelines
+    fn create_pipelines(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_layout: &wgpu::BindGroupLayout,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<(wgpu::RenderPipeline, wgpu::RenderPipeline), ShaderError> {
+        let shader = shader_cache.get_or_compile(device, SHADER, &ShaderDefines::new())?;
+        let buffers = [DebugVertex::desc()];
+        shader
+            .reflection
+            .validate_vertex_buffers("vs_main", &buffers)
+            .map_err(|errors| ShaderError::new(SHADER, errors.join("\n")))?;
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Debug draw pipeline layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+        let builder = RenderPipelineBuilder::new("Debug draw pipeline", &layout, &shader.module)
+            .buffers(&buffers)
+            .topology(wgpu::PrimitiveTopology::LineList)
+            .cull_mode(None)
+            .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+            .color_format(format)
+            .sample_count(sample_count)
+            .depth_write(false)
+            .depth_compare(wgpu::CompareFunction::LessEqual);
+        let depth_tested = pipeline_cache.get_or_build(device, &builder);
+        let overlay = pipeline_cache.get_or_build(device, &builder.depth_compare(wgpu::CompareFunction::Always));
+        Ok((depth_tested, overlay))
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Debug draw vertex buffer"),
+            size: (capacity * mem::size_of::<DebugVertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Drops single-frame shapes and ages the rest, call before adding new ones
+    pub fn begin_frame(&mut self, dt: Duration) {
+        self.items.retain_mut(|item| match item.remaining.as_mut() {
+            None => false,
+            Some(remaining) => {
+                *remaining = remaining.saturating_sub(dt);
+                !remaining.is_zero()
+            }
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    fn push(&mut self, vertices: Vec<DebugVertex>) -> Shape<'_> {
+        self.items.push(Item {
+            vertices,
+            depth_test: true,
+            remaining: None,
+        });
+        Shape(self.items.last_mut().unwrap())
+    }
+
+    pub fn line(&mut self, from: impl Into<Point3<f32>>, to: impl Into<Point3<f32>>, color: [f32; 4]) -> Shape<'_> {
+        self.push(segments(&[(from.into(), to.into())], color))
+    }
+
+    pub fn aabb(&mut self, min: impl Into<Point3<f32>>, max: impl Into<Point3<f32>>, color: [f32; 4]) -> Shape<'_> {
+        let (min, max) = (min.into(), max.into());
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: usize| {
+            Point3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        });
+        self.push(box_edges(&corners, color))
+    }
+
+    // Three great circles
+    pub fn sphere(&mut self, center: impl Into<Point3<f32>>, radius: f32, color: [f32; 4]) -> Shape<'_> {
+        let center = center.into();
+        let mut lines = Vec::with_capacity(CIRCLE_SEGMENTS * 3);
+        for (u, v) in [
+            (Vector3::unit_x(), Vector3::unit_y()),
+            (Vector3::unit_x(), Vector3::unit_z()),
+            (Vector3::unit_y(), Vector3::unit_z()),
+        ] {
+            lines.extend(circle(center, u * radius, v * radius));
+        }
+        self.push(segments(&lines, color))
+    }
+
+    pub fn arrow(&mut self, from: impl Into<Point3<f32>>, to: impl Into<Point3<f32>>, color: [f32; 4]) -> Shape<'_> {
+        let (from, to) = (from.into(), to.into());
+        let direction = to - from;
+        let length = direction.magnitude();
+        let mut lines = vec![(from, to)];
+        if length > f32::EPSILON {
+            let forward = direction / length;
+            // Any vector not parallel to `forward` gives a basis for the head
+            let up = if forward.y.abs() < 0.99 { Vector3::unit_y() } else { Vector3::unit_x() };
+            let side = forward.cross(up).normalize();
+            let up = side.cross(forward);
+            let head = length * 0.2;
+            let base = to - forward * head;
+            for offset in [side, -side, up, -up] {
+                lines.push((to, base + offset * head * 0.5));
+            }
+        }
+        self.push(segments(&lines, color))
+    }
+
+    // Edges of the volume a view-projection matrix sees, e.g. another camera's
+    // Needs a finite far plane, reverse-Z works as is
+    pub fn frustum(&mut self, view_proj: Matrix4<f32>, color: [f32; 4]) -> Shape<'_> {
+        let Some(inverse) = view_proj.invert() else {
+            return self.push(Vec::new());
+        };
+        // wgpu clip space: x/y in -1..1, z in 0..1
+        let corners = [0, 1, 2, 3, 4, 5, 6, 7].map(|i: usize| {
+            let ndc = Vector4::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { 0.0 } else { 1.0 },
+                1.0,
+            );
+            let world = inverse * ndc;
+            Point3::from_homogeneous(world)
+        });
+        self.push(box_edges(&corners, color))
+    }
+
+    // Square grid on the XZ plane through `center`
+    pub fn grid(&mut self, center: impl Into<Point3<f32>>, size: f32, divisions: u32, color: [f32; 4]) -> Shape<'_> {
+        let center = center.into();
+        let divisions = divisions.max(1);
+        let half = size * 0.5;
+        let step = size / divisions as f32;
+        let mut lines = Vec::with_capacity((divisions as usize + 1) * 2);
+        for i in 0..=divisions {
+            let offset = -half + step * i as f32;
+            lines.push((center + Vector3::new(offset, 0.0, -half), center + Vector3::new(offset, 0.0, half)));
+            lines.push((center + Vector3::new(-half, 0.0, offset), center + Vector3::new(half, 0.0, offset)));
+        }
+        self.push(segments(&lines, color))
+    }
+
+    // X, Y and Z axes of `transform` in red, green and blue
+    pub fn axes(&mut self, transform: Matrix4<f32>, size: f32) -> Shape<'_> {
+        let origin = transform.transform_point(Point3::origin());
+        let mut vertices = Vec::with_capacity(6);
+        for (axis, color) in [(Vector3::unit_x(), RED), (Vector3::unit_y(), GREEN), (Vector3::unit_z(), BLUE)] {
+            let end = transform.transform_point(Point3::from_vec(axis * size));
+            vertices.extend(segments(&[(origin, end)], color));
+        }
+        self.push(vertices)
+    }
+
+    // Uploads this frame's shapes, growing the vertex buffer when needed
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let mut vertices = Vec::new();
+        for depth_test in [true, false] {
+            for item in self.items.iter().filter(|item| item.depth_test == depth_test) {
+                vertices.extend_from_slice(&item.vertices);
+            }
+            if depth_test {
+                self.counts.0 = vertices.len() as u32;
+            }
+        }
+        self.counts.1 = vertices.len() as u32 - self.counts.0;
+
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        if !vertices.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&vertices));
+        }
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, camera_bind_group: &wgpu::BindGroup) {
+        let (depth_tested, overlay) = self.counts;
+        if depth_tested + overlay == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        if depth_tested > 0 {
+            render_pass.set_pipeline(&self.depth_tested);
+            render_pass.draw(0..depth_tested, 0..1);
+        }
+        if overlay > 0 {
+            render_pass.set_pipeline(&self.overlay);
+            render_pass.draw(depth_tested..depth_tested + overlay, 0..1);
+        }
+    }
+}
+
+fn segments(lines: &[(Point3<f32>, Point3<f32>)], color: [f32; 4]) -> Vec<DebugVertex> {
+    lines
+        .iter()
+        .flat_map(|(a, b)| [*a, *b])
+        .map(|p| DebugVertex { position: p.into(), color })
+        .collect()
+}
+
+// Corners indexed by bits: 1 = +x, 2 = +y, 4 = +z
+fn box_edges(corners: &[Point3<f32>; 8], color: [f32; 4]) -> Vec<DebugVertex> {
+    let mut lines = Vec::with_capacity(12);
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                lines.push((corners[i], corners[i | bit]));
+            }
+        }
+    }
+    segments(&lines, color)
+}
+
+fn circle(center: Point3<f32>, u: Vector3<f32>, v: Vector3<f32>) -> Vec<(Point3<f32>, Point3<f32>)> {
+    let point = |i: usize| {
+        let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+        center + u * angle.cos() + v * angle.sin()
+    };
+    (0..CIRCLE_SEGMENTS).map(|i| (point(i), point(i + 1))).collect()
+}
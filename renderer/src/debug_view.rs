@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use crate::instance::InstanceRaw;
+use crate::model::{ModelVertex, Vertex};
+use crate::pipeline::{PipelineCache, RenderPipelineBuilder};
+use crate::shader::{ShaderCache, ShaderDefines, ShaderError};
+
+pub const SHADER: &str = "debug.wgsl";
+
+// What the scene is rendered as. `Wireframe` is drawn over the lit scene, the
+// other views replace material shading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DebugView {
+    None,
+    Wireframe,
+    Normals,
+    Tangents,
+    Bitangents,
+    Uvs,
+    MipLevel,
+    Depth,
+    Overdraw,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 9] = [
+        DebugView::None,
+        DebugView::Wireframe,
+        DebugView::Normals,
+        DebugView::Tangents,
+        DebugView::Bitangents,
+        DebugView::Uvs,
+        DebugView::MipLevel,
+        DebugView::Depth,
+        DebugView::Overdraw,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|view| *view == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    // Views that keep the regular material pipelines
+    pub fn is_overlay(self) -> bool {
+        matches!(self, DebugView::None | DebugView::Wireframe)
+    }
+
+    fn define(self) -> Option<&'static str> {
+        match self {
+            DebugView::None => None,
+            DebugView::Wireframe => Some("DEBUG_WIREFRAME"),
+            DebugView::Normals => Some("DEBUG_NORMALS"),
+            DebugView::Tangents => Some("DEBUG_TANGENTS"),
+            DebugView::Bitangents => Some("DEBUG_BITANGENTS"),
+            DebugView::Uvs => Some("DEBUG_UVS"),
+            DebugView::MipLevel => Some("DEBUG_MIP_LEVEL"),
+            DebugView::Depth => Some("DEBUG_DEPTH"),
+            DebugView::Overdraw => Some("DEBUG_OVERDRAW"),
+        }
+    }
+}
+
+// Pipelines for every debug view, built from debug.wgsl against the model layout
+pub struct DebugViews {
+    pipelines: HashMap<DebugView, wgpu::RenderPipeline>,
+    // Without POLYGON_MODE_LINE the wireframe is drawn as filled triangles that
+    // keep only pixels near an edge, using de-indexed `Mesh::wireframe_buffer`s
+    barycentric_wireframe: bool,
+}
+
+impl DebugViews {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        layout: &wgpu::PipelineLayout,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<Self, ShaderError> {
+        let barycentric_wireframe = !device.features().contains(wgpu::Features::POLYGON_MODE_LINE);
+        let buffers = [ModelVertex::desc(), InstanceRaw::desc()];
+
+        let mut pipelines = HashMap::new();
+        for view in DebugView::ALL {
+            let Some(define) = view.define() else {
+                continue;
+            };
+            let mut defines = ShaderDefines::new();
+            defines.insert(define.to_string());
+            if view == DebugView::Wireframe && barycentric_wireframe {
+                defines.insert("WIREFRAME_BARYCENTRIC".to_string());
+            }
+            let shader = shader_cache.get_or_compile(device, SHADER, &defines)?;
+            shader
+                .reflection
+                .validate_vertex_buffers("vs_main", &buffers)
+                .map_err(|errors| ShaderError::new(SHADER, errors.join("\n")))?;
+
+            let mut builder = RenderPipelineBuilder::new("Debug view pipeline", layout, &shader.module)
+                .buffers(&buffers)
+                .sample_count(sample_count);
+            builder = match view {
+                // Drawn over the scene, pulled slightly towards the camera to win the depth test
+                DebugView::Wireframe => {
+                    let builder = builder
+                        .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+                        .color_format(format)
+                        .cull_mode(None)
+                        .depth_write(false)
+                        .depth_compare(wgpu::CompareFunction::LessEqual)
+                        .depth_bias(wgpu::DepthBiasState {
+                            constant: -2,
+                            slope_scale: -1.0,
+                            clamp: 0.0,
+                        });
+                    if barycentric_wireframe {
+                        builder
+                    } else {
+                        builder.polygon_mode(wgpu::PolygonMode::Line)
+                    }
+                }
+                // Every fragment adds up, hidden ones included
+                DebugView::Overdraw => {
+                    let additive = wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    };
+                    builder
+                        .blend(Some(wgpu::BlendState { color: additive, alpha: additive }))
+                        .color_format(format)
+                        .cull_mode(None)
+                        .depth_write(false)
+                        .depth_compare(wgpu::CompareFunction::Always)
+                }
+                _ => builder.color_format(format),
+            };
+            pipelines.insert(view, pipeline_cache.get_or_build(device, &builder));
+        }
+
+        Ok(Self { pipelines, barycentric_wireframe })
+    }
+
+    pub fn pipeline(&self, view: DebugView) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(&view)
+    }
+
+    pub fn barycentric_wireframe(&self) -> bool {
+        self.barycentric_wireframe
+    }
+}
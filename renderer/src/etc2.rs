@@ -0,0 +1,508 @@
+// CPU decompression of ETC2 and EAC blocks into RGBA8, used when the adapter
+// lacks TEXTURE_COMPRESSION_ETC2 (most desktop GPUs). Signed EAC decodes to
+// RGBA8 snorm bytes.
+
+use crate::bcn::{decompress_blocks, div_round};
+
+pub fn decompress(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Option<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+
+    let block_size = match format {
+        Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb | Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb | EacR11Unorm | EacR11Snorm => 8,
+        Etc2Rgba8Unorm | Etc2Rgba8UnormSrgb | EacRg11Unorm | EacRg11Snorm => 16,
+        _ => return None,
+    };
+    decompress_blocks(width, height, data, block_size, |block, texels| match format {
+        Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb => decode_color(block, false, texels),
+        Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb => decode_color(block, true, texels),
+        Etc2Rgba8Unorm | Etc2Rgba8UnormSrgb => {
+            decode_color(&block[8..], false, texels);
+            for (texel, a) in texels.iter_mut().zip(decode_eac(&block[..8], false)) {
+                texel[3] = a;
+            }
+        }
+        EacR11Unorm => {
+            for (texel, r) in texels.iter_mut().zip(decode_eac(block, true)) {
+                *texel = [r, 0, 0, 255];
+            }
+        }
+        EacRg11Unorm => {
+            let red = decode_eac(&block[..8], true);
+            let green = decode_eac(&block[8..], true);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                *texel = [red[i], green[i], 0, 255];
+            }
+        }
+        EacR11Snorm => {
+            for (texel, r) in texels.iter_mut().zip(decode_signed_eac(block)) {
+                *texel = [r as u8, 0, 0, 127];
+            }
+        }
+        EacRg11Snorm => {
+            let red = decode_signed_eac(&block[..8]);
+            let green = decode_signed_eac(&block[8..]);
+            for (i, texel) in texels.iter_mut().enumerate() {
+                *texel = [red[i] as u8, green[i] as u8, 0, 127];
+            }
+        }
+        _ => unreachable!(),
+    })
+}
+
+// Intensity modifiers of the individual and differential modes, the pixel index
+// picks +a, +b, -a or -b
+const MODIFIERS: [[i32; 2]; 8] = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]];
+// Distances of the T and H modes
+const DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+fn extend_4(value: u8) -> i32 {
+    (value as i32) * 17
+}
+
+fn extend_5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend_6(value: u8) -> i32 {
+    ((value as i32) << 2) | (value as i32 >> 4)
+}
+
+fn extend_7(value: u8) -> i32 {
+    ((value as i32) << 1) | (value as i32 >> 6)
+}
+
+fn offset(color: [i32; 3], amount: i32) -> [u8; 4] {
+    let channel = |c: i32| (c + amount).clamp(0, 255) as u8;
+    [channel(color[0]), channel(color[1]), channel(color[2]), 255]
+}
+
+// 8 byte ETC2 RGB block. With `punch_through` (RGB8A1) the differential bit
+// says whether the block is opaque, and index 2 is transparent in blocks that
+// aren't.
+fn decode_color(block: &[u8], punch_through: bool, texels: &mut [[u8; 4]; 16]) {
+    let bits = u64::from_be_bytes(block.try_into().unwrap());
+    let flag = block[3] & 2 != 0;
+    let opaque = !punch_through || flag;
+    let differential = punch_through || flag;
+
+    // Pixel indices are stored column by column, most significant bits first
+    let pixel_index = |texel: usize| -> usize {
+        let i = (texel % 4) * 4 + texel / 4;
+        ((((bits >> (16 + i)) & 1) << 1) | ((bits >> i) & 1)) as usize
+    };
+
+    if !differential {
+        let base = [
+            [extend_4(block[0] >> 4), extend_4(block[1] >> 4), extend_4(block[2] >> 4)],
+            [extend_4(block[0] & 0xF), extend_4(block[1] & 0xF), extend_4(block[2] & 0xF)],
+        ];
+        decode_subblocks(block, base, true, &pixel_index, texels);
+        return;
+    }
+
+    // Second base color as a signed 3 bit delta on the first. Deltas that leave
+    // the 5 bit range select the T, H and planar modes instead.
+    let second = |byte: u8| (byte >> 3) as i32 + (((byte & 7) as i32 ^ 4) - 4);
+    let (r, g, b) = (second(block[0]), second(block[1]), second(block[2]));
+
+    if !(0..32).contains(&r) {
+        // T mode
+        let c0 = [
+            extend_4(((block[0] >> 1) & 0xC) | (block[0] & 3)),
+            extend_4(block[1] >> 4),
+            extend_4(block[1] & 0xF),
+        ];
+        let c1 = [extend_4(block[2] >> 4), extend_4(block[2] & 0xF), extend_4(block[3] >> 4)];
+        let distance = DISTANCES[(((block[3] >> 1) & 6) | (block[3] & 1)) as usize];
+        let paint = [offset(c0, 0), offset(c1, distance), offset(c1, 0), offset(c1, -distance)];
+        decode_paint(paint, opaque, &pixel_index, texels);
+    } else if !(0..32).contains(&g) {
+        // H mode
+        let c0 = [
+            extend_4((block[0] >> 3) & 0xF),
+            extend_4(((block[0] & 7) << 1) | ((block[1] >> 4) & 1)),
+            extend_4((block[1] & 8) | ((block[1] & 3) << 1) | (block[2] >> 7)),
+        ];
+        let c1 = [
+            extend_4((block[2] >> 3) & 0xF),
+            extend_4(((block[2] & 7) << 1) | (block[3] >> 7)),
+            extend_4((block[3] >> 3) & 0xF),
+        ];
+        // The lowest distance bit is implied by the order of the two colors
+        let value = |c: [i32; 3]| (c[0] << 16) | (c[1] << 8) | c[2];
+        let order = (value(c0) >= value(c1)) as u8;
+        let distance = DISTANCES[((block[3] & 4) | ((block[3] & 1) << 1) | order) as usize];
+        let paint = [offset(c0, distance), offset(c0, -distance), offset(c1, distance), offset(c1, -distance)];
+        decode_paint(paint, opaque, &pixel_index, texels);
+    } else if !(0..32).contains(&b) {
+        decode_planar(block, texels);
+    } else {
+        let base = [
+            [extend_5((block[0] >> 3) as i32), extend_5((block[1] >> 3) as i32), extend_5((block[2] >> 3) as i32)],
+            [extend_5(r), extend_5(g), extend_5(b)],
+        ];
+        decode_subblocks(block, base, opaque, &pixel_index, texels);
+    }
+}
+
+// Individual and differential modes: two half blocks, side by side or stacked
+// when the flip bit is set, each with a base color and modifier table
+fn decode_subblocks(
+    block: &[u8],
+    base: [[i32; 3]; 2],
+    opaque: bool,
+    pixel_index: &dyn Fn(usize) -> usize,
+    texels: &mut [[u8; 4]; 16],
+) {
+    let flip = block[3] & 1 != 0;
+    let tables = [(block[3] >> 5) as usize, ((block[3] >> 2) & 7) as usize];
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let (x, y) = (texel % 4, texel / 4);
+        let subblock = if flip { y / 2 } else { x / 2 };
+        let [a, b] = MODIFIERS[tables[subblock]];
+        let index = pixel_index(texel);
+        *out = match index {
+            // Punch-through blocks drop the +a modifier and make index 2 transparent
+            0 if !opaque => offset(base[subblock], 0),
+            2 if !opaque => [0; 4],
+            0 => offset(base[subblock], a),
+            1 => offset(base[subblock], b),
+            2 => offset(base[subblock], -a),
+            _ => offset(base[subblock], -b),
+        };
+    }
+}
+
+// T and H modes index straight into four paint colors
+fn decode_paint(paint: [[u8; 4]; 4], opaque: bool, pixel_index: &dyn Fn(usize) -> usize, texels: &mut [[u8; 4]; 16]) {
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let index = pixel_index(texel);
+        *out = if index == 2 && !opaque { [0; 4] } else { paint[index] };
+    }
+}
+
+// Planar mode: a color gradient given at the origin and the right and bottom
+// edges, always opaque
+fn decode_planar(block: &[u8], texels: &mut [[u8; 4]; 16]) {
+    let origin = [
+        extend_6((block[0] >> 1) & 0x3F),
+        extend_7(((block[0] & 1) << 6) | ((block[1] >> 1) & 0x3F)),
+        extend_6(((block[1] & 1) << 5) | (block[2] & 0x18) | ((block[2] & 3) << 1) | (block[3] >> 7)),
+    ];
+    let horizontal = [
+        extend_6((((block[3] >> 2) & 0x1F) << 1) | (block[3] & 1)),
+        extend_7(block[4] >> 1),
+        extend_6(((block[4] & 1) << 5) | (block[5] >> 3)),
+    ];
+    let vertical = [
+        extend_6(((block[5] & 7) << 3) | (block[6] >> 5)),
+        extend_7(((block[6] & 0x1F) << 2) | (block[7] >> 6)),
+        extend_6(block[7] & 0x3F),
+    ];
+    for (texel, out) in texels.iter_mut().enumerate() {
+        let (x, y) = ((texel % 4) as i32, (texel / 4) as i32);
+        for channel in 0..3 {
+            let (o, h, v) = (origin[channel], horizontal[channel], vertical[channel]);
+            out[channel] = ((x * (h - o) + y * (v - o) + 4 * o + 2) >> 2).clamp(0, 255) as u8;
+        }
+        out[3] = 255;
+    }
+}
+
+// 8 byte EAC block, the alpha of ETC2 RGBA8 or an 11 bit R11/RG11 channel
+// (rounded to 8 bits here)
+fn decode_eac(block: &[u8], eleven_bit: bool) -> [u8; 16] {
+    let base = block[0] as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    eac_modifiers(block).map(|modifier| {
+        if eleven_bit {
+            // A zero multiplier means 1/8 at 11 bit precision
+            let scale = if multiplier == 0 { 1 } else { multiplier * 8 };
+            let value = (base * 8 + 4 + modifier * scale).clamp(0, 2047);
+            ((value * 255 + 1023) / 2047) as u8
+        } else {
+            (base + modifier * multiplier).clamp(0, 255) as u8
+        }
+    })
+}
+
+// Signed 11 bit R11/RG11 channel rounded to 8 bit snorm. Like signed BC4, a
+// base of -128 is treated as -127.
+fn decode_signed_eac(block: &[u8]) -> [i8; 16] {
+    let base = (block[0] as i8).max(-127) as i32;
+    let multiplier = (block[1] >> 4) as i32;
+    let scale = if multiplier == 0 { 1 } else { multiplier * 8 };
+    eac_modifiers(block).map(|modifier| {
+        let value = (base * 8 + modifier * scale).clamp(-1023, 1023);
+        div_round(value * 127, 1023) as i8
+    })
+}
+
+// Modifier of each row-major texel, looked up from the 3 bit indices that are
+// stored column by column
+fn eac_modifiers(block: &[u8]) -> [i32; 16] {
+    let table = EAC_MODIFIERS[(block[1] & 0xF) as usize];
+    let bits = u64::from_be_bytes(block.try_into().unwrap());
+
+    let mut out = [0; 16];
+    for (texel, modifier) in out.iter_mut().enumerate() {
+        let i = (texel % 4) * 4 + texel / 4;
+        *modifier = table[((bits >> (45 - 3 * i)) & 7) as usize];
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::TextureFormat::*;
+
+    // Expected texels were read back from Mesa's decoders unless noted. Decoders
+    // may round interpolated values differently, `tolerance` absorbs that.
+    fn assert_block(format: wgpu::TextureFormat, block: &[u8], expected: [[i32; 4]; 16], tolerance: i32) {
+        let signed = matches!(format, EacR11Snorm | EacRg11Snorm);
+        let decoded = decompress(format, 4, 4, block).unwrap();
+        for (i, (texel, expected)) in decoded.chunks(4).zip(expected).enumerate() {
+            let texel = texel.iter().map(|&c| if signed { c as i8 as i32 } else { c as i32 });
+            for (channel, (actual, expected)) in texel.zip(expected).enumerate() {
+                assert!(
+                    (actual - expected).abs() <= tolerance,
+                    "{format:?} texel {i} channel {channel}: {actual} != {expected}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn etc2_individual_mode() {
+        assert_block(
+            Etc2Rgb8Unorm,
+            &[0x72, 0x18, 0x28, 0x10, 0x1A, 0x18, 0xEC, 0x17],
+            [
+                [127, 25, 42, 255], [111, 9, 26, 255], [52, 154, 154, 255], [16, 118, 118, 255],
+                [127, 25, 42, 255], [121, 19, 36, 255], [16, 118, 118, 255], [94, 196, 196, 255],
+                [127, 25, 42, 255], [121, 19, 36, 255], [94, 196, 196, 255], [94, 196, 196, 255],
+                [117, 15, 32, 255], [121, 19, 36, 255], [0, 76, 76, 255], [94, 196, 196, 255],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn etc2_differential_mode() {
+        assert_block(
+            Etc2Rgb8Unorm,
+            &[0x81, 0x42, 0x27, 0x2B, 0xED, 0x9C, 0x95, 0xF4],
+            [
+                [137, 71, 38, 255], [115, 49, 16, 255], [115, 49, 16, 255], [149, 83, 50, 255],
+                [137, 71, 38, 255], [149, 83, 50, 255], [137, 71, 38, 255], [127, 61, 28, 255],
+                [111, 53, 0, 255], [169, 111, 53, 255], [111, 53, 0, 255], [131, 73, 15, 255],
+                [131, 73, 15, 255], [111, 53, 0, 255], [131, 73, 15, 255], [111, 53, 0, 255],
+            ],
+            0,
+        );
+    }
+
+    // Red overflows the differential range
+    #[test]
+    fn etc2_t_mode() {
+        assert_block(
+            Etc2Rgb8Unorm,
+            &[0xFB, 0x58, 0x8A, 0x93, 0x58, 0x1E, 0xBE, 0xF5],
+            [
+                [142, 176, 159, 255], [130, 164, 147, 255], [255, 85, 136, 255], [130, 164, 147, 255],
+                [136, 170, 153, 255], [142, 176, 159, 255], [142, 176, 159, 255], [142, 176, 159, 255],
+                [130, 164, 147, 255], [142, 176, 159, 255], [142, 176, 159, 255], [136, 170, 153, 255],
+                [136, 170, 153, 255], [142, 176, 159, 255], [130, 164, 147, 255], [142, 176, 159, 255],
+            ],
+            0,
+        );
+    }
+
+    // Green overflows the differential range
+    #[test]
+    fn etc2_h_mode() {
+        assert_block(
+            Etc2Rgb8Unorm,
+            &[0x81, 0xFB, 0x87, 0x7E, 0x54, 0x68, 0xC0, 0x55],
+            [
+                [0, 28, 232, 255], [0, 28, 232, 255], [23, 74, 255, 255], [23, 255, 255, 255],
+                [23, 74, 255, 255], [23, 255, 255, 255], [23, 74, 255, 255], [23, 74, 255, 255],
+                [0, 28, 232, 255], [0, 215, 232, 255], [23, 255, 255, 255], [0, 215, 232, 255],
+                [23, 255, 255, 255], [23, 74, 255, 255], [23, 74, 255, 255], [0, 28, 232, 255],
+            ],
+            0,
+        );
+    }
+
+    // Blue overflows the differential range
+    #[test]
+    fn etc2_planar_mode() {
+        assert_block(
+            Etc2Rgb8Unorm,
+            &[0x81, 0x42, 0xFB, 0xDA, 0x4D, 0x31, 0x95, 0x14],
+            [
+                [0, 195, 125, 255], [45, 165, 132, 255], [89, 136, 140, 255], [134, 106, 147, 255],
+                [12, 189, 114, 255], [57, 159, 121, 255], [101, 129, 129, 255], [146, 99, 136, 255],
+                [24, 182, 103, 255], [69, 152, 110, 255], [113, 123, 118, 255], [158, 93, 125, 255],
+                [36, 176, 92, 255], [81, 146, 99, 255], [125, 116, 107, 255], [170, 86, 114, 255],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn etc2_punch_through_is_transparent_at_index_2() {
+        assert_block(
+            Etc2Rgb8A1Unorm,
+            &[0x81, 0x42, 0x27, 0x2C, 0xF6, 0x91, 0x06, 0x94],
+            [
+                [0, 0, 0, 0], [115, 49, 16, 255], [140, 82, 24, 255], [0, 0, 0, 0],
+                [132, 66, 33, 255], [132, 66, 33, 255], [98, 40, 0, 255], [0, 0, 0, 0],
+                [149, 83, 50, 255], [132, 66, 33, 255], [98, 40, 0, 255], [0, 0, 0, 0],
+                [132, 66, 33, 255], [115, 49, 16, 255], [140, 82, 24, 255], [0, 0, 0, 0],
+            ],
+            0,
+        );
+        assert_block(
+            Etc2Rgb8A1Unorm,
+            &[0xFB, 0x96, 0x0B, 0x55, 0x0B, 0x33, 0xE3, 0x80],
+            [
+                [0, 0, 0, 0], [0, 0, 0, 0], [0, 171, 69, 255], [255, 153, 102, 255],
+                [0, 0, 0, 0], [0, 0, 0, 0], [0, 171, 69, 255], [16, 203, 101, 255],
+                [255, 153, 102, 255], [255, 153, 102, 255], [255, 153, 102, 255], [16, 203, 101, 255],
+                [255, 153, 102, 255], [16, 203, 101, 255], [0, 0, 0, 0], [16, 203, 101, 255],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn etc2_rgba8_alpha() {
+        assert_block(
+            Etc2Rgba8Unorm,
+            &[
+                0x83, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95,
+                0xB2, 0xAD, 0x2C, 0xB2, 0x8F, 0x00, 0x30, 0x5E,
+            ],
+            [
+                [205, 197, 65, 166], [255, 253, 121, 187], [180, 130, 0, 89], [255, 208, 68, 26],
+                [255, 253, 121, 110], [205, 197, 65, 145], [180, 130, 0, 187], [255, 208, 68, 187],
+                [255, 253, 121, 145], [255, 253, 121, 229], [180, 130, 0, 187], [216, 166, 26, 68],
+                [255, 253, 121, 89], [205, 197, 65, 89], [180, 130, 0, 110], [180, 130, 0, 166],
+            ],
+            0,
+        );
+    }
+
+    #[test]
+    fn unsigned_eac() {
+        assert_block(
+            EacR11Unorm,
+            &[0x83, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95],
+            [
+                [166, 0, 0, 255], [187, 0, 0, 255], [89, 0, 0, 255], [26, 0, 0, 255],
+                [110, 0, 0, 255], [145, 0, 0, 255], [187, 0, 0, 255], [187, 0, 0, 255],
+                [145, 0, 0, 255], [229, 0, 0, 255], [187, 0, 0, 255], [68, 0, 0, 255],
+                [89, 0, 0, 255], [89, 0, 0, 255], [110, 0, 0, 255], [166, 0, 0, 255],
+            ],
+            0,
+        );
+        assert_block(
+            EacR11Unorm,
+            &[0x83, 0x00, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95],
+            [
+                [132, 0, 0, 255], [132, 0, 0, 255], [130, 0, 0, 255], [129, 0, 0, 255],
+                [131, 0, 0, 255], [131, 0, 0, 255], [132, 0, 0, 255], [132, 0, 0, 255],
+                [131, 0, 0, 255], [133, 0, 0, 255], [132, 0, 0, 255], [130, 0, 0, 255],
+                [130, 0, 0, 255], [130, 0, 0, 255], [131, 0, 0, 255], [132, 0, 0, 255],
+            ],
+            0,
+        );
+        assert_block(
+            EacRg11Unorm,
+            &[
+                0x83, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95,
+                0x20, 0x08, 0x5C, 0xD0, 0xA3, 0x3C, 0x8C, 0x3B,
+            ],
+            [
+                [166, 31, 0, 255], [187, 32, 0, 255], [89, 32, 0, 255], [26, 33, 0, 255],
+                [110, 33, 0, 255], [145, 31, 0, 255], [187, 33, 0, 255], [187, 32, 0, 255],
+                [145, 32, 0, 255], [229, 32, 0, 255], [187, 32, 0, 255], [68, 33, 0, 255],
+                [89, 33, 0, 255], [89, 31, 0, 255], [110, 32, 0, 255], [166, 31, 0, 255],
+            ],
+            1,
+        );
+    }
+
+    // 0x80 as the base is read as -127
+    #[test]
+    fn signed_eac() {
+        assert_block(
+            EacR11Snorm,
+            &[0x83, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95],
+            [
+                [-89, 0, 0, 127], [-69, 0, 0, 127], [-127, 0, 0, 127], [-127, 0, 0, 127],
+                [-127, 0, 0, 127], [-110, 0, 0, 127], [-69, 0, 0, 127], [-69, 0, 0, 127],
+                [-110, 0, 0, 127], [-27, 0, 0, 127], [-69, 0, 0, 127], [-127, 0, 0, 127],
+                [-127, 0, 0, 127], [-127, 0, 0, 127], [-127, 0, 0, 127], [-89, 0, 0, 127],
+            ],
+            0,
+        );
+        assert_block(
+            EacR11Snorm,
+            &[0x80, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95],
+            [
+                [-91, 0, 0, 127], [-71, 0, 0, 127], [-127, 0, 0, 127], [-127, 0, 0, 127],
+                [-127, 0, 0, 127], [-112, 0, 0, 127], [-71, 0, 0, 127], [-71, 0, 0, 127],
+                [-112, 0, 0, 127], [-29, 0, 0, 127], [-71, 0, 0, 127], [-127, 0, 0, 127],
+                [-127, 0, 0, 127], [-127, 0, 0, 127], [-127, 0, 0, 127], [-91, 0, 0, 127],
+            ],
+            0,
+        );
+        assert_block(
+            EacR11Snorm,
+            &[0x83, 0x00, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95],
+            [
+                [-123, 0, 0, 127], [-123, 0, 0, 127], [-125, 0, 0, 127], [-126, 0, 0, 127],
+                [-124, 0, 0, 127], [-124, 0, 0, 127], [-123, 0, 0, 127], [-123, 0, 0, 127],
+                [-124, 0, 0, 127], [-122, 0, 0, 127], [-123, 0, 0, 127], [-125, 0, 0, 127],
+                [-125, 0, 0, 127], [-125, 0, 0, 127], [-124, 0, 0, 127], [-123, 0, 0, 127],
+            ],
+            1,
+        );
+        assert_block(
+            EacRg11Snorm,
+            &[
+                0x20, 0x08, 0x5C, 0xD0, 0xA3, 0x3C, 0x8C, 0x3B,
+                0x83, 0x70, 0xA2, 0x1D, 0x39, 0x3B, 0x07, 0x95,
+            ],
+            [
+                [31, -89, 0, 127], [32, -69, 0, 127], [31, -127, 0, 127], [33, -127, 0, 127],
+                [33, -127, 0, 127], [31, -110, 0, 127], [33, -69, 0, 127], [32, -69, 0, 127],
+                [31, -110, 0, 127], [32, -27, 0, 127], [31, -69, 0, 127], [33, -127, 0, 127],
+                [32, -127, 0, 127], [31, -127, 0, 127], [32, -127, 0, 127], [31, -89, 0, 127],
+            ],
+            0,
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use instant::{Duration, Instant};
+
+// Order `next_present_mode` cycles through
+const PRESENT_MODES: [wgpu::PresentMode; 4] = [
+    wgpu::PresentMode::AutoVsync,
+    wgpu::PresentMode::Fifo,
+    wgpu::PresentMode::Mailbox,
+    wgpu::PresentMode::Immediate,
+];
+
+pub fn next_present_mode(mode: wgpu::PresentMode) -> wgpu::PresentMode {
+    let index = PRESENT_MODES.iter().position(|m| *m == mode).map_or(0, |i| i + 1);
+    PRESENT_MODES[index % PRESENT_MODES.len()]
+}
+
+// `requested` if the surface supports it, otherwise the closest mode that it
+// does. The auto modes always work, wgpu resolves them itself.
+pub fn choose_present_mode(requested: wgpu::PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    use wgpu::PresentMode::*;
+    if matches!(requested, AutoVsync | AutoNoVsync) || supported.contains(&requested) {
+        return requested;
+    }
+    // Uncapped modes fall back to each other before giving up on low latency
+    let fallbacks: &[wgpu::PresentMode] = match requested {
+        Mailbox => &[Immediate, Fifo],
+        Immediate => &[Mailbox, Fifo],
+        FifoRelaxed => &[Fifo],
+        _ => &[],
+    };
+    // Fifo is the only mode every surface has to support
+    fallbacks.iter().copied().find(|mode| supported.contains(mode)).unwrap_or(Fifo)
+}
+
+// Waits between frames so the loop doesn't spin faster than needed. The event
+// loop sleeps until shortly before the deadline, the rest is spun away since
+// OS timers are often a millisecond or more late.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    target_fps: f64,
+    enabled: bool,
+    // Applied even when the limiter is disabled, nobody is watching
+    unfocused_fps: f64,
+    minimized_fps: f64,
+    focused: bool,
+    minimized: bool,
+    next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+    const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+    pub fn new(target_fps: f64) -> Self {
+        Self {
+            target_fps: target_fps.max(1.0),
+            enabled: false,
+            unfocused_fps: 15.0,
+            minimized_fps: 4.0,
+            focused: true,
+            minimized: false,
+            next_frame: None,
+        }
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn background_fps(mut self, unfocused_fps: f64, minimized_fps: f64) -> Self {
+        self.unfocused_fps = unfocused_fps.max(1.0);
+        self.minimized_fps = minimized_fps.max(1.0);
+        self
+    }
+
+    pub fn target_fps(&self) -> f64 {
+        self.target_fps
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: f64) {
+        self.target_fps = target_fps.max(1.0);
+        self.next_frame = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.next_frame = None;
+    }
+
+    pub fn set_focused(&mut self, focused: bool) {
+        self.focused = focused;
+        self.next_frame = None;
+    }
+
+    pub fn set_minimized(&mut self, minimized: bool) {
+        self.minimized = minimized;
+        self.next_frame = None;
+    }
+
+    // Minimized windows still update, they just have nothing to draw
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    // Time between frames, `None` when frames aren't limited
+    pub fn frame_time(&self) -> Option<Duration> {
+        let fps = if self.minimized {
+            self.minimized_fps
+        } else if !self.focused {
+            self.unfocused_fps
+        } else if self.enabled {
+            self.target_fps
+        } else {
+            return None;
+        };
+        Some(Duration::from_secs_f64(1.0 / fps))
+    }
+
+    // When to check for the next frame, `None` when frames aren't limited
+    pub fn wake_at(&self) -> Option<Instant> {
+        self.next_frame.map(|deadline| deadline - Self::SPIN_MARGIN)
+    }
+
+    // `None` when the next frame is due, otherwise when to check again
+    pub fn poll(&mut self, now: Instant) -> Option<Instant> {
+        let Some(frame_time) = self.frame_time() else {
+            self.next_frame = None;
+            return None;
+        };
+        let deadline = self.next_frame.unwrap_or(now);
+        if now + Self::SPIN_MARGIN < deadline {
+            return Some(deadline - Self::SPIN_MARGIN);
+        }
+        // Spinning would block the browser's event loop, its timers have to do
+        #[cfg(not(target_arch = "wasm32"))]
+        while Instant::now() < deadline {
+            std::thread::yield_now();
+        }
+        // A late frame moves the schedule instead of earning a burst of catch-up frames
+        self.next_frame = Some((deadline + frame_time).max(Instant::now()));
+        None
+    }
+}
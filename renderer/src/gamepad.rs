@@ -0,0 +1,256 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 15] = [
+        GamepadButton::South,
+        GamepadButton::East,
+        GamepadButton::North,
+        GamepadButton::West,
+        GamepadButton::LeftBumper,
+        GamepadButton::RightBumper,
+        GamepadButton::Select,
+        GamepadButton::Start,
+        GamepadButton::Mode,
+        GamepadButton::LeftThumb,
+        GamepadButton::RightThumb,
+        GamepadButton::DPadUp,
+        GamepadButton::DPadDown,
+        GamepadButton::DPadLeft,
+        GamepadButton::DPadRight,
+    ];
+}
+
+// Sticks are -1..1 with y up, triggers 0..1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftX,
+    LeftY,
+    RightX,
+    RightY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    pub const ALL: [GamepadAxis; 6] = [
+        GamepadAxis::LeftX,
+        GamepadAxis::LeftY,
+        GamepadAxis::RightX,
+        GamepadAxis::RightY,
+        GamepadAxis::LeftTrigger,
+        GamepadAxis::RightTrigger,
+    ];
+
+    // The other half of a stick, triggers have none
+    pub fn pair(self) -> Option<GamepadAxis> {
+        match self {
+            GamepadAxis::LeftX => Some(GamepadAxis::LeftY),
+            GamepadAxis::LeftY => Some(GamepadAxis::LeftX),
+            GamepadAxis::RightX => Some(GamepadAxis::RightY),
+            GamepadAxis::RightY => Some(GamepadAxis::RightX),
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => None,
+        }
+    }
+}
+
+// Raw input from a controller, before dead zones and response curves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    Button(GamepadButton, bool),
+    Axis(GamepadAxis, f32),
+    // Everything returns to rest
+    Disconnected,
+}
+
+// How raw stick and trigger values become axis values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadSettings {
+    // Values below this are treated as zero, sticks use the radial magnitude
+    pub dead_zone: f32,
+    // Values above this count as fully deflected
+    pub outer_dead_zone: f32,
+    // Applied to the rescaled magnitude, above 1 gives finer control near the center
+    pub exponent: f32,
+}
+
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.15,
+            outer_dead_zone: 0.95,
+            exponent: 2.0,
+        }
+    }
+}
+
+impl GamepadSettings {
+    fn response(&self, magnitude: f32) -> f32 {
+        let range = (self.outer_dead_zone - self.dead_zone).max(f32::EPSILON);
+        let rescaled = ((magnitude - self.dead_zone) / range).clamp(0.0, 1.0);
+        rescaled.powf(self.exponent)
+    }
+
+    // Radial dead zone, so diagonals keep their direction and the stick has no square corners
+    pub fn apply_stick(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude <= self.dead_zone {
+            return (0.0, 0.0);
+        }
+        let scale = self.response(magnitude) / magnitude;
+        (x * scale, y * scale)
+    }
+
+    pub fn apply_trigger(&self, value: f32) -> f32 {
+        if value <= self.dead_zone {
+            return 0.0;
+        }
+        self.response(value)
+    }
+}
+
+// Anything that produces gamepad events, polled once per frame. Events are
+// handed back instead of applied so they can be recorded on the way in.
+pub trait GamepadSource {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>);
+}
+
+// Synthetic controller for headless runs and tests, events are queued and
+// delivered on the next `poll` exactly like a hardware backend would
+#[derive(Debug, Default, Clone)]
+pub struct VirtualGamepad {
+    events: Vec<GamepadEvent>,
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn press(&mut self, button: GamepadButton) {
+        self.events.push(GamepadEvent::Button(button, true));
+    }
+
+    pub fn release(&mut self, button: GamepadButton) {
+        self.events.push(GamepadEvent::Button(button, false));
+    }
+
+    pub fn set_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.events.push(GamepadEvent::Axis(axis, value));
+    }
+
+    pub fn set_stick(&mut self, x_axis: GamepadAxis, x: f32, y: f32) {
+        self.set_axis(x_axis, x);
+        if let Some(y_axis) = x_axis.pair() {
+            self.set_axis(y_axis, y);
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        self.events.push(GamepadEvent::Disconnected);
+    }
+}
+
+impl GamepadSource for VirtualGamepad {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        events.append(&mut self.events);
+    }
+}
+
+// Hardware controllers through gilrs. All connected pads feed the same input.
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+pub struct GilrsGamepad {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+impl GilrsGamepad {
+    pub fn new() -> anyhow::Result<Self> {
+        // Dead zones are applied by `GamepadSettings`, gilrs' own filter would stack with them
+        let gilrs = gilrs::GilrsBuilder::new()
+            .with_default_filters(false)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Unable to initialize gamepads: {}", e))?;
+        for (_, gamepad) in gilrs.gamepads() {
+            log::info!("Gamepad connected: {}", gamepad.name());
+        }
+        Ok(Self { gilrs })
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton> {
+        Some(match button {
+            gilrs::Button::South => GamepadButton::South,
+            gilrs::Button::East => GamepadButton::East,
+            gilrs::Button::North => GamepadButton::North,
+            gilrs::Button::West => GamepadButton::West,
+            gilrs::Button::LeftTrigger => GamepadButton::LeftBumper,
+            gilrs::Button::RightTrigger => GamepadButton::RightBumper,
+            gilrs::Button::Select => GamepadButton::Select,
+            gilrs::Button::Start => GamepadButton::Start,
+            gilrs::Button::Mode => GamepadButton::Mode,
+            gilrs::Button::LeftThumb => GamepadButton::LeftThumb,
+            gilrs::Button::RightThumb => GamepadButton::RightThumb,
+            gilrs::Button::DPadUp => GamepadButton::DPadUp,
+            gilrs::Button::DPadDown => GamepadButton::DPadDown,
+            gilrs::Button::DPadLeft => GamepadButton::DPadLeft,
+            gilrs::Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+        Some(match axis {
+            gilrs::Axis::LeftStickX => GamepadAxis::LeftX,
+            gilrs::Axis::LeftStickY => GamepadAxis::LeftY,
+            gilrs::Axis::RightStickX => GamepadAxis::RightX,
+            gilrs::Axis::RightStickY => GamepadAxis::RightY,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+impl GamepadSource for GilrsGamepad {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        while let Some(gilrs::Event { event, .. }) = self.gilrs.next_event() {
+            let event = match event {
+                gilrs::EventType::ButtonPressed(button, _) => Self::button(button).map(|b| GamepadEvent::Button(b, true)),
+                gilrs::EventType::ButtonReleased(button, _) => Self::button(button).map(|b| GamepadEvent::Button(b, false)),
+                // The analog triggers report as buttons with a value
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    Some(GamepadEvent::Axis(GamepadAxis::LeftTrigger, value))
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    Some(GamepadEvent::Axis(GamepadAxis::RightTrigger, value))
+                }
+                gilrs::EventType::AxisChanged(axis, value, _) => Self::axis(axis).map(|a| GamepadEvent::Axis(a, value)),
+                gilrs::EventType::Connected => {
+                    log::info!("Gamepad connected");
+                    None
+                }
+                gilrs::EventType::Disconnected => {
+                    log::info!("Gamepad disconnected");
+                    Some(GamepadEvent::Disconnected)
+                }
+                _ => None,
+            };
+            events.extend(event);
+        }
+    }
+}
@@ -0,0 +1,830 @@
+use std::collections::{HashMap, HashSet};
+use crate::gamepad::{GamepadAxis, GamepadButton, GamepadEvent, GamepadSettings};
+use winit::{
+    dpi::PhysicalPosition,
+    event::*,
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+// A physical input that can drive an action or axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    Gamepad(GamepadButton),
+    // Only meaningful for axes, contributes the processed stick or trigger value
+    GamepadAxis(GamepadAxis),
+}
+
+macro_rules! key_names {
+    ($($key:ident),* $(,)?) => {
+        fn key_from_name(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($key) => Some(KeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_names!(
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+    KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    ArrowUp, ArrowDown, ArrowLeft, ArrowRight,
+    Space, Tab, Enter, Escape, Backspace, Delete, Insert, Home, End, PageUp, PageDown,
+    ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+    Minus, Equal, BracketLeft, BracketRight, Semicolon, Quote, Comma, Period, Slash, Backslash, Backquote,
+    Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide, NumpadEnter,
+);
+
+impl Binding {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "MouseLeft" => Some(Binding::Mouse(MouseButton::Left)),
+            "MouseRight" => Some(Binding::Mouse(MouseButton::Right)),
+            "MouseMiddle" => Some(Binding::Mouse(MouseButton::Middle)),
+            "MouseBack" => Some(Binding::Mouse(MouseButton::Back)),
+            "MouseForward" => Some(Binding::Mouse(MouseButton::Forward)),
+            "WheelUp" => Some(Binding::WheelUp),
+            "WheelDown" => Some(Binding::WheelDown),
+            _ => {
+                if let Some(id) = name.strip_prefix("Mouse").and_then(|id| id.parse().ok()) {
+                    return Some(Binding::Mouse(MouseButton::Other(id)));
+                }
+                if let Some(pad) = name.strip_prefix("Pad") {
+                    let button = GamepadButton::ALL.into_iter().find(|b| format!("{:?}", b) == pad);
+                    let axis = GamepadAxis::ALL.into_iter().find(|a| format!("{:?}", a) == pad);
+                    return button.map(Binding::Gamepad).or(axis.map(Binding::GamepadAxis));
+                }
+                key_from_name(name).map(Binding::Key)
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(code) => format!("{:?}", code),
+            Binding::Mouse(MouseButton::Left) => "MouseLeft".to_string(),
+            Binding::Mouse(MouseButton::Right) => "MouseRight".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "MouseMiddle".to_string(),
+            Binding::Mouse(MouseButton::Back) => "MouseBack".to_string(),
+            Binding::Mouse(MouseButton::Forward) => "MouseForward".to_string(),
+            Binding::Mouse(MouseButton::Other(id)) => format!("Mouse{}", id),
+            Binding::WheelUp => "WheelUp".to_string(),
+            Binding::WheelDown => "WheelDown".to_string(),
+            Binding::Gamepad(button) => format!("Pad{:?}", button),
+            Binding::GamepadAxis(axis) => format!("Pad{:?}", axis),
+        }
+    }
+}
+
+// Everything `InputMap` reacts to, reduced from winit and gamepad events to
+// plain values so a session can be written out and fed back in, see `replay`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    // Keys, mouse and gamepad buttons going down or up, without key repeats
+    Button(Binding, bool),
+    // Lines scrolled, positive is away from the user
+    Wheel(f32),
+    // Physical pixels from the top left of the window
+    Cursor(f64, f64),
+    // Raw device motion, scaled to logical pixels by `InputMap`
+    MouseMotion(f64, f64),
+    ScaleFactor(f64),
+    Focused(bool),
+    GamepadAxis(GamepadAxis, f32),
+    GamepadDisconnected,
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        Some(match event {
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { physical_key: PhysicalKey::Code(code), state, repeat: false, .. },
+                ..
+            } => InputEvent::Button(Binding::Key(*code), state.is_pressed()),
+            WindowEvent::MouseInput { button, state, .. } => InputEvent::Button(Binding::Mouse(*button), state.is_pressed()),
+            WindowEvent::MouseWheel { delta, .. } => InputEvent::Wheel(match delta {
+                MouseScrollDelta::LineDelta(_, lines) => *lines,
+                // Assuming a line is about 100 pixels
+                MouseScrollDelta::PixelDelta(PhysicalPosition { y, .. }) => *y as f32 / 100.0,
+            }),
+            WindowEvent::CursorMoved { position, .. } => InputEvent::Cursor(position.x, position.y),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => InputEvent::ScaleFactor(*scale_factor),
+            WindowEvent::Focused(focused) => InputEvent::Focused(*focused),
+            _ => return None,
+        })
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<Self> {
+        match event {
+            DeviceEvent::MouseMotion { delta } => Some(InputEvent::MouseMotion(delta.0, delta.1)),
+            _ => None,
+        }
+    }
+
+    // One event per line, e.g. `down KeyW` or `motion 3 -1.5`
+    pub fn to_line(&self) -> String {
+        match self {
+            InputEvent::Button(binding, true) => format!("down {}", binding.name()),
+            InputEvent::Button(binding, false) => format!("up {}", binding.name()),
+            InputEvent::Wheel(lines) => format!("wheel {}", lines),
+            InputEvent::Cursor(x, y) => format!("cursor {} {}", x, y),
+            InputEvent::MouseMotion(dx, dy) => format!("motion {} {}", dx, dy),
+            InputEvent::ScaleFactor(scale_factor) => format!("scale {}", scale_factor),
+            InputEvent::Focused(focused) => format!("focus {}", focused),
+            InputEvent::GamepadAxis(axis, value) => format!("pad_axis {} {}", Binding::GamepadAxis(*axis).name(), value),
+            InputEvent::GamepadDisconnected => "pad_disconnected".to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> anyhow::Result<Self> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        let binding = |name: &str| Binding::parse(name).ok_or_else(|| anyhow::anyhow!("unknown binding {:?}", name));
+        Ok(match fields[..] {
+            ["down", name] => InputEvent::Button(binding(name)?, true),
+            ["up", name] => InputEvent::Button(binding(name)?, false),
+            ["wheel", lines] => InputEvent::Wheel(lines.parse()?),
+            ["cursor", x, y] => InputEvent::Cursor(x.parse()?, y.parse()?),
+            ["motion", dx, dy] => InputEvent::MouseMotion(dx.parse()?, dy.parse()?),
+            ["scale", scale_factor] => InputEvent::ScaleFactor(scale_factor.parse()?),
+            ["focus", focused] => InputEvent::Focused(focused.parse()?),
+            ["pad_axis", name, value] => match binding(name)? {
+                Binding::GamepadAxis(axis) => InputEvent::GamepadAxis(axis, value.parse()?),
+                _ => anyhow::bail!("{:?} is not a gamepad axis", name),
+            },
+            ["pad_disconnected"] => InputEvent::GamepadDisconnected,
+            _ => anyhow::bail!("unknown input event {:?}", line.trim()),
+        })
+    }
+}
+
+impl From<GamepadEvent> for InputEvent {
+    fn from(event: GamepadEvent) -> Self {
+        match event {
+            GamepadEvent::Button(button, pressed) => InputEvent::Button(Binding::Gamepad(button), pressed),
+            GamepadEvent::Axis(axis, value) => InputEvent::GamepadAxis(axis, value),
+            GamepadEvent::Disconnected => InputEvent::GamepadDisconnected,
+        }
+    }
+}
+
+// Bindings used when there is no config file, and for actions it leaves out.
+// store_bookmark is held with a digit, so it gets modifiers nothing else uses.
+const DEFAULT_CONFIG: &str = "\
+action exit = Escape
+action next_pipeline = Tab
+action toggle_transparency = KeyT
+action next_debug_view = KeyV
+action toggle_debug_shapes = KeyG
+action next_controller = KeyC PadSelect
+action frame_scene = KeyF PadNorth
+action toggle_orthographic = KeyO
+action toggle_mouse_capture = KeyM
+action play_camera_path = KeyP PadStart
+action store_bookmark = ControlRight ShiftRight
+action toggle_pause = F5
+action single_step = F6
+action slower = BracketLeft
+action faster = BracketRight
+action next_present_mode = F7
+action toggle_frame_limit = F8
+action bookmark_1 = Digit1
+action bookmark_2 = Digit2
+action bookmark_3 = Digit3
+action bookmark_4 = Digit4
+action bookmark_5 = Digit5
+action bookmark_6 = Digit6
+action bookmark_7 = Digit7
+action bookmark_8 = Digit8
+action bookmark_9 = Digit9
+action look = MouseLeft
+action pan = MouseRight MouseMiddle
+action sprint = ControlLeft PadLeftThumb
+action slow = AltLeft PadRightThumb
+axis move_forward = KeyW ArrowUp -KeyS -ArrowDown PadLeftY
+axis move_right = KeyD ArrowRight -KeyA -ArrowLeft PadLeftX
+axis move_up = Space -ShiftLeft PadRightTrigger -PadLeftTrigger
+axis zoom = WheelUp -WheelDown
+axis look_x = PadRightX
+axis look_y = PadRightY
+";
+
+// Named actions and axes bound to keys, mouse buttons and the wheel. Events are
+// fed in as they arrive, queries answer for the current frame until `end_frame`
+// and, for the wheel and mouse movement, the next simulation step until `end_step`.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    // Each binding contributes its scale while held, wheel bindings per line scrolled
+    axes: HashMap<String, Vec<(Binding, f32)>>,
+    down: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    wheel: f32,
+    mouse_delta: (f64, f64),
+    cursor: PhysicalPosition<f64>,
+    size: (u32, u32),
+    // Mouse deltas are divided by this so sensitivity is per logical pixel
+    scale_factor: f64,
+    mouse_captured: bool,
+    // Raw values, dead zones and curves are applied when queried
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    pub gamepad_settings: GamepadSettings,
+    // The next binding pressed replaces the bindings of this action
+    rebinding: Option<String>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.load_config(DEFAULT_CONFIG).expect("default input config is valid");
+        map
+    }
+}
+
+impl InputMap {
+    pub fn empty() -> Self {
+        Self {
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            wheel: 0.0,
+            mouse_delta: (0.0, 0.0),
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            size: (1, 1),
+            scale_factor: 1.0,
+            mouse_captured: false,
+            gamepad_axes: HashMap::new(),
+            gamepad_settings: GamepadSettings::default(),
+            rebinding: None,
+        }
+    }
+
+    // Defaults overridden by `config`, see `load_config`
+    pub fn from_config(config: &str) -> anyhow::Result<Self> {
+        let mut map = Self::default();
+        map.load_config(config)?;
+        Ok(map)
+    }
+
+    // One binding per line:
+    //   action <name> = <binding> <binding> ...
+    //   axis <name> = <binding> -<binding> ...
+    // A `-` prefix makes the binding push the axis negative. Lines replace
+    // earlier bindings of the same name, `#` starts a comment. Bindings shared
+    // between actions and axes are allowed but warned about.
+    pub fn load_config(&mut self, config: &str) -> anyhow::Result<()> {
+        for (number, line) in config.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |message: String| anyhow::anyhow!("input config line {}: {}", number + 1, message);
+            let Some((head, bindings)) = line.split_once('=') else {
+                return Err(error("expected `action|axis <name> = <bindings>`".to_string()));
+            };
+            let (kind, name) = match head.split_whitespace().collect::<Vec<_>>()[..] {
+                [kind, name] => (kind, name.to_string()),
+                _ => return Err(error(format!("expected `action|axis <name>`, got {:?}", head.trim()))),
+            };
+            let mut parsed = Vec::new();
+            for token in bindings.split_whitespace() {
+                let (scale, binding_name) = match token.strip_prefix('-') {
+                    Some(rest) => (-1.0, rest),
+                    None => (1.0, token),
+                };
+                let binding = Binding::parse(binding_name).ok_or_else(|| error(format!("unknown binding {:?}", binding_name)))?;
+                parsed.push((binding, scale));
+            }
+            match kind {
+                "action" => {
+                    self.actions.insert(name, parsed.into_iter().map(|(binding, _)| binding).collect());
+                }
+                "axis" => {
+                    self.axes.insert(name, parsed);
+                }
+                _ => return Err(error(format!("unknown kind {:?}", kind))),
+            }
+        }
+        self.warn_conflicts();
+        Ok(())
+    }
+
+    // Bindings that drive more than one action or axis, with their names sorted.
+    // Holding one of them triggers all of them, e.g. a sprint modifier that also
+    // stores bookmarks.
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<&str>)> {
+        let mut users: HashMap<Binding, Vec<&str>> = HashMap::new();
+        let actions = self.actions.iter().flat_map(|(name, bindings)| bindings.iter().map(move |b| (*b, name.as_str())));
+        let axes = self.axes.iter().flat_map(|(name, bindings)| bindings.iter().map(move |(b, _)| (*b, name.as_str())));
+        for (binding, name) in actions.chain(axes) {
+            let names = users.entry(binding).or_default();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let mut conflicts = users
+            .into_iter()
+            .filter(|(_, names)| names.len() > 1)
+            .map(|(binding, mut names)| {
+                names.sort();
+                (binding, names)
+            })
+            .collect::<Vec<_>>();
+        conflicts.sort_by_key(|(binding, _)| binding.name());
+        conflicts
+    }
+
+    fn warn_conflicts(&self) {
+        for (binding, names) in self.conflicts() {
+            log::warn!("{} is bound to {}, holding it triggers all of them", binding.name(), names.join(", "));
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut lines = Vec::new();
+        for (name, bindings) in &self.actions {
+            let bindings = bindings.iter().map(Binding::name).collect::<Vec<_>>().join(" ");
+            lines.push(format!("action {} = {}", name, bindings));
+        }
+        for (name, bindings) in &self.axes {
+            let bindings = bindings
+                .iter()
+                .map(|(binding, scale)| if *scale < 0.0 { format!("-{}", binding.name()) } else { binding.name() })
+                .collect::<Vec<_>>()
+                .join(" ");
+            lines.push(format!("axis {} = {}", name, bindings));
+        }
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.actions.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.actions.get_mut(action) {
+            bindings.retain(|b| *b != binding);
+        }
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: Binding, scale: f32) {
+        let bindings = self.axes.entry(axis.to_string()).or_default();
+        bindings.retain(|(b, _)| *b != binding);
+        bindings.push((binding, scale));
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    // The next key, button or wheel step becomes the only binding of `action`
+    pub fn start_rebind(&mut self, action: &str) {
+        self.rebinding = Some(action.to_string());
+    }
+
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width.max(1), height.max(1));
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor.max(f64::EPSILON);
+    }
+
+    // While captured the mouse looks around without holding `look`
+    pub fn set_mouse_captured(&mut self, captured: bool) {
+        self.mouse_captured = captured;
+    }
+
+    pub fn mouse_captured(&self) -> bool {
+        self.mouse_captured
+    }
+
+    pub fn mouse_look(&self) -> bool {
+        self.mouse_captured || self.held("look")
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+        InputEvent::from_window_event(event).is_some_and(|event| self.handle_event(&event))
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) -> bool {
+        InputEvent::from_device_event(event).is_some_and(|event| self.handle_event(&event))
+    }
+
+    pub fn handle_gamepad_event(&mut self, event: &GamepadEvent) {
+        self.handle_event(&InputEvent::from(*event));
+    }
+
+    pub fn handle_event(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::Button(binding, is_down) => self.set_down(binding, is_down),
+            InputEvent::Wheel(lines) => {
+                self.wheel += lines;
+                if lines != 0.0 {
+                    let binding = if lines > 0.0 { Binding::WheelUp } else { Binding::WheelDown };
+                    self.set_down(binding, true);
+                    // Wheel steps have no release event, they end with the frame
+                    self.down.remove(&binding);
+                    self.released.insert(binding);
+                }
+                true
+            }
+            InputEvent::Cursor(x, y) => {
+                self.cursor = PhysicalPosition::new(x, y);
+                true
+            }
+            InputEvent::MouseMotion(dx, dy) => {
+                self.mouse_delta.0 += dx / self.scale_factor;
+                self.mouse_delta.1 += dy / self.scale_factor;
+                true
+            }
+            InputEvent::ScaleFactor(scale_factor) => {
+                self.set_scale_factor(scale_factor);
+                false
+            }
+            // Nothing is held while the window can't see the releases. Gamepads
+            // are polled independently of focus and keep their state.
+            InputEvent::Focused(false) => {
+                let lost = self.down.iter().filter(|b| !matches!(b, Binding::Gamepad(_))).copied().collect::<Vec<_>>();
+                for binding in lost {
+                    self.set_down(binding, false);
+                }
+                false
+            }
+            InputEvent::Focused(true) => false,
+            InputEvent::GamepadAxis(axis, value) => {
+                self.gamepad_axes.insert(axis, value);
+                true
+            }
+            InputEvent::GamepadDisconnected => {
+                self.gamepad_axes.clear();
+                let buttons = self.down.iter().filter(|b| matches!(b, Binding::Gamepad(_))).copied().collect::<Vec<_>>();
+                for button in buttons {
+                    self.set_down(button, false);
+                }
+                true
+            }
+        }
+    }
+
+    // Stick or trigger value after dead zones and the response curve
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        let raw = |axis| self.gamepad_axes.get(&axis).copied().unwrap_or(0.0);
+        match axis {
+            GamepadAxis::LeftX | GamepadAxis::RightX => self.gamepad_settings.apply_stick(raw(axis), raw(axis.pair().unwrap())).0,
+            GamepadAxis::LeftY | GamepadAxis::RightY => self.gamepad_settings.apply_stick(raw(axis.pair().unwrap()), raw(axis)).1,
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => self.gamepad_settings.apply_trigger(raw(axis)),
+        }
+    }
+
+    fn set_down(&mut self, binding: Binding, is_down: bool) -> bool {
+        if is_down {
+            if let Some(action) = self.rebinding.take() {
+                log::info!("Bound {} to {}", action, binding.name());
+                self.actions.insert(action, vec![binding]);
+                self.warn_conflicts();
+                return true;
+            }
+            if self.down.insert(binding) {
+                self.pressed.insert(binding);
+            }
+        } else if self.down.remove(&binding) {
+            self.released.insert(binding);
+        }
+        true
+    }
+
+    // Clears presses and releases, call once after the frame handled its actions
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    // Clears scrolling and mouse movement, call after a simulation step consumed
+    // them. Frames without a step keep accumulating so no motion is lost.
+    pub fn end_step(&mut self) {
+        self.wheel = 0.0;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    // Went down this frame
+    pub fn pressed(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|binding| self.pressed.contains(binding))
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|binding| self.down.contains(binding))
+    }
+
+    // Went up this frame
+    pub fn released(&self, action: &str) -> bool {
+        self.bindings(action).iter().any(|binding| self.released.contains(binding)) && !self.held(action)
+    }
+
+    pub fn axis(&self, axis: &str) -> f32 {
+        let Some(bindings) = self.axes.get(axis) else {
+            return 0.0;
+        };
+        bindings
+            .iter()
+            .map(|(binding, scale)| match binding {
+                Binding::WheelUp => self.wheel.max(0.0) * scale,
+                Binding::WheelDown => (-self.wheel).max(0.0) * scale,
+                Binding::GamepadAxis(axis) => self.gamepad_axis(*axis) * scale,
+                _ if self.down.contains(binding) => *scale,
+                _ => 0.0,
+            })
+            .sum()
+    }
+
+    // Mouse movement since the last frame, in logical pixels
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    // Cursor in normalized device coordinates, -1..1 with y up
+    pub fn cursor_ndc(&self) -> (f32, f32) {
+        (
+            self.cursor.x as f32 / self.size.0 as f32 * 2.0 - 1.0,
+            1.0 - self.cursor.y as f32 / self.size.1 as f32 * 2.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, CameraController, STICK_RADIANS_PER_SECOND};
+    use crate::gamepad::{GamepadSource, VirtualGamepad};
+    use cgmath::{Deg, InnerSpace, Rad};
+    use instant::Duration;
+
+    const EPSILON: f32 = 1e-4;
+
+    // Delivers the queued events the way `State::poll_gamepad` does
+    fn feed(input: &mut InputMap, pad: &mut VirtualGamepad) {
+        let mut events = Vec::new();
+        pad.poll(&mut events);
+        for event in events {
+            input.handle_event(&InputEvent::from(event));
+        }
+    }
+
+    fn key(code: KeyCode, is_down: bool) -> InputEvent {
+        InputEvent::Button(Binding::Key(code), is_down)
+    }
+
+    #[test]
+    fn config_overrides_defaults() {
+        let input = InputMap::from_config(
+            "# comment\n\naction exit = KeyQ Mouse4  # trailing comment\naxis zoom = -PadLeftY WheelUp\n",
+        )
+        .unwrap();
+        assert_eq!(input.bindings("exit"), [Binding::Key(KeyCode::KeyQ), Binding::Mouse(MouseButton::Other(4))]);
+        assert_eq!(input.bindings("next_pipeline"), [Binding::Key(KeyCode::Tab)]);
+        assert_eq!(input.axes["zoom"], [(Binding::GamepadAxis(GamepadAxis::LeftY), -1.0), (Binding::WheelUp, 1.0)]);
+    }
+
+    #[test]
+    fn config_errors_name_the_line() {
+        let error = |config| InputMap::from_config(config).unwrap_err().to_string();
+        assert_eq!(error("action exit = Escape\naction exit Escape"), "input config line 2: expected `action|axis <name> = <bindings>`");
+        assert_eq!(error("\n# comment\naction = Escape"), "input config line 3: expected `action|axis <name>`, got \"action\"");
+        assert_eq!(error("action exit = Escpe"), "input config line 1: unknown binding \"Escpe\"");
+        assert_eq!(error("action exit = -"), "input config line 1: unknown binding \"\"");
+        assert_eq!(error("button exit = Escape"), "input config line 1: unknown kind \"button\"");
+    }
+
+    #[test]
+    fn serialized_config_loads_back() {
+        let mut input = InputMap::default();
+        input.bind("exit", Binding::WheelDown);
+        input.bind_axis("zoom", Binding::Key(KeyCode::Minus), -2.0);
+        let loaded = InputMap::from_config(&input.serialize()).unwrap();
+        assert_eq!(loaded.serialize(), input.serialize());
+    }
+
+    #[test]
+    fn pressed_held_and_released_across_frames() {
+        let mut input = InputMap::default();
+        input.handle_event(&key(KeyCode::Escape, true));
+        assert!(input.pressed("exit") && input.held("exit") && !input.released("exit"));
+
+        input.end_frame();
+        assert!(!input.pressed("exit") && input.held("exit"));
+
+        // Repeated downs while held are not new presses
+        input.handle_event(&key(KeyCode::Escape, true));
+        assert!(!input.pressed("exit"));
+
+        input.handle_event(&key(KeyCode::Escape, false));
+        assert!(!input.held("exit") && input.released("exit"));
+        input.end_frame();
+        assert!(!input.released("exit"));
+
+        // A tap within one frame is both pressed and released
+        input.handle_event(&key(KeyCode::Escape, true));
+        input.handle_event(&key(KeyCode::Escape, false));
+        assert!(input.pressed("exit") && input.released("exit") && !input.held("exit"));
+    }
+
+    #[test]
+    fn releasing_one_of_two_bindings_keeps_the_action_held() {
+        let mut input = InputMap::default();
+        input.handle_event(&InputEvent::Button(Binding::Mouse(MouseButton::Right), true));
+        input.handle_event(&InputEvent::Button(Binding::Mouse(MouseButton::Middle), true));
+        input.handle_event(&InputEvent::Button(Binding::Mouse(MouseButton::Right), false));
+        assert!(input.held("pan") && !input.released("pan"));
+    }
+
+    #[test]
+    fn negative_axis_bindings() {
+        let mut input = InputMap::default();
+        input.handle_event(&key(KeyCode::KeyS, true));
+        assert_eq!(input.axis("move_forward"), -1.0);
+        // Opposite bindings cancel, two on the same side add up
+        input.handle_event(&key(KeyCode::KeyW, true));
+        assert_eq!(input.axis("move_forward"), 0.0);
+        input.handle_event(&key(KeyCode::KeyW, false));
+        input.handle_event(&key(KeyCode::ArrowDown, true));
+        assert_eq!(input.axis("move_forward"), -2.0);
+        assert_eq!(input.axis("unbound"), 0.0);
+    }
+
+    #[test]
+    fn wheel_accumulates_until_the_step_ends() {
+        let mut input = InputMap::default();
+        input.handle_event(&InputEvent::Wheel(1.0));
+        input.handle_event(&InputEvent::Wheel(0.5));
+        assert!((input.axis("zoom") - 1.5).abs() < EPSILON);
+
+        // Frames without a simulation step keep the scroll
+        input.end_frame();
+        input.handle_event(&InputEvent::Wheel(-2.0));
+        assert!((input.axis("zoom") + 0.5).abs() < EPSILON);
+
+        input.end_step();
+        assert_eq!(input.axis("zoom"), 0.0);
+    }
+
+    #[test]
+    fn wheel_steps_are_pressed_for_one_frame() {
+        let mut input = InputMap::from_config("action next_pipeline = WheelUp").unwrap();
+        input.handle_event(&InputEvent::Wheel(1.0));
+        assert!(input.pressed("next_pipeline") && !input.held("next_pipeline"));
+        input.end_frame();
+        assert!(!input.pressed("next_pipeline"));
+    }
+
+    #[test]
+    fn rebinding_takes_the_next_press() {
+        let mut input = InputMap::default();
+        input.start_rebind("exit");
+        assert!(input.is_rebinding());
+
+        // The press that rebinds doesn't trigger anything
+        input.handle_event(&key(KeyCode::KeyQ, true));
+        assert!(!input.is_rebinding());
+        assert_eq!(input.bindings("exit"), [Binding::Key(KeyCode::KeyQ)]);
+        assert!(!input.pressed("exit"));
+
+        input.handle_event(&key(KeyCode::KeyQ, false));
+        input.end_frame();
+        input.handle_event(&key(KeyCode::Escape, true));
+        assert!(!input.pressed("exit"));
+        input.handle_event(&key(KeyCode::KeyQ, true));
+        assert!(input.pressed("exit"));
+    }
+
+    #[test]
+    fn default_bindings_do_not_conflict() {
+        assert!(InputMap::default().conflicts().is_empty());
+    }
+
+    #[test]
+    fn shared_bindings_are_reported() {
+        let input = InputMap::from_config("action store_bookmark = ControlLeft\naxis move_up = Space -ControlLeft").unwrap();
+        assert_eq!(
+            input.conflicts(),
+            vec![(Binding::Key(KeyCode::ControlLeft), vec!["move_up", "sprint", "store_bookmark"])]
+        );
+    }
+
+    #[test]
+    fn events_round_trip_through_lines() {
+        let events = [
+            InputEvent::Button(Binding::Key(KeyCode::KeyW), true),
+            InputEvent::Button(Binding::Key(KeyCode::Numpad7), false),
+            InputEvent::Button(Binding::Mouse(MouseButton::Left), true),
+            InputEvent::Button(Binding::Mouse(MouseButton::Other(9)), false),
+            InputEvent::Button(Binding::Gamepad(GamepadButton::LeftThumb), true),
+            InputEvent::Wheel(-2.5),
+            InputEvent::Cursor(0.1, 719.75),
+            InputEvent::MouseMotion(-1e-7, 1.0 / 3.0),
+            InputEvent::ScaleFactor(1.25),
+            InputEvent::Focused(false),
+            InputEvent::GamepadAxis(GamepadAxis::RightTrigger, 0.1 + 0.2),
+            InputEvent::GamepadAxis(GamepadAxis::LeftY, -f32::MIN_POSITIVE),
+            InputEvent::GamepadDisconnected,
+        ];
+        for event in events {
+            let line = event.to_line();
+            assert_eq!(InputEvent::parse(&line).unwrap(), event, "{:?}", line);
+        }
+        for line in ["", "down", "down NotAKey", "pad_axis PadSouth 0.5", "wheel up", "motion 1"] {
+            assert!(InputEvent::parse(line).is_err(), "{:?} should not parse", line);
+        }
+    }
+
+    #[test]
+    fn dead_zone_is_rescaled_from_zero() {
+        let settings = GamepadSettings::default();
+        let mut input = InputMap::default();
+        let mut pad = VirtualGamepad::new();
+
+        // Each axis is inside the dead zone on its own, together they aren't
+        pad.set_stick(GamepadAxis::LeftX, 0.1, 0.1);
+        pad.set_axis(GamepadAxis::RightTrigger, settings.dead_zone);
+        feed(&mut input, &mut pad);
+        assert_eq!(input.gamepad_axis(GamepadAxis::LeftX), 0.0);
+        assert_eq!(input.gamepad_axis(GamepadAxis::LeftY), 0.0);
+        assert_eq!(input.axis("move_right"), 0.0);
+        assert_eq!(input.axis("move_up"), 0.0);
+
+        // Just past the edge starts from zero instead of jumping to the dead zone
+        pad.set_stick(GamepadAxis::LeftX, settings.dead_zone + 0.01, 0.0);
+        pad.set_axis(GamepadAxis::RightTrigger, settings.dead_zone + 0.01);
+        feed(&mut input, &mut pad);
+        let x = input.gamepad_axis(GamepadAxis::LeftX);
+        assert!(x > 0.0 && x < 1e-3, "stick just outside the dead zone gives {}", x);
+        assert_eq!(input.axis("move_right"), x);
+        let trigger = input.axis("move_up");
+        assert!(trigger > 0.0 && trigger < 1e-3, "trigger just outside the dead zone gives {}", trigger);
+
+        // Past the outer dead zone is full deflection
+        pad.set_stick(GamepadAxis::LeftX, 0.0, -settings.outer_dead_zone);
+        feed(&mut input, &mut pad);
+        assert!((input.axis("move_forward") + 1.0).abs() < EPSILON);
+
+        pad.disconnect();
+        feed(&mut input, &mut pad);
+        assert_eq!(input.axis("move_forward"), 0.0);
+    }
+
+    // Holds the stick halfway between the dead zones for five seconds
+    fn hold_stick(stick: GamepadAxis, exponent: f32) -> (Camera, CameraController) {
+        let settings = GamepadSettings::default();
+        let half = (settings.dead_zone + settings.outer_dead_zone) / 2.0;
+        let mut input = InputMap::default();
+        input.gamepad_settings.exponent = exponent;
+        let mut pad = VirtualGamepad::new();
+        pad.set_stick(stick, half, 0.0);
+        feed(&mut input, &mut pad);
+
+        let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(-90.0), Deg(0.0));
+        let mut controller = CameraController::new(4.0, 1.0);
+        for _ in 0..100 {
+            controller.update_camera(&mut camera, &input, Duration::from_millis(50));
+            input.end_step();
+        }
+        (camera, controller)
+    }
+
+    #[test]
+    fn response_curve_reaches_the_camera() {
+        for exponent in [1.0, 2.0, 3.0] {
+            // Halfway along the response curve
+            let expected = 0.5f32.powf(exponent);
+
+            let (camera, controller) = hold_stick(GamepadAxis::LeftY, exponent);
+            let speed = controller.velocity().magnitude();
+            assert!((speed - 4.0 * expected).abs() < EPSILON, "exponent {}: moving at {}", exponent, speed);
+            assert!(controller.velocity().z < 0.0, "exponent {}: should move forward", exponent);
+            assert_eq!(camera.yaw(), Rad::from(Deg(-90.0)));
+
+            let (camera, controller) = hold_stick(GamepadAxis::RightX, exponent);
+            let yaw = (camera.yaw() - Rad::from(Deg(-90.0))).0;
+            let expected_yaw = expected * STICK_RADIANS_PER_SECOND * 5.0;
+            assert!((yaw - expected_yaw).abs() < EPSILON, "exponent {}: turned {} instead of {}", exponent, yaw, expected_yaw);
+            assert_eq!(camera.pitch().0, 0.0);
+            assert_eq!(controller.velocity().magnitude(), 0.0);
+        }
+    }
+}
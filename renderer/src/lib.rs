@@ -3,11 +3,30 @@ pub mod state;
 pub mod vertex_buffer;
 pub mod uniform;
 pub mod texture;
+pub mod bcn;
+pub mod etc2;
 pub mod camera;
+pub mod controller;
+pub mod input;
+pub mod gamepad;
+pub mod camera_path;
+pub mod replay;
+pub mod timestep;
+pub mod frame_pacing;
 pub mod instance;
 pub mod model;
 pub mod resources;
+pub mod assets;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod watcher;
 pub mod pipeline;
+pub mod render_graph;
+pub mod oit;
+pub mod debug_view;
+pub mod debug_draw;
+pub mod text_overlay;
+pub mod shader;
+pub mod reflect;
 pub mod light;
 
 use crate::app::App;
@@ -23,12 +42,71 @@ pub fn run() -> anyhow::Result<()> {
         console_log::init_with_level(log::Level::Info).unwrap_throw();
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        // CAPTURE_PATH=<file> renders a camera path to PNGs without opening a window
+        if let Ok(path) = std::env::var("CAPTURE_PATH") {
+            return capture(&path);
+        }
+        // REPLAY_HEADLESS=1 checks a REPLAY_INPUT recording without a window
+        if let (Ok(path), Ok(_)) = (std::env::var("REPLAY_INPUT"), std::env::var("REPLAY_HEADLESS")) {
+            return replay_headless(&path);
+        }
+    }
+
     let event_loop = EventLoop::with_user_event().build()?;
+    #[allow(unused_mut)]
     let mut app = App::new(
         #[cfg(target_arch = "wasm32")]
         &event_loop,
     );
+    // RECORD_INPUT=<file> records the session, REPLAY_INPUT=<file> plays one back
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        if let Ok(path) = std::env::var("RECORD_INPUT") {
+            app = app.record_input(replay::InputRecorder::create(path)?);
+        }
+        if let Ok(path) = std::env::var("REPLAY_INPUT") {
+            app = app.replay_input(replay::InputReplay::load(path)?);
+        }
+    }
     event_loop.run_app(&mut app)?;
     
     Ok(())
 }
+
+// Frames go to CAPTURE_DIR (default "capture") at CAPTURE_FPS (default 30) and
+// CAPTURE_SIZE (default 1280x720)
+#[cfg(not(target_arch = "wasm32"))]
+fn capture(path: &str) -> anyhow::Result<()> {
+    let camera_path = camera_path::CameraPath::parse(&std::fs::read_to_string(path)?)?;
+    let fps = std::env::var("CAPTURE_FPS").map_or(Ok(30.0), |fps| fps.parse())?;
+    let dir = std::env::var("CAPTURE_DIR").unwrap_or_else(|_| "capture".to_string());
+    let (width, height) = match std::env::var("CAPTURE_SIZE") {
+        Ok(size) => {
+            let (width, height) = size
+                .split_once('x')
+                .ok_or_else(|| anyhow::anyhow!("CAPTURE_SIZE should look like 1280x720, got {:?}", size))?;
+            (width.parse()?, height.parse()?)
+        }
+        Err(_) => (1280, 720),
+    };
+
+    let mut state = pollster::block_on(state::State::headless(width, height))?;
+    let frames = state.capture_path(&camera_path, fps, std::path::Path::new(&dir))?;
+    log::info!("Captured {} frames of {:?} to {:?}", frames, path, dir);
+    Ok(())
+}
+
+// Fails when any frame ends up somewhere other than where it was recorded
+#[cfg(not(target_arch = "wasm32"))]
+fn replay_headless(path: &str) -> anyhow::Result<()> {
+    let mut replay = replay::InputReplay::load(path)?;
+    let mut state = pollster::block_on(state::State::headless(1280, 720))?;
+    let diverged = replay.run(&mut state);
+    log::info!("Replay finished after {} frames, {} diverged", replay.frame(), diverged);
+    if diverged > 0 {
+        anyhow::bail!("{} of {} replayed frames diverged from {:?}", diverged, replay.frame(), path);
+    }
+    Ok(())
+}
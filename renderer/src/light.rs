@@ -63,10 +63,7 @@ pub trait DrawLight<'a> {
     );
 }
 
-impl<'a, 'b> DrawLight<'b> for wgpu::RenderPass<'a>
-where
-    'b: 'a,
-{
+impl<'a, 'b> DrawLight<'b> for wgpu::RenderPass<'a> {
     fn draw_light_mesh(
         &mut self,
         mesh: &'b Mesh,
@@ -1,3 +1,3 @@
-fn main() {
-    let _ = renderer::run();
+fn main() -> anyhow::Result<()> {
+    renderer::run()
 }
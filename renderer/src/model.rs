@@ -1,8 +1,10 @@
 use std::mem;
 use std::ops::Range;
 use mikktspace;
+use crate::assets::{Assets, Handle};
 use crate::texture::Texture;
 use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
@@ -16,18 +18,106 @@ pub struct Model {
     pub meshes: Vec<Mesh>,
     pub materials: Vec<Material>,
 }
+// How a material's alpha is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    Opaque,
+    // Cutout (foliage, fences): fragments below `MaterialUniform::alpha_cutoff` are dropped
+    Mask,
+    // Translucent (glass): blended after opaques, sorted back to front
+    Blend,
+}
+
 pub struct Material {
     pub name: String,
-    pub diffuse_texture: Texture,
-    pub normal_texture: Texture,
+    pub alpha_mode: AlphaMode,
+    // Disables back-face culling and lights back faces with a flipped normal
+    pub two_sided: bool,
+    pub textures: MaterialTextures,
+    pub uniform: MaterialUniform,
+    pub uniform_buffer: wgpu::Buffer,
+    pub sampler: wgpu::Sampler,
+    pub layout: wgpu::BindGroupLayout,
     pub bind_group: wgpu::BindGroup,
 }
+
+// Texture bindings of a material. The occlusion, roughness and metallic maps are
+// read from the red, green and blue channel, so a packed ORM map can be bound
+// to all three and separate grayscale maps work as they are. The opacity map's
+// red channel scales the diffuse alpha.
+#[derive(Debug, Clone)]
+pub struct MaterialTextures {
+    pub diffuse: Handle<Texture>,
+    pub normal: Handle<Texture>,
+    pub occlusion: Handle<Texture>,
+    pub roughness: Handle<Texture>,
+    pub metallic: Handle<Texture>,
+    pub opacity: Handle<Texture>,
+}
+
+impl MaterialTextures {
+    fn iter(&self) -> impl Iterator<Item = &Handle<Texture>> {
+        [
+            &self.diffuse,
+            &self.normal,
+            &self.occlusion,
+            &self.roughness,
+            &self.metallic,
+            &self.opacity,
+        ]
+        .into_iter()
+    }
+}
+
+// Scalar MTL parameters, bound next to the material textures
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct MaterialUniform {
+    pub diffuse: [f32; 4], // Kd (or white when map_Kd is present), d in alpha
+    pub specular: [f32; 3], // Ks
+    pub shininess: f32, // Ns
+    pub alpha_cutoff: f32, // Only used by AlphaMode::Mask
+    pub roughness: f32, // Pr, scales the roughness map
+    pub metallic: f32, // Pm, scales the metallic map
+    pub _padding: f32,
+}
+
+impl Default for MaterialUniform {
+    fn default() -> Self {
+        Self {
+            diffuse: [1.0, 1.0, 1.0, 1.0],
+            specular: [0.5, 0.5, 0.5],
+            shininess: 32.0,
+            alpha_cutoff: 0.5,
+            // Zero keeps plain Blinn-Phong for materials without PBR parameters
+            roughness: 0.0,
+            metallic: 0.0,
+            _padding: 0.0,
+        }
+    }
+}
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    // De-indexed copy of the vertices for the barycentric wireframe, only built
+    // when the device can't rasterize lines from polygons
+    pub wireframe_buffer: Option<wgpu::Buffer>,
+    // Object space bounding box, used for depth sorting
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+}
+
+impl Mesh {
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.bounds_min[0] + self.bounds_max[0]) * 0.5,
+            (self.bounds_min[1] + self.bounds_max[1]) * 0.5,
+            (self.bounds_min[2] + self.bounds_max[2]) * 0.5,
+        ]
+    }
 }
 
 pub struct TobjMeshWrapper<'a> {
@@ -83,37 +173,110 @@ impl Material {
     pub fn new(
         device: &wgpu::Device,
         name: &str,
-        diffuse_texture: Texture,
-        normal_texture: Texture,
+        assets: &Assets<Texture>,
+        textures: MaterialTextures,
         sampler: wgpu::Sampler,
+        uniform: MaterialUniform,
         layout: &wgpu::BindGroupLayout,
     ) -> Self {
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{name}_material_buffer")),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = Self::create_bind_group(
+            device,
+            name,
+            assets,
+            &textures,
+            &sampler,
+            &uniform_buffer,
+            layout,
+        );
+
+        Self {
+            name: String::from(name),
+            alpha_mode: AlphaMode::Opaque,
+            two_sided: false,
+            textures,
+            uniform,
+            uniform_buffer,
+            sampler,
+            layout: layout.clone(),
+            bind_group,
+        }
+    }
+
+    // Called after one of the material's textures was replaced in place
+    pub fn rebuild_bind_group(&mut self, device: &wgpu::Device, assets: &Assets<Texture>) {
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.name,
+            assets,
+            &self.textures,
+            &self.sampler,
+            &self.uniform_buffer,
+            &self.layout,
+        );
+    }
 
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+    fn create_bind_group(
+        device: &wgpu::Device,
+        name: &str,
+        assets: &Assets<Texture>,
+        textures: &MaterialTextures,
+        sampler: &wgpu::Sampler,
+        uniform_buffer: &wgpu::Buffer,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        // Handles keep their texture alive, so these are always present
+        let view = |handle: &Handle<Texture>| {
+            wgpu::BindingResource::TextureView(&assets.get(handle).expect("material texture not loaded").view)
+        };
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(name),
             layout,
             entries: & [
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
+                    resource: view(&textures.diffuse),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
                 wgpu::BindGroupEntry {
                     binding: 2,
-                    resource: wgpu::BindingResource::TextureView(&normal_texture.view),
+                    resource: view(&textures.normal),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: view(&textures.occlusion),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: view(&textures.roughness),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 6,
+                    resource: view(&textures.metallic),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 7,
+                    resource: view(&textures.opacity),
                 },
             ],
-        });
+        })
+    }
 
-        Self {
-            name: String::from(name),
-            diffuse_texture,
-            normal_texture,
-            bind_group,
-        }
+    pub fn uses_texture(&self, id: u64) -> bool {
+        self.textures.iter().any(|handle| handle.id() == id)
     }
 }
 
@@ -176,10 +339,7 @@ pub trait DrawModel<'a> {
 }
 
 
-impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
-where
-    'b: 'a,
-{
+impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a> {
     fn draw_mesh(&mut self, mesh: &'b Mesh, material: &'b Material, camera_bind_group: &'b wgpu::BindGroup, light_bind_group: &'b wgpu::BindGroup) {
         self.draw_mesh_instanced(mesh, material, 0..1, camera_bind_group, light_bind_group);
     }
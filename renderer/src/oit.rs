@@ -0,0 +1,117 @@
+use crate::pipeline::{PipelineCache, RenderPipelineBuilder};
+use crate::reflect;
+use crate::shader::{ShaderCache, ShaderDefines, ShaderError};
+
+// Premultiplied color and coverage, summed over all transparent fragments
+pub const ACCUM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+// Product of (1 - alpha), cleared to 1
+pub const REVEALAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+pub const COMPOSITE_SHADER: &str = "oit_composite.wgsl";
+
+// How alpha-blended materials are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransparencyMode {
+    // Per-object back-to-front sorting, exact for non-intersecting meshes
+    Sorted,
+    // Weighted blended OIT, order independent but approximate
+    WeightedBlended,
+}
+
+impl TransparencyMode {
+    pub fn next(self) -> Self {
+        match self {
+            TransparencyMode::Sorted => TransparencyMode::WeightedBlended,
+            TransparencyMode::WeightedBlended => TransparencyMode::Sorted,
+        }
+    }
+}
+
+// Targets for the accumulation pass, shaders output `OitOutput` from common/oit.wgsl
+pub fn accumulate_targets() -> [wgpu::ColorTargetState; 2] {
+    let additive = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::One,
+        dst_factor: wgpu::BlendFactor::One,
+        operation: wgpu::BlendOperation::Add,
+    };
+    let multiplicative = wgpu::BlendComponent {
+        src_factor: wgpu::BlendFactor::Zero,
+        dst_factor: wgpu::BlendFactor::OneMinusSrc,
+        operation: wgpu::BlendOperation::Add,
+    };
+    [
+        wgpu::ColorTargetState {
+            format: ACCUM_FORMAT,
+            blend: Some(wgpu::BlendState { color: additive, alpha: additive }),
+            write_mask: wgpu::ColorWrites::ALL,
+        },
+        wgpu::ColorTargetState {
+            format: REVEALAGE_FORMAT,
+            blend: Some(wgpu::BlendState { color: multiplicative, alpha: multiplicative }),
+            write_mask: wgpu::ColorWrites::RED,
+        },
+    ]
+}
+
+// Resolves the accumulation targets over the opaque image
+pub struct OitComposite {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+impl OitComposite {
+    pub fn new(
+        device: &wgpu::Device,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<Self, ShaderError> {
+        let shader = shader_cache.get_or_compile(device, COMPOSITE_SHADER, &ShaderDefines::new())?;
+        let layout = reflect::create_bind_group_layout(device, "OIT composite bind group layout", &[(&shader.reflection, 0)])
+            .map_err(|e| ShaderError::new(COMPOSITE_SHADER, e))?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("OIT composite pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let builder = RenderPipelineBuilder::new("OIT composite pipeline", &pipeline_layout, &shader.module)
+            .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+            .color_format(format)
+            .sample_count(sample_count)
+            .cull_mode(None)
+            .depth_format(None);
+
+        Ok(Self {
+            pipeline: pipeline_cache.get_or_build(device, &builder),
+            layout,
+        })
+    }
+
+    pub fn draw(
+        &self,
+        device: &wgpu::Device,
+        render_pass: &mut wgpu::RenderPass<'_>,
+        accum: &wgpu::TextureView,
+        revealage: &wgpu::TextureView,
+    ) {
+        // The views change whenever the graph reallocates, so bind them per frame
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("OIT composite bind group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(accum),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(revealage),
+                },
+            ],
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
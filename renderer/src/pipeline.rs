@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use crate::texture::Texture;
 
 pub fn create_render_pipeline(
@@ -6,49 +7,295 @@ pub fn create_render_pipeline(
     label: &str,
     layout: &wgpu::PipelineLayout,
     shader: &wgpu::ShaderModule,
-    buffers: &[wgpu::VertexBufferLayout<'_>],
+    buffers: &[wgpu::VertexBufferLayout<'static>],
 ) -> wgpu::RenderPipeline {
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some(label),
-        layout: Some(layout),
-        vertex: wgpu::VertexState {
-            module: shader,
-            entry_point: Some("vs_main"),
-            buffers,
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: shader,
-            entry_point: Some("fs_main"),
-            targets: &[Some(wgpu::ColorTargetState {
-                format: config.format,
-                blend: Some(wgpu::BlendState::REPLACE),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-            compilation_options: wgpu::PipelineCompilationOptions::default(),
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: wgpu::PolygonMode::Fill,
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: Some(wgpu::DepthStencilState {
-            format: Texture::DEPTH_FORMAT,
+    RenderPipelineBuilder::new(label, layout, shader)
+        .buffers(buffers)
+        .color_format(config.format)
+        .build(device)
+}
+
+// Everything that affects the compiled pipeline. Layouts and shader modules
+// compare by identity, so two builders with equal keys produce identical pipelines.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct PipelineKey {
+    layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    vertex_entry: String,
+    fragment_entry: Option<String>,
+    buffers: Vec<wgpu::VertexBufferLayout<'static>>,
+    targets: Vec<wgpu::ColorTargetState>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+}
+
+// Defaults match the original fixed pipeline: opaque triangles, back-face
+// culling and `Less` depth testing with writes
+#[derive(Clone, Debug)]
+pub struct RenderPipelineBuilder {
+    label: String,
+    key: PipelineKey,
+    blend: Option<wgpu::BlendState>,
+    write_mask: wgpu::ColorWrites,
+}
+
+impl RenderPipelineBuilder {
+    pub fn new(label: &str, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule) -> Self {
+        Self {
+            label: label.to_string(),
+            key: PipelineKey {
+                layout: layout.clone(),
+                shader: shader.clone(),
+                vertex_entry: "vs_main".to_string(),
+                fragment_entry: Some("fs_main".to_string()),
+                buffers: Vec::new(),
+                targets: Vec::new(),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+            },
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+        }
+    }
+
+    pub fn vertex_entry(mut self, entry: &str) -> Self {
+        self.key.vertex_entry = entry.to_string();
+        self
+    }
+
+    // `None` builds a depth-only pipeline
+    pub fn fragment_entry(mut self, entry: Option<&str>) -> Self {
+        self.key.fragment_entry = entry.map(str::to_string);
+        self
+    }
+
+    pub fn buffers(mut self, buffers: &[wgpu::VertexBufferLayout<'static>]) -> Self {
+        self.key.buffers = buffers.to_vec();
+        self
+    }
+
+    // Adds a color target using the current blend state and write mask
+    pub fn color_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.key.targets.push(wgpu::ColorTargetState {
+            format,
+            blend: self.blend,
+            write_mask: self.write_mask,
+        });
+        self
+    }
+
+    pub fn color_formats(mut self, formats: &[wgpu::TextureFormat]) -> Self {
+        for format in formats {
+            self = self.color_format(*format);
+        }
+        self
+    }
+
+    // Applies to every color target, including ones already added
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        for target in self.key.targets.iter_mut() {
+            target.blend = blend;
+        }
+        self
+    }
+
+    pub fn write_mask(mut self, write_mask: wgpu::ColorWrites) -> Self {
+        self.write_mask = write_mask;
+        for target in self.key.targets.iter_mut() {
+            target.write_mask = write_mask;
+        }
+        self
+    }
+
+    // Full control over the targets, e.g. different blending per attachment
+    pub fn color_targets(mut self, targets: &[wgpu::ColorTargetState]) -> Self {
+        self.key.targets = targets.to_vec();
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.key.primitive.topology = topology;
+        self
+    }
+
+    // Required for indexed strip topologies, must match the format of the index
+    // buffer they're drawn with. Leave it `None` for lists and non-indexed strips.
+    pub fn strip_index_format(mut self, format: Option<wgpu::IndexFormat>) -> Self {
+        self.key.primitive.strip_index_format = format;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.key.primitive.front_face = front_face;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.key.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    // Line and Point need POLYGON_MODE_LINE / POLYGON_MODE_POINT
+    pub fn polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.key.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    // `None` disables depth testing entirely
+    pub fn depth_format(mut self, format: Option<wgpu::TextureFormat>) -> Self {
+        self.key.depth_stencil = format.map(|format| wgpu::DepthStencilState {
+            format,
             depth_write_enabled: true,
             depth_compare: wgpu::CompareFunction::Less,
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
-        }),
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-        cache: None,
-    })
+        });
+        self
+    }
+
+    pub fn depth_write(mut self, enabled: bool) -> Self {
+        if let Some(depth) = self.key.depth_stencil.as_mut() {
+            depth.depth_write_enabled = enabled;
+        }
+        self
+    }
+
+    pub fn depth_compare(mut self, compare: wgpu::CompareFunction) -> Self {
+        if let Some(depth) = self.key.depth_stencil.as_mut() {
+            depth.depth_compare = compare;
+        }
+        self
+    }
+
+    pub fn depth_bias(mut self, bias: wgpu::DepthBiasState) -> Self {
+        if let Some(depth) = self.key.depth_stencil.as_mut() {
+            depth.bias = bias;
+        }
+        self
+    }
+
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.key.multisample.count = count;
+        self
+    }
+
+    pub fn alpha_to_coverage(mut self, enabled: bool) -> Self {
+        self.key.multisample.alpha_to_coverage_enabled = enabled;
+        self
+    }
+
+    // Same pipeline for reverse-Z depth: comparisons and depth bias flip direction
+    pub fn reversed_depth(mut self) -> Self {
+        if let Some(depth) = self.key.depth_stencil.as_mut() {
+            depth.depth_compare = match depth.depth_compare {
+                wgpu::CompareFunction::Less => wgpu::CompareFunction::Greater,
+                wgpu::CompareFunction::LessEqual => wgpu::CompareFunction::GreaterEqual,
+                wgpu::CompareFunction::Greater => wgpu::CompareFunction::Less,
+                wgpu::CompareFunction::GreaterEqual => wgpu::CompareFunction::LessEqual,
+                compare => compare,
+            };
+            depth.bias.constant = -depth.bias.constant;
+            depth.bias.slope_scale = -depth.bias.slope_scale;
+        }
+        self
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let key = &self.key;
+        let targets = key.targets.iter().cloned().map(Some).collect::<Vec<_>>();
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&self.label),
+            layout: Some(&key.layout),
+            vertex: wgpu::VertexState {
+                module: &key.shader,
+                entry_point: Some(&key.vertex_entry),
+                buffers: &key.buffers,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: key.fragment_entry.as_deref().map(|entry| wgpu::FragmentState {
+                module: &key.shader,
+                entry_point: Some(entry),
+                targets: &targets,
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: key.primitive,
+            depth_stencil: key.depth_stencil.clone(),
+            multisample: key.multisample,
+            multiview: None,
+            cache: None,
+        })
+    }
+}
+
+// Deduplicates pipelines by descriptor so materials can ask for variants
+// (blend mode, culling, ...) on demand without building them twice
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    // Builders are written for regular depth, see `RenderPipelineBuilder::reversed_depth`
+    reverse_z: bool,
+}
+
+impl PipelineCache {
+    pub fn reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    // Only affects pipelines built afterwards
+    pub fn set_reverse_z(&mut self, enabled: bool) {
+        self.reverse_z = enabled;
+    }
+
+    pub fn get_or_build(&mut self, device: &wgpu::Device, builder: &RenderPipelineBuilder) -> wgpu::RenderPipeline {
+        if self.reverse_z {
+            let builder = builder.clone().reversed_depth();
+            return self.pipelines
+                .entry(builder.key.clone())
+                .or_insert_with(|| builder.build(device))
+                .clone();
+        }
+        self.pipelines
+            .entry(builder.key.clone())
+            .or_insert_with(|| builder.build(device))
+            .clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    // Drops pipelines built from `shader`, e.g. after it was recompiled
+    pub fn remove_shader(&mut self, shader: &wgpu::ShaderModule) {
+        self.pipelines.retain(|key, _| key.shader != *shader);
+    }
+
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+    }
 }
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+
+// Bind group layouts and vertex inputs as declared by a WGSL module, so the
+// Rust side doesn't have to mirror `@group`/`@binding`/`@location` by hand
+#[derive(Debug, Clone, Default)]
+pub struct ShaderReflection {
+    // group -> entries sorted by binding
+    pub bind_groups: BTreeMap<u32, Vec<wgpu::BindGroupLayoutEntry>>,
+    // vertex entry point -> (location, component kind)
+    pub vertex_inputs: BTreeMap<String, Vec<(u32, naga::ScalarKind)>>,
+}
+
+impl ShaderReflection {
+    pub fn new(module: &naga::Module, info: &naga::valid::ModuleInfo) -> Result<Self, String> {
+        let mut reflection = Self::default();
+
+        for (handle, global) in module.global_variables.iter() {
+            let Some(binding) = &global.binding else {
+                continue;
+            };
+            let mut visibility = wgpu::ShaderStages::NONE;
+            for (index, entry_point) in module.entry_points.iter().enumerate() {
+                if !info.get_entry_point(index)[handle].is_empty() {
+                    visibility |= match entry_point.stage {
+                        naga::ShaderStage::Vertex => wgpu::ShaderStages::VERTEX,
+                        naga::ShaderStage::Fragment => wgpu::ShaderStages::FRAGMENT,
+                        naga::ShaderStage::Compute => wgpu::ShaderStages::COMPUTE,
+                        _ => wgpu::ShaderStages::NONE,
+                    };
+                }
+            }
+            // Declared but unused bindings still get an entry so layouts are shareable
+            if visibility.is_empty() {
+                visibility = wgpu::ShaderStages::VERTEX_FRAGMENT;
+            }
+
+            let name = global.name.as_deref().unwrap_or("?");
+            let ty = binding_type(module, global).ok_or_else(|| {
+                format!("unsupported binding type for {} @group({}) @binding({})", name, binding.group, binding.binding)
+            })?;
+
+            let entries = reflection.bind_groups.entry(binding.group).or_default();
+            entries.push(wgpu::BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility,
+                ty,
+                count: None,
+            });
+            entries.sort_by_key(|entry| entry.binding);
+        }
+
+        for entry_point in module.entry_points.iter() {
+            if entry_point.stage != naga::ShaderStage::Vertex {
+                continue;
+            }
+            let mut inputs = Vec::new();
+            for argument in entry_point.function.arguments.iter() {
+                collect_locations(module, argument.ty, argument.binding.as_ref(), &mut inputs);
+            }
+            inputs.sort_by_key(|(location, _)| *location);
+            reflection.vertex_inputs.insert(entry_point.name.clone(), inputs);
+        }
+
+        Ok(reflection)
+    }
+
+    // Checks that every `@location` the entry point reads is fed by one of the
+    // buffers with a matching component type
+    pub fn validate_vertex_buffers(
+        &self,
+        entry_point: &str,
+        buffers: &[wgpu::VertexBufferLayout<'_>],
+    ) -> Result<(), Vec<String>> {
+        let Some(inputs) = self.vertex_inputs.get(entry_point) else {
+            return Err(vec![format!("no vertex entry point named {entry_point}")]);
+        };
+
+        let mut provided = BTreeMap::new();
+        let mut errors = Vec::new();
+        for (index, buffer) in buffers.iter().enumerate() {
+            for attribute in buffer.attributes {
+                if provided.insert(attribute.shader_location, (index, attribute.format)).is_some() {
+                    errors.push(format!("@location({}) is provided by more than one attribute", attribute.shader_location));
+                }
+            }
+        }
+
+        for (location, kind) in inputs {
+            match provided.get(location) {
+                None => errors.push(format!("{entry_point} reads @location({location}) but no vertex buffer provides it")),
+                Some((buffer, format)) if vertex_format_kind(*format) != *kind => errors.push(format!(
+                    "{entry_point} reads @location({location}) as {kind:?} but buffer {buffer} provides {format:?}"
+                )),
+                Some(_) => {}
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+// Merges the entries several shaders expect for one bind group, widening
+// visibility to every stage that uses a binding
+pub fn merge_bind_group(sources: &[(&ShaderReflection, u32)]) -> Result<Vec<wgpu::BindGroupLayoutEntry>, String> {
+    let mut merged: BTreeMap<u32, wgpu::BindGroupLayoutEntry> = BTreeMap::new();
+    for (reflection, group) in sources {
+        for entry in reflection.bind_groups.get(group).into_iter().flatten() {
+            match merged.get_mut(&entry.binding) {
+                Some(existing) if existing.ty != entry.ty => {
+                    return Err(format!(
+                        "@binding({}) is declared as {:?} and {:?} by different shaders",
+                        entry.binding, existing.ty, entry.ty
+                    ));
+                }
+                Some(existing) => existing.visibility |= entry.visibility,
+                None => {
+                    merged.insert(entry.binding, *entry);
+                }
+            }
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+pub fn create_bind_group_layout(
+    device: &wgpu::Device,
+    label: &str,
+    sources: &[(&ShaderReflection, u32)],
+) -> Result<wgpu::BindGroupLayout, String> {
+    let entries = merge_bind_group(sources)?;
+    Ok(device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some(label),
+        entries: &entries,
+    }))
+}
+
+fn binding_type(module: &naga::Module, global: &naga::GlobalVariable) -> Option<wgpu::BindingType> {
+    match global.space {
+        naga::AddressSpace::Uniform => {
+            return Some(wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            });
+        }
+        naga::AddressSpace::Storage { access } => {
+            return Some(wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage {
+                    read_only: !access.contains(naga::StorageAccess::STORE),
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            });
+        }
+        _ => {}
+    }
+
+    match &module.types[global.ty].inner {
+        naga::TypeInner::Sampler { comparison } => Some(wgpu::BindingType::Sampler(if *comparison {
+            wgpu::SamplerBindingType::Comparison
+        } else {
+            wgpu::SamplerBindingType::Filtering
+        })),
+        naga::TypeInner::Image { dim, arrayed, class } => {
+            let view_dimension = match (dim, arrayed) {
+                (naga::ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+                (naga::ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+                (naga::ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+                (naga::ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+                (naga::ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+                (naga::ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+            };
+            match class {
+                naga::ImageClass::Sampled { kind, multi } => Some(wgpu::BindingType::Texture {
+                    sample_type: match kind {
+                        naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        // Multisampled textures can't be filtered
+                        _ => wgpu::TextureSampleType::Float { filterable: !multi },
+                    },
+                    view_dimension,
+                    multisampled: *multi,
+                }),
+                naga::ImageClass::Depth { multi } => Some(wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension,
+                    multisampled: *multi,
+                }),
+                naga::ImageClass::Storage { .. } => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn collect_locations(
+    module: &naga::Module,
+    ty: naga::Handle<naga::Type>,
+    binding: Option<&naga::Binding>,
+    out: &mut Vec<(u32, naga::ScalarKind)>,
+) {
+    match (&module.types[ty].inner, binding) {
+        (naga::TypeInner::Struct { members, .. }, None) => {
+            for member in members {
+                collect_locations(module, member.ty, member.binding.as_ref(), out);
+            }
+        }
+        (inner, Some(naga::Binding::Location { location, .. })) => {
+            if let Some(scalar) = inner.scalar() {
+                out.push((*location, scalar.kind));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn vertex_format_kind(format: wgpu::VertexFormat) -> naga::ScalarKind {
+    use wgpu::VertexFormat::*;
+    match format {
+        Uint8 | Uint8x2 | Uint8x4 | Uint16 | Uint16x2 | Uint16x4 | Uint32 | Uint32x2 | Uint32x3 | Uint32x4 => {
+            naga::ScalarKind::Uint
+        }
+        Sint8 | Sint8x2 | Sint8x4 | Sint16 | Sint16x2 | Sint16x4 | Sint32 | Sint32x2 | Sint32x3 | Sint32x4 => {
+            naga::ScalarKind::Sint
+        }
+        _ => naga::ScalarKind::Float,
+    }
+}
@@ -0,0 +1,709 @@
+use std::collections::{HashMap, HashSet};
+
+// Handle to a texture or buffer declared on a `RenderGraph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    // Follows the surface on resize
+    Surface,
+    // Fraction of the surface size, e.g. 0.5 for half resolution effects
+    Scaled(f32),
+    Fixed(u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    pub format: wgpu::TextureFormat,
+    pub size: TextureSize,
+    pub sample_count: u32,
+    // Extra usages on top of what the passes declare (attachment, sampled)
+    pub usage: wgpu::TextureUsages,
+}
+
+impl TextureDesc {
+    pub fn new(format: wgpu::TextureFormat) -> Self {
+        Self {
+            format,
+            size: TextureSize::Surface,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::empty(),
+        }
+    }
+
+    pub fn size(mut self, size: TextureSize) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.sample_count = count;
+        self
+    }
+
+    pub fn usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+}
+
+// What an attachment starts with. `Clear` uses the resource's clear value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Load {
+    Clear,
+    Load,
+}
+
+enum ResourceKind {
+    // Owned by the graph, allocated on compile and possibly aliased
+    Transient(TextureDesc),
+    // Owned elsewhere (e.g. the swapchain image), bound every frame
+    ImportedTexture,
+    // Only tracked for ordering, passes access the buffer themselves
+    ImportedBuffer,
+}
+
+struct Resource {
+    name: String,
+    kind: ResourceKind,
+    clear_color: wgpu::Color,
+    clear_depth: f32,
+}
+
+type RenderFn<W> = Box<dyn Fn(&mut wgpu::RenderPass<'_>, &PassResources<'_>, &W)>;
+type EncodeFn<W> = Box<dyn Fn(&mut wgpu::CommandEncoder, &PassResources<'_>, &W)>;
+
+enum PassFn<W> {
+    // The graph begins a render pass with the declared attachments
+    Render(RenderFn<W>),
+    // Raw encoder access for copies and compute work
+    Encode(EncodeFn<W>),
+}
+
+struct Pass<W> {
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    // Attachment, how it starts and where it's resolved to when multisampled
+    colors: Vec<(ResourceId, Load, Option<ResourceId>)>,
+    depth: Option<(ResourceId, Load, bool)>,
+    side_effect: bool,
+    run: PassFn<W>,
+}
+
+// Texture views a pass can look up by resource id
+pub struct PassResources<'a> {
+    resources: &'a [Resource],
+    views: &'a HashMap<ResourceId, wgpu::TextureView>,
+}
+
+impl PassResources<'_> {
+    pub fn view(&self, id: ResourceId) -> &wgpu::TextureView {
+        self.views
+            .get(&id)
+            .unwrap_or_else(|| panic!("render graph texture {:?} is not available", self.resources[id.0].name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct PhysicalKey {
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    sample_count: u32,
+    usage: wgpu::TextureUsages,
+}
+
+// Result of `RenderGraph::plan`
+struct Plan {
+    order: Vec<usize>,
+    // Physical texture index of each transient, in order of first use
+    placements: Vec<(ResourceId, usize)>,
+    physical: Vec<PhysicalKey>,
+}
+
+struct Compiled {
+    order: Vec<usize>,
+    views: HashMap<ResourceId, wgpu::TextureView>,
+}
+
+// Passes declare which resources they read and write; the graph orders them,
+// culls passes nothing depends on, allocates transient textures (reusing
+// memory between textures whose lifetimes don't overlap) and executes them.
+// Aliased textures start with undefined contents, so the first pass writing
+// one should clear it.
+pub struct RenderGraph<W> {
+    resources: Vec<Resource>,
+    passes: Vec<Pass<W>>,
+    imported: HashMap<ResourceId, wgpu::TextureView>,
+    width: u32,
+    height: u32,
+    compiled: Option<Compiled>,
+}
+
+impl<W> Default for RenderGraph<W> {
+    fn default() -> Self {
+        Self {
+            resources: Vec::new(),
+            passes: Vec::new(),
+            imported: HashMap::new(),
+            width: 1,
+            height: 1,
+            compiled: None,
+        }
+    }
+}
+
+impl<W> RenderGraph<W> {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            ..Default::default()
+        }
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) -> ResourceId {
+        self.compiled = None;
+        self.resources.push(Resource {
+            name: name.to_string(),
+            kind,
+            clear_color: wgpu::Color::BLACK,
+            clear_depth: 1.0,
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Transient(desc))
+    }
+
+    // A texture owned outside the graph, its view is bound with `set_texture`
+    pub fn import_texture(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedTexture)
+    }
+
+    pub fn import_buffer(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedBuffer)
+    }
+
+    pub fn set_texture(&mut self, id: ResourceId, view: wgpu::TextureView) {
+        self.imported.insert(id, view);
+    }
+
+    pub fn set_clear_color(&mut self, id: ResourceId, color: wgpu::Color) {
+        self.resources[id.0].clear_color = color;
+    }
+
+    pub fn set_clear_depth(&mut self, id: ResourceId, depth: f32) {
+        self.resources[id.0].clear_depth = depth;
+    }
+
+    pub fn add_pass(&mut self, name: &str) -> PassBuilder<'_, W> {
+        PassBuilder {
+            graph: self,
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            colors: Vec::new(),
+            depth: None,
+            side_effect: false,
+        }
+    }
+
+    // Surface sized textures are reallocated on the next execute
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.compiled = None;
+        }
+    }
+
+    // Pass names in execution order, culled passes excluded
+    pub fn pass_order(&self) -> Option<Vec<&str>> {
+        let compiled = self.compiled.as_ref()?;
+        Some(compiled.order.iter().map(|i| self.passes[*i].name.as_str()).collect())
+    }
+
+    fn is_imported(&self, id: ResourceId) -> bool {
+        !matches!(self.resources[id.0].kind, ResourceKind::Transient(_))
+    }
+
+    fn texture_size(&self, size: TextureSize) -> (u32, u32) {
+        match size {
+            TextureSize::Surface => (self.width, self.height),
+            TextureSize::Scaled(scale) => (
+                ((self.width as f32 * scale) as u32).max(1),
+                ((self.height as f32 * scale) as u32).max(1),
+            ),
+            TextureSize::Fixed(width, height) => (width.max(1), height.max(1)),
+        }
+    }
+
+    // Orders the live passes. Dependencies are deliberately asymmetric:
+    // - a pass that only reads a resource depends on every writer of it, including
+    //   writers declared after it, so consumers can be declared before producers
+    // - a pass that reads and writes a resource (a loaded attachment, an
+    //   accumulation buffer) depends only on writers declared before it, so a
+    //   chain of read-write passes runs in declaration order
+    // - writers of the same resource keep their declaration order
+    // A plain reader therefore runs after the whole read-write chain. Passes
+    // that don't lead to an imported resource or a side effect are culled.
+    fn sort(&self) -> anyhow::Result<Vec<usize>> {
+        let mut writers: HashMap<ResourceId, Vec<usize>> = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            for id in &pass.writes {
+                writers.entry(*id).or_default().push(index);
+            }
+        }
+
+        let mut dependencies: Vec<HashSet<usize>> = vec![HashSet::new(); self.passes.len()];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for id in &pass.reads {
+                if pass.writes.contains(id) {
+                    continue;
+                }
+                dependencies[index].extend(writers.get(id).into_iter().flatten().copied());
+            }
+            for id in &pass.writes {
+                let earlier = writers[id].iter().copied().filter(|writer| *writer < index);
+                dependencies[index].extend(earlier);
+            }
+        }
+
+        // Only keep passes that lead to an imported resource or have side effects
+        let mut live = vec![false; self.passes.len()];
+        let mut stack: Vec<usize> = (0..self.passes.len())
+            .filter(|i| {
+                let pass = &self.passes[*i];
+                pass.side_effect || pass.writes.iter().any(|id| self.is_imported(*id))
+            })
+            .collect();
+        while let Some(index) = stack.pop() {
+            if !live[index] {
+                live[index] = true;
+                stack.extend(dependencies[index].iter().copied());
+            }
+        }
+        for (index, pass) in self.passes.iter().enumerate() {
+            if !live[index] {
+                log::debug!("Render graph culled pass {:?}", pass.name);
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < live.iter().filter(|l| **l).count() {
+            let next = (0..self.passes.len()).find(|i| {
+                live[*i] && !done[*i] && dependencies[*i].iter().all(|d| done[*d] || !live[*d])
+            });
+            let Some(next) = next else {
+                let stuck = (0..self.passes.len())
+                    .filter(|i| live[*i] && !done[*i])
+                    .map(|i| self.passes[i].name.as_str())
+                    .collect::<Vec<_>>();
+                anyhow::bail!("Render graph has a dependency cycle between passes {:?}", stuck);
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    // Pass order and texture placement, decided without touching the device.
+    // Transients are assigned to physical textures in order of first use, reusing
+    // one whose last user ran before and that matches in format, size, samples
+    // and usage.
+    fn plan(&self) -> anyhow::Result<Plan> {
+        let order = self.sort()?;
+
+        // First and last use of each transient texture, plus the usages passes need
+        let mut lifetimes: HashMap<ResourceId, (usize, usize, wgpu::TextureUsages)> = HashMap::new();
+        for (position, index) in order.iter().enumerate() {
+            let pass = &self.passes[*index];
+            let attachments = pass
+                .colors
+                .iter()
+                .flat_map(|(id, _, resolve)| std::iter::once(*id).chain(*resolve))
+                .chain(pass.depth.map(|(id, _, _)| id))
+                .collect::<Vec<_>>();
+            // Attachments that are loaded or depth tested against are read in place, not sampled
+            let sampled = pass.reads.iter().filter(|id| !attachments.contains(id));
+            let uses = attachments
+                .iter()
+                .map(|id| (*id, wgpu::TextureUsages::RENDER_ATTACHMENT))
+                .chain(sampled.map(|id| (*id, wgpu::TextureUsages::TEXTURE_BINDING)))
+                .chain(pass.writes.iter().map(|id| (*id, wgpu::TextureUsages::empty())));
+            for (id, usage) in uses {
+                if self.is_imported(id) {
+                    continue;
+                }
+                let lifetime = lifetimes.entry(id).or_insert((position, position, wgpu::TextureUsages::empty()));
+                lifetime.1 = position;
+                lifetime.2 |= usage;
+            }
+        }
+
+        let mut transients = lifetimes.into_iter().collect::<Vec<_>>();
+        transients.sort_by_key(|(id, (first, _, _))| (*first, *id));
+
+        // Physical textures with the position after which they're free again
+        let mut free_after: Vec<usize> = Vec::new();
+        let mut physical = Vec::new();
+        let mut placements = Vec::new();
+        for (id, (first, last, usage)) in transients {
+            let resource = &self.resources[id.0];
+            let ResourceKind::Transient(desc) = resource.kind else {
+                continue;
+            };
+            let (width, height) = self.texture_size(desc.size);
+            let key = PhysicalKey {
+                format: desc.format,
+                width,
+                height,
+                sample_count: desc.sample_count,
+                usage: usage | desc.usage,
+            };
+
+            let reusable = (0..physical.len()).find(|slot| physical[*slot] == key && free_after[*slot] < first);
+            let slot = match reusable {
+                Some(slot) => {
+                    log::debug!("Render graph aliases texture {:?}", resource.name);
+                    slot
+                }
+                None => {
+                    physical.push(key);
+                    free_after.push(0);
+                    physical.len() - 1
+                }
+            };
+            free_after[slot] = last;
+            placements.push((id, slot));
+        }
+
+        Ok(Plan { order, placements, physical })
+    }
+
+    pub fn compile(&mut self, device: &wgpu::Device) -> anyhow::Result<()> {
+        let Plan { order, placements, physical } = self.plan()?;
+
+        // Each physical texture is labelled after the first resource placed in it
+        let mut textures: Vec<Option<wgpu::TextureView>> = vec![None; physical.len()];
+        let mut views = HashMap::new();
+        for (id, slot) in placements {
+            let key = physical[slot];
+            let view = textures[slot].get_or_insert_with(|| {
+                device
+                    .create_texture(&wgpu::TextureDescriptor {
+                        label: Some(&self.resources[id.0].name),
+                        size: wgpu::Extent3d { width: key.width, height: key.height, depth_or_array_layers: 1 },
+                        mip_level_count: 1,
+                        sample_count: key.sample_count,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: key.format,
+                        usage: key.usage,
+                        view_formats: &[],
+                    })
+                    .create_view(&wgpu::TextureViewDescriptor::default())
+            });
+            views.insert(id, view.clone());
+        }
+
+        log::debug!(
+            "Render graph compiled: {} passes, {} transient textures in {} allocations",
+            order.len(),
+            views.len(),
+            physical.len()
+        );
+        self.compiled = Some(Compiled { order, views });
+        Ok(())
+    }
+
+    // Records every live pass into one encoder and submits it
+    pub fn execute(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, world: &W) -> anyhow::Result<()> {
+        if self.compiled.is_none() {
+            self.compile(device)?;
+        }
+        let Some(compiled) = &self.compiled else {
+            return Ok(());
+        };
+
+        let mut views = compiled.views.clone();
+        for index in &compiled.order {
+            let pass = &self.passes[*index];
+            let used = pass.reads.iter().chain(pass.writes.iter());
+            for id in used {
+                if matches!(self.resources[id.0].kind, ResourceKind::ImportedTexture) {
+                    let Some(view) = self.imported.get(id) else {
+                        anyhow::bail!(
+                            "Render graph texture {:?} used by {:?} was never bound",
+                            self.resources[id.0].name,
+                            pass.name
+                        );
+                    };
+                    views.insert(*id, view.clone());
+                }
+            }
+        }
+        let resources = PassResources {
+            resources: &self.resources,
+            views: &views,
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Render graph encoder"),
+        });
+        for index in &compiled.order {
+            let pass = &self.passes[*index];
+            match &pass.run {
+                PassFn::Render(run) => {
+                    let color_attachments = pass
+                        .colors
+                        .iter()
+                        .map(|(id, load, resolve)| {
+                            Some(wgpu::RenderPassColorAttachment {
+                                view: resources.view(*id),
+                                resolve_target: resolve.map(|resolve| resources.view(resolve)),
+                                ops: wgpu::Operations {
+                                    load: match load {
+                                        Load::Clear => wgpu::LoadOp::Clear(self.resources[id.0].clear_color),
+                                        Load::Load => wgpu::LoadOp::Load,
+                                    },
+                                    store: wgpu::StoreOp::Store,
+                                },
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    // Read-only depth has no ops, the contents stay intact for later passes
+                    let depth_stencil_attachment = pass.depth.map(|(id, load, write)| {
+                        wgpu::RenderPassDepthStencilAttachment {
+                            view: resources.view(id),
+                            depth_ops: write.then(|| wgpu::Operations {
+                                load: match load {
+                                    Load::Clear => wgpu::LoadOp::Clear(self.resources[id.0].clear_depth),
+                                    Load::Load => wgpu::LoadOp::Load,
+                                },
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        }
+                    });
+
+                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some(&pass.name),
+                        color_attachments: &color_attachments,
+                        depth_stencil_attachment,
+                        occlusion_query_set: None,
+                        timestamp_writes: None,
+                    });
+                    run(&mut render_pass, &resources, world);
+                }
+                PassFn::Encode(run) => run(&mut encoder, &resources, world),
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        Ok(())
+    }
+}
+
+pub struct PassBuilder<'g, W> {
+    graph: &'g mut RenderGraph<W>,
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    colors: Vec<(ResourceId, Load, Option<ResourceId>)>,
+    depth: Option<(ResourceId, Load, bool)>,
+    side_effect: bool,
+}
+
+impl<W> PassBuilder<'_, W> {
+    // Sampled texture or buffer consumed by the pass
+    pub fn read(mut self, id: ResourceId) -> Self {
+        self.reads.push(id);
+        self
+    }
+
+    // Storage texture or buffer written outside of attachments
+    pub fn write(mut self, id: ResourceId) -> Self {
+        self.writes.push(id);
+        self
+    }
+
+    pub fn color(mut self, id: ResourceId, load: Load) -> Self {
+        self.colors.push((id, load, None));
+        self.writes.push(id);
+        if load == Load::Load {
+            self.reads.push(id);
+        }
+        self
+    }
+
+    // Resolves the multisampled color attachment added last into `target` at the
+    // end of the pass
+    pub fn resolve(mut self, target: ResourceId) -> Self {
+        let Some((_, _, resolve)) = self.colors.last_mut() else {
+            panic!("render graph pass {:?} resolves without a color attachment", self.name);
+        };
+        *resolve = Some(target);
+        self.writes.push(target);
+        self
+    }
+
+    pub fn depth(mut self, id: ResourceId, load: Load) -> Self {
+        self.depth = Some((id, load, true));
+        self.writes.push(id);
+        if load == Load::Load {
+            self.reads.push(id);
+        }
+        self
+    }
+
+    // Depth tested against but not written, e.g. transparent geometry. Pipelines
+    // drawn in the pass must have depth writes disabled.
+    pub fn depth_read_only(mut self, id: ResourceId) -> Self {
+        self.depth = Some((id, Load::Load, false));
+        self.reads.push(id);
+        self
+    }
+
+    // Keeps the pass even if nothing reads its outputs (readbacks, captures)
+    pub fn side_effect(mut self) -> Self {
+        self.side_effect = true;
+        self
+    }
+
+    pub fn render(self, run: impl Fn(&mut wgpu::RenderPass<'_>, &PassResources<'_>, &W) + 'static) {
+        self.finish(PassFn::Render(Box::new(run)));
+    }
+
+    pub fn encode(self, run: impl Fn(&mut wgpu::CommandEncoder, &PassResources<'_>, &W) + 'static) {
+        self.finish(PassFn::Encode(Box::new(run)));
+    }
+
+    fn finish(self, run: PassFn<W>) {
+        self.graph.compiled = None;
+        self.graph.passes.push(Pass {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            colors: self.colors,
+            depth: self.depth,
+            side_effect: self.side_effect,
+            run,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    fn order(graph: &RenderGraph<()>, plan: &Plan) -> Vec<String> {
+        plan.order.iter().map(|i| graph.passes[*i].name.clone()).collect()
+    }
+
+    fn slot(plan: &Plan, id: ResourceId) -> usize {
+        plan.placements.iter().find(|(placed, _)| *placed == id).unwrap().1
+    }
+
+    #[test]
+    fn read_write_chain_runs_in_declaration_order() {
+        let mut graph = RenderGraph::<()>::new(64, 64);
+        let surface = graph.import_texture("surface");
+        let accum = graph.create_texture("accum", TextureDesc::new(FORMAT));
+        // Declared before the passes it reads from
+        graph.add_pass("composite").read(accum).color(surface, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("opaque").color(accum, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("transparent").color(accum, Load::Load).render(|_, _, _| {});
+        graph.add_pass("overlay").color(accum, Load::Load).render(|_, _, _| {});
+
+        let plan = graph.plan().unwrap();
+        assert_eq!(order(&graph, &plan), ["opaque", "transparent", "overlay", "composite"]);
+    }
+
+    #[test]
+    fn passes_without_consumers_are_culled() {
+        let mut graph = RenderGraph::<()>::new(64, 64);
+        let surface = graph.import_texture("surface");
+        let scene = graph.create_texture("scene", TextureDesc::new(FORMAT));
+        let unused = graph.create_texture("unused", TextureDesc::new(FORMAT));
+        let readback = graph.create_texture("readback", TextureDesc::new(FORMAT));
+        graph.add_pass("scene").color(scene, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("unused").color(unused, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("unused_consumer").read(unused).write(readback).encode(|_, _, _| {});
+        graph.add_pass("capture").read(scene).side_effect().encode(|_, _, _| {});
+        graph.add_pass("present").read(scene).color(surface, Load::Clear).render(|_, _, _| {});
+
+        let plan = graph.plan().unwrap();
+        assert_eq!(order(&graph, &plan), ["scene", "capture", "present"]);
+        assert_eq!(plan.placements.len(), 1);
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let mut graph = RenderGraph::<()>::new(64, 64);
+        let a = graph.create_texture("a", TextureDesc::new(FORMAT));
+        let b = graph.create_texture("b", TextureDesc::new(FORMAT));
+        graph.add_pass("first").read(a).color(b, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("second").read(b).color(a, Load::Clear).side_effect().render(|_, _, _| {});
+
+        let error = graph.plan().err().unwrap().to_string();
+        assert!(error.contains("dependency cycle"), "{error}");
+        assert!(error.contains("first") && error.contains("second"), "{error}");
+    }
+
+    #[test]
+    fn textures_alias_once_their_lifetimes_end() {
+        let mut graph = RenderGraph::<()>::new(64, 64);
+        let surface = graph.import_texture("surface");
+        let first = graph.create_texture("first", TextureDesc::new(FORMAT));
+        let second = graph.create_texture("second", TextureDesc::new(FORMAT));
+        let third = graph.create_texture("third", TextureDesc::new(FORMAT));
+        let half = graph.create_texture("half", TextureDesc::new(FORMAT).size(TextureSize::Scaled(0.5)));
+        graph.add_pass("a").color(first, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("b").read(first).color(second, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("c").read(second).color(third, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("d").read(third).color(half, Load::Clear).render(|_, _, _| {});
+        graph.add_pass("e").read(half).color(surface, Load::Clear).render(|_, _, _| {});
+
+        let plan = graph.plan().unwrap();
+        // `second` overlaps both neighbours, `third` takes over `first` after b.
+        // `half` is free to alias by lifetime but has a different size.
+        assert_eq!(slot(&plan, first), slot(&plan, third));
+        assert_ne!(slot(&plan, first), slot(&plan, second));
+        assert_ne!(slot(&plan, half), slot(&plan, first));
+        assert_eq!(plan.physical.len(), 3);
+        assert_eq!((plan.physical[slot(&plan, half)].width, plan.physical[slot(&plan, half)].height), (32, 32));
+    }
+
+    #[test]
+    fn usages_follow_how_passes_access_textures() {
+        let mut graph = RenderGraph::<()>::new(64, 64);
+        let surface = graph.import_texture("surface");
+        let depth = graph.create_texture("depth", TextureDesc::new(wgpu::TextureFormat::Depth32Float).sample_count(4));
+        let color = graph.create_texture("color", TextureDesc::new(FORMAT).sample_count(4));
+        let resolved = graph.create_texture("resolved", TextureDesc::new(FORMAT));
+        graph.add_pass("opaque").color(color, Load::Clear).depth(depth, Load::Clear).render(|_, _, _| {});
+        graph
+            .add_pass("transparent")
+            .color(color, Load::Load)
+            .resolve(resolved)
+            .depth_read_only(depth)
+            .render(|_, _, _| {});
+        graph.add_pass("present").read(resolved).color(surface, Load::Clear).render(|_, _, _| {});
+
+        let plan = graph.plan().unwrap();
+        assert_eq!(order(&graph, &plan), ["opaque", "transparent", "present"]);
+        // Loaded and depth tested attachments aren't sampled, multisampled
+        // textures must not be created with TEXTURE_BINDING on every backend
+        let usage = |id| plan.physical[slot(&plan, id)].usage;
+        assert_eq!(usage(depth), wgpu::TextureUsages::RENDER_ATTACHMENT);
+        assert_eq!(usage(color), wgpu::TextureUsages::RENDER_ATTACHMENT);
+        assert_eq!(usage(resolved), wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING);
+    }
+}
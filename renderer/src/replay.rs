@@ -0,0 +1,256 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use cgmath::MetricSpace;
+use instant::Duration;
+use crate::camera_path::CameraPose;
+use crate::input::InputEvent;
+use crate::state::State;
+
+// Replayed poses further apart than this count as a divergence
+const POSITION_TOLERANCE: f32 = 1e-3;
+const ANGLE_TOLERANCE: f32 = 1e-4;
+
+// The input that arrived before one update, the `dt` it ran with and where the
+// camera ended up. Recordings are text, one input event per line (see
+// `InputEvent::to_line`) followed by `frame <dt in nanoseconds> <pose>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    pub events: Vec<InputEvent>,
+    pub dt: Duration,
+    pub pose: Option<CameraPose>,
+}
+
+// Writes a session as it happens. Every frame is flushed so a crash still
+// leaves a usable recording.
+pub struct InputRecorder {
+    out: Option<Box<dyn Write>>,
+    frames: usize,
+}
+
+impl InputRecorder {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Self { out: Some(Box::new(out)), frames: 0 }
+    }
+
+    pub fn create(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::create(path)
+            .map_err(|e| anyhow::anyhow!("Unable to create input recording {}: {}", path.display(), e))?;
+        Ok(Self::new(std::io::BufWriter::new(file)))
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn record(&mut self, event: &InputEvent) {
+        let line = event.to_line();
+        self.write(|out| writeln!(out, "{}", line));
+    }
+
+    // Closes the frame, call after the update that consumed the recorded events
+    pub fn end_frame(&mut self, dt: Duration, pose: CameraPose) {
+        self.write(|out| {
+            writeln!(out, "frame {} {}", dt.as_nanos(), pose.to_line())?;
+            out.flush()
+        });
+        self.frames += 1;
+    }
+
+    fn write(&mut self, write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>) {
+        let Some(out) = self.out.as_mut() else {
+            return;
+        };
+        if let Err(e) = write(out.as_mut()) {
+            // One error is enough, the rest of the recording would be garbage anyway
+            log::error!("Input recording stopped: {}", e);
+            self.out = None;
+        }
+    }
+}
+
+// Feeds a recording back frame by frame and checks each camera pose against
+// the recorded one, so a replayed session can be compared with the original
+#[derive(Debug, Clone)]
+pub struct InputReplay {
+    frames: VecDeque<RecordedFrame>,
+    frame: usize,
+    diverged: usize,
+}
+
+impl InputReplay {
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Unable to read input recording {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let mut frames = VecDeque::new();
+        let mut events = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let error = |e: anyhow::Error| anyhow::anyhow!("recording line {}: {}", number + 1, e);
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            match fields[..] {
+                [] => {}
+                ["frame", dt, ref pose @ ..] => {
+                    let dt = Duration::from_nanos(dt.parse().map_err(|e: std::num::ParseIntError| error(e.into()))?);
+                    let pose = if pose.is_empty() { None } else { Some(CameraPose::parse(pose).map_err(error)?) };
+                    frames.push_back(RecordedFrame { events: std::mem::take(&mut events), dt, pose });
+                }
+                _ => events.push(InputEvent::parse(line).map_err(error)?),
+            }
+        }
+        if !events.is_empty() {
+            log::warn!("Ignoring {} input events after the last recorded frame", events.len());
+        }
+        Ok(Self { frames, frame: 0, diverged: 0 })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // Frames replayed so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    // Frames whose pose didn't match the recording
+    pub fn diverged(&self) -> usize {
+        self.diverged
+    }
+
+    // Input and `dt` for the next update
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        let frame = self.frames.pop_front()?;
+        self.frame += 1;
+        Some(frame)
+    }
+
+    // Plays the remaining frames straight into `state`, as fast as the updates
+    // run. Works on a headless state, nothing is drawn. Returns the divergences.
+    pub fn run(&mut self, state: &mut State) -> usize {
+        while let Some(frame) = self.next_frame() {
+            for event in &frame.events {
+                state.input(event);
+            }
+            state.update(frame.dt);
+            self.check(&frame, state.camera_pose());
+        }
+        self.diverged
+    }
+
+    // Compares the pose after the update of `frame` with the recorded one
+    pub fn check(&mut self, frame: &RecordedFrame, pose: CameraPose) -> bool {
+        let Some(expected) = frame.pose else {
+            return true;
+        };
+        let matches = expected.position.distance(pose.position) <= POSITION_TOLERANCE
+            && (expected.yaw - pose.yaw).0.abs() <= ANGLE_TOLERANCE
+            && (expected.pitch - pose.pitch).0.abs() <= ANGLE_TOLERANCE
+            && (expected.fovy - pose.fovy).0.abs() <= ANGLE_TOLERANCE;
+        if !matches {
+            // Later frames usually follow the first divergence, only it is worth a line each
+            if self.diverged == 0 {
+                log::warn!("Replay diverged at frame {}: expected {}, got {}", self.frame, expected.to_line(), pose.to_line());
+            }
+            self.diverged += 1;
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::Binding;
+    use cgmath::{Deg, Point3};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use winit::keyboard::KeyCode;
+
+    // Lets the test read back what the recorder wrote
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn pose(x: f32, yaw: f32) -> CameraPose {
+        CameraPose {
+            position: Point3::new(x, 1.5, -0.1),
+            yaw: Deg(yaw).into(),
+            pitch: Deg(-12.5).into(),
+            fovy: Deg(45.0).into(),
+        }
+    }
+
+    #[test]
+    fn frames_round_trip_through_a_recording() {
+        let frames = [
+            RecordedFrame {
+                events: vec![
+                    InputEvent::Button(Binding::Key(KeyCode::KeyW), true),
+                    InputEvent::MouseMotion(3.0, -1.5),
+                ],
+                dt: Duration::from_nanos(16_666_667),
+                pose: Some(pose(0.0, -90.0)),
+            },
+            // No input at all still makes a frame
+            RecordedFrame { events: Vec::new(), dt: Duration::from_nanos(1), pose: Some(pose(0.25, -89.0)) },
+            RecordedFrame {
+                events: vec![InputEvent::Button(Binding::Key(KeyCode::KeyW), false)],
+                dt: Duration::from_secs(2),
+                pose: Some(pose(1.0 / 3.0, 270.0)),
+            },
+        ];
+
+        let out = Shared::default();
+        let mut recorder = InputRecorder::new(out.clone());
+        for frame in &frames {
+            for event in &frame.events {
+                recorder.record(event);
+            }
+            recorder.end_frame(frame.dt, frame.pose.unwrap());
+        }
+        assert_eq!(recorder.frames(), frames.len());
+
+        let text = String::from_utf8(out.0.borrow().clone()).unwrap();
+        let mut replay = InputReplay::parse(&text).unwrap();
+        for expected in &frames {
+            let frame = replay.next_frame().unwrap();
+            assert_eq!(frame.events, expected.events);
+            assert_eq!(frame.dt, expected.dt);
+            // Poses go through degrees as text, so they only match within the tolerance
+            assert!(replay.check(&frame, expected.pose.unwrap()), "{}", expected.pose.unwrap().to_line());
+        }
+        assert!(replay.is_finished());
+        assert_eq!(replay.diverged(), 0);
+    }
+
+    #[test]
+    fn frames_without_a_pose_are_not_checked() {
+        let mut replay = InputReplay::parse("down KeyW\nframe 5\n").unwrap();
+        let frame = replay.next_frame().unwrap();
+        assert_eq!(frame.pose, None);
+        assert!(replay.check(&frame, pose(100.0, 0.0)));
+    }
+
+    #[test]
+    fn divergence_is_counted() {
+        let mut replay = InputReplay::parse(&format!("frame 5 {}\n", pose(0.0, 0.0).to_line())).unwrap();
+        let frame = replay.next_frame().unwrap();
+        assert!(!replay.check(&frame, pose(0.01, 0.0)));
+        assert_eq!(replay.diverged(), 1);
+    }
+}
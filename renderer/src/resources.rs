@@ -2,10 +2,15 @@ use std::io::{BufReader, Cursor};
 use wgpu::util::DeviceExt;
 use mikktspace;
 use crate::{model, texture};
+use crate::assets::{AssetServer, Handle};
 
 pub fn get_res_path(file_name: &str) -> std::path::PathBuf {
+    res_dir().join(file_name)
+}
+
+pub fn res_dir() -> std::path::PathBuf {
     if let Ok(res_dir) = std::env::var("RES_DIR") {
-        return std::path::Path::new(&res_dir).join(file_name);
+        return std::path::PathBuf::from(res_dir);
     }
 
     let mut dir = std::env::current_exe()
@@ -16,14 +21,43 @@ pub fn get_res_path(file_name: &str) -> std::path::PathBuf {
     loop {
         let candidate = dir.join("res");
         if candidate.exists() {
-            return candidate.join(file_name);
+            return candidate;
         }
         if !dir.pop() {
             break; // reached filesystem root
         }
     }
 
-    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res").join(file_name)}
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("res")
+}
+
+// Settings changed at runtime (bindings, bookmarks) live outside `res_dir`, which
+// is build output and watched for asset changes. `USER_DIR` if set, otherwise
+// the platform config directory, e.g. ~/.config/renderer on Linux.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn user_dir() -> std::path::PathBuf {
+    if let Ok(user_dir) = std::env::var("USER_DIR") {
+        return std::path::PathBuf::from(user_dir);
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join(env!("CARGO_PKG_NAME")))
+        .unwrap_or_else(|| std::path::PathBuf::from("user"))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn get_user_path(file_name: &str) -> std::path::PathBuf {
+    user_dir().join(file_name)
+}
+
+// Writes a file to `user_dir`, creating the directory on first save
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_user_file(file_name: &str, contents: &str) -> std::io::Result<()> {
+    let path = get_user_path(file_name);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)
+}
 
 #[cfg(target_arch = "wasm32")]
 fn format_url(file_name: &str) -> reqwest::Url {
@@ -69,14 +103,94 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
     Ok(data)
 }
 
+// Only color maps are authored in sRGB, everything else (map_Bump, norm, map_Pr,
+// map_Pm, map_ao, map_d, map_Ns) holds linear data
+pub fn mtl_map_color_space(key: &str) -> texture::ColorSpace {
+    match key {
+        "map_Kd" | "map_Ka" | "map_Ks" | "map_Ke" => texture::ColorSpace::Srgb,
+        _ => texture::ColorSpace::Linear,
+    }
+}
+
 pub async fn load_texture(
     file_name: &str,
     device: &wgpu::Device,
     queue: &wgpu::Queue,
+    color_space: texture::ColorSpace,
+    mipmaps: Option<&mut texture::MipmapGenerator>,
 ) -> anyhow::Result<texture::Texture> 
 {
     let data = load_binary(file_name).await?;
-    texture::Texture::from_bytes(device, queue, &data, file_name)
+    texture::Texture::from_bytes(device, queue, &data, file_name, color_space, mipmaps)
+}
+
+// Engine-side MTL parameters (PBR extension keys, `alpha_mode`) end up in
+// tobj's `unknown_param`. Map statements may carry options before the file name.
+fn mtl_param_map<'a>(m: &'a tobj::Material, key: &str) -> Option<&'a str> {
+    m.unknown_param.get(key).and_then(|value| value.split_whitespace().last())
+}
+
+fn mtl_param_f32(m: &tobj::Material, key: &str) -> Option<f32> {
+    let value = m.unknown_param.get(key)?;
+    match value.trim().parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            log::warn!("Material {:?} has an invalid {} {:?}, ignoring it", m.name, key, value);
+            None
+        }
+    }
+}
+
+// A linear data map, or white (no occlusion, full roughness/metallic factor, opaque) when
+// there is none. Broken maps fall back the same way with a warning.
+async fn load_data_map(
+    file_name: Option<&str>,
+    material: &str,
+    key: &str,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    assets: &mut AssetServer,
+) -> Handle<texture::Texture> {
+    let white = |assets: &mut AssetServer| {
+        assets.texture_or_insert_with("white_linear", || {
+            texture::Texture::from_color(device, queue, [255; 4], "white_linear_texture", texture::ColorSpace::Linear)
+        })
+    };
+    let Some(file_name) = file_name else {
+        return white(assets);
+    };
+    let mipmaps = assets.texture_settings.mipmaps;
+    match assets.load_texture(file_name, device, queue, mtl_map_color_space(key), mipmaps).await {
+        Ok(handle) => handle,
+        Err(e) => {
+            log::warn!("Unable to load {} {:?} for material {:?} ({}), using white", key, file_name, material, e);
+            white(assets)
+        }
+    }
+}
+
+// `d` below 1 means a translucent material and an opacity map (map_d) a cutout,
+// the map scales the diffuse texture's alpha. The `alpha_mode`
+// (opaque/mask/blend), `alpha_cutoff` and `two_sided` statements are engine
+// extensions that override this.
+fn mtl_alpha_mode(m: &tobj::Material) -> model::AlphaMode {
+    let inferred = if m.dissolve < 1.0 {
+        model::AlphaMode::Blend
+    } else if !m.dissolve_texture.is_empty() {
+        model::AlphaMode::Mask
+    } else {
+        model::AlphaMode::Opaque
+    };
+    match m.unknown_param.get("alpha_mode").map(|v| v.trim()) {
+        Some("opaque") => model::AlphaMode::Opaque,
+        Some("mask") => model::AlphaMode::Mask,
+        Some("blend") => model::AlphaMode::Blend,
+        Some(other) => {
+            log::warn!("Material {:?} has unknown alpha_mode {:?}, using {:?}", m.name, other, inferred);
+            inferred
+        }
+        None => inferred,
+    }
 }
 
 pub async fn load_model(
@@ -84,6 +198,7 @@ pub async fn load_model(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
     layout: &wgpu::BindGroupLayout,
+    assets: &mut AssetServer,
 ) -> anyhow::Result<model::Model> 
 {
     let obj_text = load_string(file_name).await?;
@@ -98,78 +213,185 @@ pub async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+            match load_string(&p).await {
+                Ok(mat_text) => tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text))),
+                Err(e) => {
+                    log::warn!("Unable to read material library {:?}: {}", p, e);
+                    Err(tobj::LoadError::OpenFileFailed)
+                }
+            }
         },
     )
     .await?;
 
+    let mut obj_materials = obj_materials.unwrap_or_else(|e| {
+        log::warn!("Unable to load materials for {:?} ({}), using default material", file_name, e);
+        Vec::new()
+    });
+    if obj_materials.is_empty() {
+        obj_materials.push(tobj::Material {
+            name: "default".to_string(),
+            diffuse: [1.0, 1.0, 1.0],
+            specular: [0.5, 0.5, 0.5],
+            shininess: 32.0,
+            ..Default::default()
+        });
+    }
+
+    let mipmaps = assets.texture_settings.mipmaps;
     let mut materials = Vec::new();
-    for m in obj_materials? {
-        let diffuse_texture = load_texture(&m.diffuse_texture, device, queue).await?;
-        let normal_texture = load_texture(&m.normal_texture, device, queue).await?;
-        let sampler = device.create_sampler(
-            &wgpu::SamplerDescriptor {
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Nearest,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                ..Default::default()
+    for m in obj_materials {
+        let diffuse_texture = if m.diffuse_texture.is_empty() {
+            log::warn!("Material {:?} has no diffuse texture (map_Kd), using white with Kd color", m.name);
+            assets.texture_or_insert_with("white", || texture::Texture::white(device, queue))
+        } else {
+            match assets.load_texture(&m.diffuse_texture, device, queue, mtl_map_color_space("map_Kd"), mipmaps).await {
+                Ok(handle) => handle,
+                Err(e) => {
+                    log::warn!("Unable to load diffuse texture {:?} for material {:?} ({}), using missing texture", m.diffuse_texture, m.name, e);
+                    assets.texture_or_insert_with("missing", || texture::Texture::missing(device, queue))
+                }
+            }
+        };
+        let normal_texture = if m.normal_texture.is_empty() {
+            log::warn!("Material {:?} has no normal texture (map_Bump), using flat normal", m.name);
+            assets.texture_or_insert_with("flat_normal", || texture::Texture::flat_normal(device, queue))
+        } else {
+            match assets.load_texture(&m.normal_texture, device, queue, mtl_map_color_space("map_Bump"), mipmaps).await {
+                Ok(handle) => handle,
+                Err(e) => {
+                    log::warn!("Unable to load normal texture {:?} for material {:?} ({}), using flat normal", m.normal_texture, m.name, e);
+                    assets.texture_or_insert_with("flat_normal", || texture::Texture::flat_normal(device, queue))
+                }
+            }
+        };
+
+        // PBR maps are either packed into one ORM texture (occlusion, roughness and
+        // metallic in red, green and blue, as in glTF) or given one per map_ao,
+        // map_Pr and map_Pm
+        let packed = mtl_param_map(&m, "map_ORM");
+        let occlusion_map = packed.or(mtl_param_map(&m, "map_ao"));
+        let roughness_map = packed.or(mtl_param_map(&m, "map_Pr"));
+        let metallic_map = packed.or(mtl_param_map(&m, "map_Pm"));
+        let occlusion_texture = load_data_map(occlusion_map, &m.name, "map_ao", device, queue, assets).await;
+        let roughness_texture = load_data_map(roughness_map, &m.name, "map_Pr", device, queue, assets).await;
+        let metallic_texture = load_data_map(metallic_map, &m.name, "map_Pm", device, queue, assets).await;
+        let opacity_map = (!m.dissolve_texture.is_empty()).then_some(m.dissolve_texture.as_str());
+        let opacity_texture = load_data_map(opacity_map, &m.name, "map_d", device, queue, assets).await;
+        // The fallback maps are white, so without a map the scalar is the value.
+        // A map without a scalar is used as is.
+        let roughness = mtl_param_f32(&m, "Pr").unwrap_or(if roughness_map.is_some() { 1.0 } else { 0.0 });
+        let metallic = mtl_param_f32(&m, "Pm").unwrap_or(if metallic_map.is_some() { 1.0 } else { 0.0 });
+
+        // Kd only tints when there is no diffuse map, otherwise it would darken the texture
+        let diffuse = if m.diffuse_texture.is_empty() { m.diffuse } else { [1.0; 3] };
+        let defaults = model::MaterialUniform::default();
+        let alpha_mode = mtl_alpha_mode(&m);
+        let alpha_cutoff = match m.unknown_param.get("alpha_cutoff").map(|v| v.trim().parse::<f32>()) {
+            Some(Ok(cutoff)) => cutoff,
+            Some(Err(_)) => {
+                log::warn!("Material {:?} has an invalid alpha_cutoff, using {}", m.name, defaults.alpha_cutoff);
+                defaults.alpha_cutoff
             }
+            None => defaults.alpha_cutoff,
+        };
+        let two_sided = m
+            .unknown_param
+            .get("two_sided")
+            .is_some_and(|v| matches!(v.trim(), "1" | "on" | "true"));
+        let uniform = model::MaterialUniform {
+            diffuse: [diffuse[0], diffuse[1], diffuse[2], m.dissolve],
+            specular: m.specular,
+            shininess: if m.shininess > 0.0 { m.shininess } else { defaults.shininess },
+            alpha_cutoff,
+            roughness,
+            metallic,
+            ..defaults
+        };
+        let sampler = texture::Texture::create_sampler(
+            device,
+            assets.texture_settings.filter,
+            wgpu::AddressMode::ClampToEdge,
         );
 
-        materials.push(model::Material::new(
+        let mut material = model::Material::new(
             device,
             &m.name,
-            diffuse_texture,
-            normal_texture,
+            &assets.textures,
+            model::MaterialTextures {
+                diffuse: diffuse_texture,
+                normal: normal_texture,
+                occlusion: occlusion_texture,
+                roughness: roughness_texture,
+                metallic: metallic_texture,
+                opacity: opacity_texture,
+            },
             sampler,
+            uniform,
             layout,
-        ))
+        );
+        material.alpha_mode = alpha_mode;
+        material.two_sided = two_sided;
+        materials.push(material);
     }
 
     let meshes = models
         .into_iter()
         .map(|m| {
-                let mut tangents = vec![[0.0; 4]; m.mesh.positions.len() / 3];
+            // Tangents need both texture coordinates and normals, meshes missing
+            // either get the zero tangent like they get the zero normal
+            let has_texcoords = !m.mesh.texcoords.is_empty();
+            let has_normals = !m.mesh.normals.is_empty();
+            if !has_texcoords {
+                log::warn!("Mesh {:?} in {:?} has no texture coordinates, using (0, 0) and no tangents", m.name, file_name);
+            }
+            let mut tangents = vec![[0.0; 4]; m.mesh.positions.len() / 3];
+            if has_texcoords && has_normals {
                 let mut wrapper = model::TobjMeshWrapper { mesh: &m.mesh, tangents: &mut tangents };
                 mikktspace::generate_tangents(&mut wrapper);
-                let vertices = (0..m.mesh.positions.len() / 3)
-                .map(|i| {
-                    let tangent = tangents[i];
-
-                    if m.mesh.normals.is_empty(){
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]],
-                            normal: [0.0, 0.0, 0.0],
-                            tangent: [0.0, 0.0, 0.0, 0.0],
-                        }
+            }
+            let vertices = (0..m.mesh.positions.len() / 3)
+                .map(|i| model::ModelVertex {
+                    position: [
+                        m.mesh.positions[i * 3],
+                        m.mesh.positions[i * 3 + 1],
+                        m.mesh.positions[i * 3 + 2],
+                    ],
+                    tex_coords: if has_texcoords {
+                        [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]]
+                    } else {
+                        [0.0, 0.0]
+                    },
+                    normal: if has_normals {
+                        [m.mesh.normals[i * 3], m.mesh.normals[i * 3 + 1], m.mesh.normals[i * 3 + 2]]
                     } else {
-                        model::ModelVertex {
-                            position: [
-                                m.mesh.positions[i * 3],
-                                m.mesh.positions[i * 3 + 1],
-                                m.mesh.positions[i * 3 + 2],
-                            ],
-                            tex_coords: [m.mesh.texcoords[i * 2], 1.0 - m.mesh.texcoords[i * 2 + 1]],
-                            normal: [
-                                m.mesh.normals[i * 3],
-                                m.mesh.normals[i * 3 + 1],
-                                m.mesh.normals[i * 3 + 2],
-                            ],
-                            tangent:[tangent[0], tangent[1], tangent[2], tangent[3]],
-                        }
-                    }
+                        [0.0, 0.0, 0.0]
+                    },
+                    tangent: tangents[i],
                 })
                 .collect::<Vec<_>>();
 
+            let mut bounds_min = [f32::MAX; 3];
+            let mut bounds_max = [f32::MIN; 3];
+            for vertex in &vertices {
+                for axis in 0..3 {
+                    bounds_min[axis] = bounds_min[axis].min(vertex.position[axis]);
+                    bounds_max[axis] = bounds_max[axis].max(vertex.position[axis]);
+                }
+            }
+            if vertices.is_empty() {
+                (bounds_min, bounds_max) = ([0.0; 3], [0.0; 3]);
+            }
+
+            let wireframe_buffer = (!device.features().contains(wgpu::Features::POLYGON_MODE_LINE)).then(|| {
+                let expanded = m.mesh.indices.iter().map(|i| vertices[*i as usize]).collect::<Vec<_>>();
+                device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{:?} Wireframe Vertex Buffer", file_name)),
+                    contents: bytemuck::cast_slice(&expanded),
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+            });
+
             let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some(&format!("{:?} Vertex Buffer", file_name)),
                 contents: bytemuck::cast_slice(&vertices),
@@ -187,6 +409,9 @@ pub async fn load_model(
                 index_buffer,
                 num_elements: m.mesh.indices.len() as u32,
                 material: m.mesh.material_id.unwrap_or(0),
+                wireframe_buffer,
+                bounds_min,
+                bounds_max,
             }
         })
         .collect::<Vec<_>>();
@@ -0,0 +1,480 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use crate::reflect::ShaderReflection;
+
+// Feature flags a shader is compiled with, ordered so they can key the cache
+pub type ShaderDefines = BTreeSet<String>;
+
+// Shaders baked into the binary, used in release builds and as a fallback when
+// the shader directory can't be read
+const EMBEDDED: &[(&str, &str)] = &[
+    ("common/camera.wgsl", include_str!("./shaders/common/camera.wgsl")),
+    ("common/light.wgsl", include_str!("./shaders/common/light.wgsl")),
+    ("common/vertex.wgsl", include_str!("./shaders/common/vertex.wgsl")),
+    ("common/material.wgsl", include_str!("./shaders/common/material.wgsl")),
+    ("common/oit.wgsl", include_str!("./shaders/common/oit.wgsl")),
+    ("shader.wgsl", include_str!("./shaders/shader.wgsl")),
+    ("bw_shader.wgsl", include_str!("./shaders/bw_shader.wgsl")),
+    ("unlit.wgsl", include_str!("./shaders/unlit.wgsl")),
+    ("oit_composite.wgsl", include_str!("./shaders/oit_composite.wgsl")),
+    ("debug.wgsl", include_str!("./shaders/debug.wgsl")),
+    ("debug_lines.wgsl", include_str!("./shaders/debug_lines.wgsl")),
+    ("error.wgsl", include_str!("./shaders/error.wgsl")),
+    ("text_overlay.wgsl", include_str!("./shaders/text_overlay.wgsl")),
+];
+
+#[derive(Debug, Clone)]
+pub struct ShaderError {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+    // Full diagnostic with the offending source line, for the log
+    pub report: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl ShaderError {
+    pub fn new(file: &str, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            line: 0,
+            column: 0,
+            report: message.clone(),
+            message,
+        }
+    }
+}
+
+// Directory WGSL is read from in development mode: `SHADER_DIR` if set, otherwise
+// the crate's source tree in debug builds. `None` means shaders are embedded only.
+pub fn shader_dir() -> Option<PathBuf> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+    if let Ok(dir) = std::env::var("SHADER_DIR") {
+        return Some(PathBuf::from(dir));
+    }
+    if cfg!(debug_assertions) {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("shaders");
+        if dir.exists() {
+            return Some(dir);
+        }
+    }
+    None
+}
+
+pub fn embedded(file_name: &str) -> Option<&'static str> {
+    EMBEDDED
+        .iter()
+        .find(|(name, _)| *name == file_name)
+        .map(|(_, source)| *source)
+}
+
+pub fn load_wgsl(file_name: &str) -> Result<String, ShaderError> {
+    if let Some(dir) = shader_dir() {
+        match std::fs::read_to_string(dir.join(file_name)) {
+            Ok(source) => return Ok(source),
+            Err(e) => log::warn!("Unable to read shader {:?} from {:?} ({}), using embedded copy", file_name, dir, e),
+        }
+    }
+
+    embedded(file_name)
+        .map(str::to_string)
+        .ok_or_else(|| ShaderError::new(file_name, "unknown shader".to_string()))
+}
+
+// WGSL after `#include`/`#ifdef` expansion, with a map back to the original files
+#[derive(Debug)]
+pub struct ComposedShader {
+    pub file: String,
+    pub source: String,
+    // Every file that was read, so edits to shared modules can be traced back
+    pub dependencies: HashSet<String>,
+    lines: Vec<(String, u32)>,
+}
+
+impl ComposedShader {
+    // Parses and validates with naga so broken shaders never reach the device.
+    // Error locations point at the file and line the code came from.
+    pub fn validate(&self) -> Result<(naga::Module, naga::valid::ModuleInfo), ShaderError> {
+        let module = naga::front::wgsl::parse_str(&self.source).map_err(|e| {
+            let location = e.location(&self.source);
+            self.error_at(
+                location.map(|l| (l.line_number, l.line_position)),
+                e.message().to_string(),
+                e.emit_to_string_with_path(&self.source, &self.file),
+            )
+        })?;
+
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+            .validate(&module)
+            .map_err(|e| {
+                let location = e.location(&self.source);
+                self.error_at(
+                    location.map(|l| (l.line_number, l.line_position)),
+                    e.as_inner().to_string(),
+                    e.emit_to_string_with_path(&self.source, &self.file),
+                )
+            })?;
+
+        Ok((module, info))
+    }
+
+    pub fn reflect(&self) -> Result<ShaderReflection, ShaderError> {
+        let (module, info) = self.validate()?;
+        ShaderReflection::new(&module, &info).map_err(|e| ShaderError::new(&self.file, e))
+    }
+
+    fn error_at(&self, location: Option<(u32, u32)>, message: String, report: String) -> ShaderError {
+        let origin = location.and_then(|(line, column)| {
+            self.lines
+                .get(line.saturating_sub(1) as usize)
+                .map(|(file, line)| (file.clone(), *line, column))
+        });
+        match origin {
+            Some((file, line, column)) => ShaderError { file, line, column, message, report },
+            None => ShaderError { file: self.file.clone(), line: 0, column: 0, message, report },
+        }
+    }
+}
+
+// Expands `#include "path"` (relative to the shader directory, each file included
+// once), `#define NAME`, `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`.
+pub fn compose(file_name: &str, defines: &ShaderDefines) -> Result<ComposedShader, ShaderError> {
+    compose_tracked(file_name, defines, &mut HashSet::new(), &load_wgsl)
+}
+
+// Like `compose`, but on failure `read` gets every file read up to that point.
+// Sources come from `load`, which is `load_wgsl` outside of tests.
+fn compose_tracked(
+    file_name: &str,
+    defines: &ShaderDefines,
+    read: &mut HashSet<String>,
+    load: &dyn Fn(&str) -> Result<String, ShaderError>,
+) -> Result<ComposedShader, ShaderError> {
+    let mut composer = Composer {
+        load,
+        defines: defines.clone(),
+        included: HashSet::new(),
+        source: String::new(),
+        lines: Vec::new(),
+    };
+    if let Err(e) = composer.include(file_name) {
+        *read = composer.included;
+        return Err(e);
+    }
+
+    Ok(ComposedShader {
+        file: file_name.to_string(),
+        source: composer.source,
+        dependencies: composer.included,
+        lines: composer.lines,
+    })
+}
+
+struct Composer<'a> {
+    load: &'a dyn Fn(&str) -> Result<String, ShaderError>,
+    defines: ShaderDefines,
+    included: HashSet<String>,
+    source: String,
+    lines: Vec<(String, u32)>,
+}
+
+struct Conditional {
+    active: bool,
+    parent_active: bool,
+    seen_else: bool,
+}
+
+impl Composer<'_> {
+    fn include(&mut self, file_name: &str) -> Result<(), ShaderError> {
+        if !self.included.insert(file_name.to_string()) {
+            return Ok(());
+        }
+        let text = (self.load)(file_name)?;
+        let error = |line: usize, message: &str| ShaderError {
+            file: file_name.to_string(),
+            line: line as u32 + 1,
+            column: 1,
+            message: message.to_string(),
+            report: format!("{}:{}: {}", file_name, line + 1, message),
+        };
+
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let active = conditionals.last().is_none_or(|c| c.active);
+            let trimmed = line.trim();
+            if !trimmed.starts_with('#') {
+                if active {
+                    self.source.push_str(line);
+                    self.source.push('\n');
+                    self.lines.push((file_name.to_string(), index as u32 + 1));
+                }
+                continue;
+            }
+
+            let mut parts = trimmed[1..].split_whitespace();
+            let directive = parts.next().unwrap_or("");
+            let argument = parts.next();
+            match (directive, argument) {
+                ("ifdef", Some(name)) | ("ifndef", Some(name)) => {
+                    let defined = self.defines.contains(name);
+                    conditionals.push(Conditional {
+                        active: active && (defined == (directive == "ifdef")),
+                        parent_active: active,
+                        seen_else: false,
+                    });
+                }
+                ("else", None) => match conditionals.last_mut() {
+                    Some(c) if !c.seen_else => {
+                        c.active = c.parent_active && !c.active;
+                        c.seen_else = true;
+                    }
+                    _ => return Err(error(index, "#else without matching #ifdef")),
+                },
+                ("endif", None) => {
+                    if conditionals.pop().is_none() {
+                        return Err(error(index, "#endif without matching #ifdef"));
+                    }
+                }
+                ("define", Some(name)) if active => {
+                    self.defines.insert(name.to_string());
+                }
+                ("include", Some(path)) if active => {
+                    let path = path.trim_matches('"');
+                    self.include(path).map_err(|mut e| {
+                        if e.line == 0 {
+                            e = error(index, &format!("unable to include {:?}: {}", path, e.message));
+                        }
+                        e
+                    })?;
+                }
+                ("define", Some(_)) | ("include", Some(_)) => {}
+                _ => return Err(error(index, &format!("invalid directive {:?}", trimmed))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(error(text.lines().count(), "missing #endif"));
+        }
+        Ok(())
+    }
+}
+
+// A device module together with what naga reflected from its source
+#[derive(Clone)]
+pub struct CompiledShader {
+    pub module: wgpu::ShaderModule,
+    pub reflection: Arc<ShaderReflection>,
+}
+
+// Compiled modules keyed by file and define set
+#[derive(Default)]
+pub struct ShaderCache {
+    modules: HashMap<(String, ShaderDefines), CachedShader>,
+    // Files each failed compile read, so fixing any of them retries it
+    failed: HashMap<(String, ShaderDefines), HashSet<String>>,
+}
+
+struct CachedShader {
+    shader: CompiledShader,
+    dependencies: HashSet<String>,
+}
+
+impl ShaderCache {
+    pub fn get_or_compile(
+        &mut self,
+        device: &wgpu::Device,
+        file_name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<CompiledShader, ShaderError> {
+        let key = (file_name.to_string(), defines.clone());
+        if let Some(cached) = self.modules.get(&key) {
+            return Ok(cached.shader.clone());
+        }
+
+        let mut read = HashSet::new();
+        let composed = match compose_tracked(file_name, defines, &mut read, &load_wgsl) {
+            Ok(composed) => composed,
+            Err(e) => {
+                self.failed.insert(key, read);
+                return Err(e);
+            }
+        };
+        let reflection = match composed.reflect() {
+            Ok(reflection) => reflection,
+            Err(e) => {
+                self.failed.insert(key, composed.dependencies);
+                return Err(e);
+            }
+        };
+        self.failed.remove(&key);
+        let shader = CompiledShader {
+            module: create_shader_module(device, file_name, &composed.source),
+            reflection: Arc::new(reflection),
+        };
+        self.modules.insert(key, CachedShader {
+            shader: shader.clone(),
+            dependencies: composed.dependencies,
+        });
+        Ok(shader)
+    }
+
+    // Drops modules built from any of the changed files and returns the root
+    // shaders that need to be recompiled, including ones that failed last time
+    pub fn invalidate(&mut self, changed: &HashSet<String>) -> HashSet<String> {
+        let mut affected = HashSet::new();
+        self.modules.retain(|(file, _), cached| {
+            let stale = cached.dependencies.iter().any(|dep| changed.contains(dep));
+            if stale {
+                affected.insert(file.clone());
+            }
+            !stale
+        });
+        self.failed.retain(|(file, _), dependencies| {
+            let stale = dependencies.iter().any(|dep| changed.contains(dep));
+            if stale {
+                affected.insert(file.clone());
+            }
+            !stale
+        });
+        affected
+    }
+}
+
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    file_name: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file_name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Composes `file_name` from in-memory sources instead of the shader directory
+    fn compose_sources(file_name: &str, defines: &[&str], sources: &[(&str, &str)]) -> Result<ComposedShader, ShaderError> {
+        let sources = sources.iter().map(|(name, text)| (name.to_string(), text.to_string())).collect::<HashMap<_, _>>();
+        let load = |name: &str| sources.get(name).cloned().ok_or_else(|| ShaderError::new(name, "unknown shader".to_string()));
+        let defines = defines.iter().map(|define| define.to_string()).collect();
+        compose_tracked(file_name, &defines, &mut HashSet::new(), &load)
+    }
+
+    fn lines(composed: &ComposedShader) -> Vec<&str> {
+        composed.source.lines().collect()
+    }
+
+    #[test]
+    fn nested_conditionals() {
+        let source = "\
+#ifdef A
+a
+#ifndef B
+a_not_b
+#else
+a_b
+#endif
+#else
+not_a
+#ifdef B
+not_a_b
+#endif
+#endif
+always
+";
+        let compose = |defines| lines(&compose_sources("main.wgsl", defines, &[("main.wgsl", source)]).unwrap()).join(" ");
+        assert_eq!(compose(&[]), "not_a always");
+        assert_eq!(compose(&["A"]), "a a_not_b always");
+        assert_eq!(compose(&["A", "B"]), "a a_b always");
+        assert_eq!(compose(&["B"]), "not_a not_a_b always");
+    }
+
+    #[test]
+    fn defines_only_count_in_active_branches() {
+        let source = "#ifdef A\n#define B\n#endif\n#define C\n#ifdef B\nb\n#endif\n#ifdef C\nc\n#endif\n";
+        let composed = compose_sources("main.wgsl", &[], &[("main.wgsl", source)]).unwrap();
+        assert_eq!(lines(&composed), ["c"]);
+    }
+
+    #[test]
+    fn unbalanced_directives() {
+        let error = |source| compose_sources("main.wgsl", &[], &[("main.wgsl", source)]).unwrap_err();
+
+        let e = error("a\n#endif\n");
+        assert_eq!((e.line, e.message.as_str()), (2, "#endif without matching #ifdef"));
+        let e = error("#ifdef A\n#else\n#else\n#endif\n");
+        assert_eq!((e.line, e.message.as_str()), (3, "#else without matching #ifdef"));
+        let e = error("#ifdef A\n#ifdef B\n#endif\n");
+        assert_eq!((e.line, e.message.as_str()), (4, "missing #endif"));
+        let e = error("#ifdef\n");
+        assert_eq!((e.line, e.message.as_str()), (1, "invalid directive \"#ifdef\""));
+    }
+
+    #[test]
+    fn files_are_included_once() {
+        // Both modules include the common one, which includes the root again
+        let composed = compose_sources(
+            "main.wgsl",
+            &[],
+            &[
+                ("main.wgsl", "#include \"a.wgsl\"\n#include \"b.wgsl\"\nmain\n"),
+                ("a.wgsl", "#include \"common.wgsl\"\na\n"),
+                ("b.wgsl", "#include \"common.wgsl\"\nb\n"),
+                ("common.wgsl", "#include \"main.wgsl\"\ncommon\n"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(lines(&composed), ["common", "a", "b", "main"]);
+        assert_eq!(composed.dependencies.len(), 4);
+    }
+
+    #[test]
+    fn include_errors_point_at_the_include() {
+        let e = compose_sources("main.wgsl", &[], &[("main.wgsl", "a\n#include \"missing.wgsl\"\n")]).unwrap_err();
+        assert_eq!((e.file.as_str(), e.line), ("main.wgsl", 2));
+        assert_eq!(e.message, "unable to include \"missing.wgsl\": unknown shader");
+
+        // Directive errors inside the included file keep their own location
+        let e = compose_sources(
+            "main.wgsl",
+            &[],
+            &[("main.wgsl", "#include \"common.wgsl\"\n"), ("common.wgsl", "a\nb\n#endif\n")],
+        )
+        .unwrap_err();
+        assert_eq!((e.file.as_str(), e.line), ("common.wgsl", 3));
+    }
+
+    #[test]
+    fn wgsl_errors_map_back_to_the_included_file() {
+        let composed = compose_sources(
+            "main.wgsl",
+            &["BROKEN"],
+            &[
+                ("main.wgsl", "#include \"common.wgsl\"\n\nfn main_fn() -> f32 {\n    return helper();\n}\n"),
+                (
+                    "common.wgsl",
+                    "// Helpers\n#ifdef UNUSED\nskipped\n#endif\nfn helper() -> f32 {\n#ifdef BROKEN\n    return undefined_value;\n#endif\n}\n",
+                ),
+            ],
+        )
+        .unwrap();
+        let e = composed.validate().unwrap_err();
+        assert_eq!((e.file.as_str(), e.line), ("common.wgsl", 7));
+        assert_eq!(e.column, 12);
+    }
+}
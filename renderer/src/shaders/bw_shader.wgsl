@@ -1,26 +1,18 @@
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-};
+#include "common/camera.wgsl"
+#include "common/vertex.wgsl"
+#include "common/material.wgsl"
+#include "common/oit.wgsl"
+
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-}
-
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    // Distance in front of the camera, for the OIT weight
+    @location(1) view_depth: f32,
 }
 
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-};
-
 @vertex
 fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
   var out: VertexOutput;
@@ -33,6 +25,7 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
 
   out.tex_coords = model.tex_coords; 
   out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+  out.view_depth = -(camera.view * model_matrix * vec4<f32>(model.position, 1.0)).z;
   return out;
 }
 
@@ -40,12 +33,35 @@ fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
+@group(0) @binding(3)
+var<uniform> material: MaterialUniform;
+@group(0) @binding(7)
+var t_opacity: texture_2d<f32>;
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-  let base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+fn shade(in: VertexOutput) -> vec4<f32> {
+  var base_color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * material.diffuse;
+  base_color.a *= textureSample(t_opacity, s_diffuse, in.tex_coords).r;
+#ifdef ALPHA_MASK
+  if base_color.a < material.alpha_cutoff {
+    discard;
+  }
+#endif
+#ifdef ALPHA_TO_COVERAGE
+  base_color.a = coverage_alpha(base_color.a, material.alpha_cutoff);
+#endif
   let grayscale_value = dot(base_color.rgb, vec3<f32>(0.299, 0.587, 0.114));
 
   return vec4<f32>(grayscale_value, grayscale_value, grayscale_value, base_color.a);
 }
 
+#ifdef WEIGHTED_OIT
+@fragment
+fn fs_main(in: VertexOutput) -> OitOutput {
+  return oit_output(shade(in), in.view_depth);
+}
+#else
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+  return shade(in);
+}
+#endif
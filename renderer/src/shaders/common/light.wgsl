@@ -0,0 +1,4 @@
+struct LightUniform {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
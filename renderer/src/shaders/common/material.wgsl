@@ -0,0 +1,15 @@
+struct MaterialUniform {
+    diffuse: vec4<f32>,
+    specular: vec3<f32>,
+    shininess: f32,
+    alpha_cutoff: f32,
+    // Scale the green channel of the roughness map and the blue channel of the metallic map
+    roughness: f32,
+    metallic: f32,
+};
+
+// Alpha for alpha-to-coverage cutouts: the cutoff becomes a ramp about one
+// pixel wide, so coverage smooths the edge without fading the inside
+fn coverage_alpha(alpha: f32, cutoff: f32) -> f32 {
+    return clamp((alpha - cutoff) / max(fwidth(alpha), 1e-4) + 0.5, 0.0, 1.0);
+}
@@ -0,0 +1,17 @@
+// Weighted blended order-independent transparency (McGuire & Bavoil 2013)
+struct OitOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+};
+
+// `view_depth` is the fragment's distance in front of the camera, closer
+// surfaces get larger weights. Based on view space rather than window depth so
+// it works the same with reverse-Z and infinite far planes.
+fn oit_output(color: vec4<f32>, view_depth: f32) -> OitOutput {
+    let z = abs(view_depth);
+    let weight = color.a * clamp(10.0 / (1e-5 + pow(z / 10.0, 3.0) + pow(z / 200.0, 6.0)), 1e-2, 3e3);
+    var out: OitOutput;
+    out.accum = vec4<f32>(color.rgb * color.a, color.a) * weight;
+    out.revealage = color.a;
+    return out;
+}
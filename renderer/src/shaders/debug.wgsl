@@ -0,0 +1,123 @@
+#include "common/camera.wgsl"
+#include "common/vertex.wgsl"
+
+// One DEBUG_* define selects what is visualized, see DebugView::define
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) view_pos: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec3<f32>,
+    @location(4) bitangent: vec3<f32>,
+    @location(5) barycentric: vec3<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(vertex_index) vertex_index: u32,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let normal_matrix = mat3x3<f32>(
+        instance.normal_matrix_0,
+        instance.normal_matrix_1,
+        instance.normal_matrix_2,
+    );
+    let model_matrix3x3 = mat3x3<f32>(
+        model_matrix[0].xyz,
+        model_matrix[1].xyz,
+        model_matrix[2].xyz,
+    );
+
+    var out: VertexOutput;
+    out.normal = normalize(normal_matrix * model.normal);
+    out.tangent = normalize(model_matrix3x3 * model.tangent.xyz);
+    out.bitangent = normalize(cross(out.normal, out.tangent)) * model.tangent.w;
+    out.view_pos = (camera.view * model_matrix * vec4<f32>(model.position, 1.0)).xyz;
+    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    out.tex_coords = model.tex_coords;
+
+    // Only meaningful for non-indexed draws, where every triangle has its own vertices
+    let corner = vertex_index % 3u;
+    out.barycentric = vec3<f32>(f32(corner == 0u), f32(corner == 1u), f32(corner == 2u));
+    return out;
+}
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var t_sampler: sampler;
+
+const WIREFRAME_COLOR: vec4<f32> = vec4<f32>(0.3, 1.0, 0.4, 1.0);
+
+// Blue, cyan, green, yellow, red, magenta for mips 0..5 and up
+fn mip_color(level: f32) -> vec3<f32> {
+    let colors = array<vec3<f32>, 6>(
+        vec3<f32>(0.0, 0.0, 1.0),
+        vec3<f32>(0.0, 1.0, 1.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(1.0, 1.0, 0.0),
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(1.0, 0.0, 1.0),
+    );
+    let clamped = clamp(level, 0.0, 5.0);
+    let index = u32(floor(clamped));
+    return mix(colors[index], colors[min(index + 1u, 5u)], fract(clamped));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var color = vec4<f32>(1.0, 0.0, 1.0, 1.0);
+#ifdef DEBUG_WIREFRAME
+    color = WIREFRAME_COLOR;
+#ifdef WIREFRAME_BARYCENTRIC
+    // Keep only pixels within ~1px of a triangle edge
+    let width = fwidth(in.barycentric);
+    let edge = smoothstep(vec3<f32>(0.0), width * 1.5, in.barycentric);
+    let coverage = 1.0 - min(min(edge.x, edge.y), edge.z);
+    if coverage < 0.1 {
+        discard;
+    }
+    color.a = coverage;
+#endif
+#endif
+#ifdef DEBUG_NORMALS
+    color = vec4<f32>(normalize(in.normal) * 0.5 + 0.5, 1.0);
+#endif
+#ifdef DEBUG_TANGENTS
+    color = vec4<f32>(normalize(in.tangent) * 0.5 + 0.5, 1.0);
+#endif
+#ifdef DEBUG_BITANGENTS
+    color = vec4<f32>(normalize(in.bitangent) * 0.5 + 0.5, 1.0);
+#endif
+#ifdef DEBUG_UVS
+    color = vec4<f32>(fract(in.tex_coords), 0.0, 1.0);
+#endif
+#ifdef DEBUG_MIP_LEVEL
+    // Same level selection the sampler does, tinted over the texture
+    let texel = in.tex_coords * vec2<f32>(textureDimensions(t_diffuse));
+    let footprint = max(dot(dpdx(texel), dpdx(texel)), dot(dpdy(texel), dpdy(texel)));
+    let level = max(0.5 * log2(footprint), 0.0);
+    let base = textureSample(t_diffuse, t_sampler, in.tex_coords).rgb;
+    color = vec4<f32>(mix(base, mip_color(level), 0.7), 1.0);
+#endif
+#ifdef DEBUG_DEPTH
+    let distance = length(in.view_pos);
+    color = vec4<f32>(vec3<f32>(exp(-distance * 0.05)), 1.0);
+#endif
+#ifdef DEBUG_OVERDRAW
+    // Summed with additive blending, brighter means more layers
+    color = vec4<f32>(0.08, 0.04, 0.01, 1.0);
+#endif
+    return color;
+}
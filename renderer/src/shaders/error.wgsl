@@ -0,0 +1,99 @@
+// Stand-in for a shader that failed to compile, draws everything magenta.
+// Self-contained so a broken include can't take it down too, and declares every
+// binding of the layouts it replaces so the bind groups still match.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+};
+
+struct LightUniform {
+    position: vec3<f32>,
+    color: vec3<f32>,
+}
+
+struct MaterialUniform {
+    diffuse: vec4<f32>,
+    specular: vec3<f32>,
+    shininess: f32,
+    alpha_cutoff: f32,
+    roughness: f32,
+    metallic: f32,
+};
+
+#ifdef LIGHT_LAYOUT
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+@group(1) @binding(0)
+var<uniform> light: LightUniform;
+#else
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0) @binding(1)
+var t_sampler: sampler;
+@group(0) @binding(2)
+var t_normal: texture_2d<f32>;
+@group(0) @binding(3)
+var<uniform> material: MaterialUniform;
+@group(0) @binding(4)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(5)
+var t_roughness: texture_2d<f32>;
+@group(0) @binding(6)
+var t_metallic: texture_2d<f32>;
+@group(0) @binding(7)
+var t_opacity: texture_2d<f32>;
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+@group(2) @binding(0)
+var<uniform> light: LightUniform;
+#endif
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+};
+
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> @builtin(position) vec4<f32> {
+#ifdef LIGHT_LAYOUT
+    // Matches the light cube in unlit.wgsl
+    return camera.view_proj * vec4<f32>(model.position * 0.25 + light.position, 1.0);
+#else
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    return camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+#endif
+}
+
+const MAGENTA: vec4<f32> = vec4<f32>(1.0, 0.0, 1.0, 1.0);
+
+#ifdef WEIGHTED_OIT
+struct OitOutput {
+    @location(0) accum: vec4<f32>,
+    @location(1) revealage: f32,
+};
+
+@fragment
+fn fs_main() -> OitOutput {
+    var out: OitOutput;
+    out.accum = MAGENTA;
+    out.revealage = 0.0;
+    return out;
+}
+#else
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    return MAGENTA;
+}
+#endif
@@ -1,12 +1,8 @@
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-    view: mat4x4<f32>,
-};
-
-struct LightUniform {
-    position: vec3<f32>,
-    color: vec3<f32>,
-}
+#include "common/camera.wgsl"
+#include "common/light.wgsl"
+#include "common/vertex.wgsl"
+#include "common/material.wgsl"
+#include "common/oit.wgsl"
 
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
@@ -14,13 +10,6 @@ var<uniform> camera: CameraUniform;
 @group(2) @binding(0)
 var<uniform> light: LightUniform;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) tangent: vec4<f32>,
-}
-
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
@@ -30,16 +19,6 @@ struct VertexOutput {
     @location(4) normal: vec3<f32>,
 }
 
-struct InstanceInput {
-    @location(5) model_matrix_0: vec4<f32>,
-    @location(6) model_matrix_1: vec4<f32>,
-    @location(7) model_matrix_2: vec4<f32>,
-    @location(8) model_matrix_3: vec4<f32>,
-    @location(9) normal_matrix_0: vec3<f32>,
-    @location(10) normal_matrix_1: vec3<f32>,
-    @location(11) normal_matrix_2: vec3<f32>,
-};
-
 @vertex
 fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
   var out: VertexOutput;
@@ -87,17 +66,50 @@ var t_sampler: sampler;
 @group(0)@binding(2)
 var t_normal: texture_2d<f32>;
 
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+@group(0) @binding(3)
+var<uniform> material: MaterialUniform;
+// Occlusion in red, roughness in green, metallic in blue, see `MaterialTextures`
+@group(0) @binding(4)
+var t_occlusion: texture_2d<f32>;
+@group(0) @binding(5)
+var t_roughness: texture_2d<f32>;
+@group(0) @binding(6)
+var t_metallic: texture_2d<f32>;
+// Opacity (map_d) in red, white when the material has none
+@group(0) @binding(7)
+var t_opacity: texture_2d<f32>;
+
+fn shade(in: VertexOutput, front_facing: bool) -> vec4<f32> {
     let tbn = mat3x3<f32>(in.tangent, in.bitangent, in.normal);
 
-    let obj_color: vec4<f32> = textureSample(t_diffuse, t_sampler, in.tex_coords);
+    var obj_color: vec4<f32> = textureSample(t_diffuse, t_sampler, in.tex_coords) * material.diffuse;
+    obj_color.a *= textureSample(t_opacity, t_sampler, in.tex_coords).r;
+#ifdef ALPHA_MASK
+    if obj_color.a < material.alpha_cutoff {
+        discard;
+    }
+#endif
+#ifdef ALPHA_TO_COVERAGE
+    obj_color.a = coverage_alpha(obj_color.a, material.alpha_cutoff);
+#endif
     let obj_norm: vec4<f32> = textureSample(t_normal, t_sampler, in.tex_coords);
     let tangent_normal = normalize(obj_norm.xyz * 2.0 - 1.0);
-    let view_space_normal = normalize(tbn * tangent_normal);
+    var view_space_normal = normalize(tbn * tangent_normal);
+#ifdef TWO_SIDED
+    if !front_facing {
+        view_space_normal = -view_space_normal;
+    }
+#endif
+
+    let occlusion = textureSample(t_occlusion, t_sampler, in.tex_coords).r;
+    let roughness = clamp(material.roughness * textureSample(t_roughness, t_sampler, in.tex_coords).g, 0.0, 1.0);
+    let metallic = clamp(material.metallic * textureSample(t_metallic, t_sampler, in.tex_coords).b, 0.0, 1.0);
+    // Rough surfaces get a wider, dimmer highlight
+    let glossiness = 1.0 - roughness;
+    let shininess = max(material.shininess * glossiness * glossiness, 1.0);
 
     let AMBIENT_STRENGTH = 0.15;
-    let ambient_color = light.color * AMBIENT_STRENGTH;
+    let ambient_color = light.color * AMBIENT_STRENGTH * occlusion;
     
     let light_pos_view = (camera.view * vec4<f32>(light.position, 1.0)).xyz;
     let light_dir = normalize(light_pos_view - in.view_pos);
@@ -105,12 +117,25 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let half_dir = normalize(view_dir + light_dir);
 
     let diffuse_strength = max(dot(view_space_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
+    // Metals have no diffuse term
+    let diffuse_color = light.color * diffuse_strength * (1.0 - metallic);
 
-    let specular_strength = pow(max(dot(view_space_normal, half_dir), 0.0), 32.0);
-    let specular_color = specular_strength * light.color;
+    // and reflect in their base color, the result is tinted by it below
+    let specular_strength = pow(max(dot(view_space_normal, half_dir), 0.0), shininess) * glossiness;
+    let specular_color = specular_strength * light.color * mix(material.specular, vec3<f32>(1.0), metallic);
     let result = (ambient_color + diffuse_color + specular_color) * obj_color.xyz;
     
     return vec4<f32>(result, obj_color.a);
 }
 
+#ifdef WEIGHTED_OIT
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> OitOutput {
+    return oit_output(shade(in, front_facing), -in.view_pos.z);
+}
+#else
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+    return shade(in, front_facing);
+}
+#endif
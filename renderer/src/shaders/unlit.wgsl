@@ -1,24 +1,13 @@
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-    view: mat4x4<f32>,
-};
+#include "common/camera.wgsl"
+#include "common/light.wgsl"
+#include "common/vertex.wgsl"
+
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct LightUniform {
-    position: vec3<f32>,
-    color: vec3<f32>,
-}
 @group(1) @binding(0)
 var<uniform> light: LightUniform;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) tangent: vec4<f32>,
-};
-
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
@@ -1,25 +1,60 @@
-use crate::model::{DrawModel, Model, ModelVertex, Vertex};
-use crate::camera::{Camera, CameraController, CameraUniform, Projection};
+use crate::model::{AlphaMode, DrawModel, Material, Model, ModelVertex, Vertex};
+use crate::camera::{Camera, CameraUniform, OrthographicProjection, Projection};
+use crate::controller::{CameraControl, ControllerKind};
+use crate::input::{InputEvent, InputMap};
+use crate::gamepad::GamepadSource;
+use crate::camera_path::{Bookmarks, CameraPath, CameraPose, PathPlayback};
+use crate::timestep::FixedTimestep;
+use crate::frame_pacing::{self, FrameLimiter};
 use crate::instance::{Instance, InstanceRaw};
 use crate::light::{LightUniform, DrawLight};
-use crate::texture::Texture;
+use crate::texture::{SamplerFilter, Texture, TextureSettings};
 use crate::uniform::UniformResource;
+use crate::assets::{AssetServer, Handle};
 use crate::resources;
-use crate::pipeline;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::watcher::FileWatcher;
+use crate::pipeline::{PipelineCache, RenderPipelineBuilder};
+use crate::render_graph::{Load, RenderGraph, ResourceId, TextureDesc};
+use crate::oit::{self, OitComposite, TransparencyMode};
+use crate::debug_view::{self, DebugView, DebugViews};
+use crate::debug_draw::{self, DebugDraw};
+use crate::text_overlay::{self, TextOverlay};
+use crate::shader::{self, ShaderCache, ShaderDefines, ShaderError};
+use crate::reflect::{self, ShaderReflection};
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::collections::btree_map::Entry;
 use cgmath::prelude::*;
 use wgpu::util::DeviceExt;
-use winit::{
-    event::*,
-    event_loop::ActiveEventLoop,
-    keyboard::{PhysicalKey, KeyCode},
-    window::Window,
-};
+use winit::window::{CursorGrabMode, Window};
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+// Depth precision is spread evenly over distance instead of bunching up at the near plane
+const REVERSE_Z: bool = true;
+const CAMERA_SPEED: f32 = 4.0;
+// Simulation steps per second, rendering runs at whatever rate the display allows
+const SIMULATION_HZ: f64 = 60.0;
+const LIGHT_DEGREES_PER_SECOND: f32 = 60.0;
+// Vsync wherever possible, `next_present_mode` cycles at runtime
+const PRESENT_MODE: wgpu::PresentMode = wgpu::PresentMode::AutoVsync;
+// Used by the frame limiter when the monitor doesn't report its refresh rate
+const DEFAULT_TARGET_FPS: f64 = 60.0;
+// Lowered to what the adapter supports
+const TEXTURE_FILTER: SamplerFilter = SamplerFilter::Anisotropic(16);
+const CAMERA_SENSITIVITY: f32 = 1.0;
+const BOOKMARKS_FILE: &str = "bookmarks.txt";
+const INPUT_FILE: &str = "input.txt";
+// Time between bookmarks when they are played back as a path
+const BOOKMARK_PATH_SECONDS: f32 = 3.0;
+// Magenta stand-in for pipelines whose shader doesn't compile
+const ERROR_SHADER: &str = "error.wgsl";
+// MSAA for the scene, lowered to 1 where the targets can't be multisampled.
+// Cutouts use alpha-to-coverage with it and fall back to `discard` without.
+const SAMPLE_COUNT: u32 = 4;
+
 // Used for managing render pipelines
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
 enum PipelineType {
@@ -28,38 +63,267 @@ enum PipelineType {
     Light,
 }
 
+impl PipelineType {
+    const ALL: [PipelineType; 3] = [PipelineType::Default, PipelineType::Experimental, PipelineType::Light];
+
+    fn shader_file(self) -> &'static str {
+        match self {
+            PipelineType::Default => "shader.wgsl",
+            PipelineType::Experimental => "bw_shader.wgsl",
+            PipelineType::Light => "unlit.wgsl",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PipelineType::Default => "Shader pipeline",
+            PipelineType::Experimental => "BW Shader pipeline",
+            PipelineType::Light => "Unlit shader pipeline",
+        }
+    }
+
+    // error.wgsl declares this type's bind groups
+    fn error_defines(self) -> ShaderDefines {
+        let mut defines = ShaderDefines::new();
+        if self == PipelineType::Light {
+            defines.insert("LIGHT_LAYOUT".to_string());
+        }
+        defines
+    }
+
+    // Light cubes are always opaque, model shaders get one pipeline per material variant
+    fn variants(self) -> Vec<PipelineVariant> {
+        match self {
+            PipelineType::Light => vec![PipelineVariant::OPAQUE],
+            _ => {
+                let modes = [
+                    (AlphaMode::Opaque, false),
+                    (AlphaMode::Mask, false),
+                    (AlphaMode::Blend, false),
+                    (AlphaMode::Blend, true),
+                ];
+                modes
+                    .into_iter()
+                    .flat_map(|(alpha_mode, weighted_oit)| {
+                        [false, true].map(|two_sided| PipelineVariant { alpha_mode, two_sided, weighted_oit })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+// Material state that needs a pipeline of its own
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Copy)]
+struct PipelineVariant {
+    alpha_mode: AlphaMode,
+    two_sided: bool,
+    // Blended materials drawn into the OIT accumulation targets
+    weighted_oit: bool,
+}
+
+impl PipelineVariant {
+    const OPAQUE: PipelineVariant = PipelineVariant {
+        alpha_mode: AlphaMode::Opaque,
+        two_sided: false,
+        weighted_oit: false,
+    };
+
+    fn of(material: &Material, transparency: TransparencyMode) -> Self {
+        Self {
+            alpha_mode: material.alpha_mode,
+            two_sided: material.two_sided,
+            weighted_oit: material.alpha_mode == AlphaMode::Blend
+                && transparency == TransparencyMode::WeightedBlended,
+        }
+    }
+}
+
+// Shader source is validated with naga before it reaches the device, and the
+// vertex buffers are checked against the `@location`s the shader reads.
+// `fallback` builds the same variant from error.wgsl instead.
+#[allow(clippy::too_many_arguments)]
+fn build_pipeline(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    layout: &wgpu::PipelineLayout,
+    shader_cache: &mut ShaderCache,
+    pipeline_cache: &mut PipelineCache,
+    ty: PipelineType,
+    variant: PipelineVariant,
+    sample_count: u32,
+    fallback: bool,
+) -> Result<wgpu::RenderPipeline, ShaderError> {
+    let alpha_to_coverage = variant.alpha_mode == AlphaMode::Mask && sample_count > 1;
+    let mut defines = ShaderDefines::new();
+    if alpha_to_coverage {
+        defines.insert("ALPHA_TO_COVERAGE".to_string());
+    } else if variant.alpha_mode == AlphaMode::Mask {
+        defines.insert("ALPHA_MASK".to_string());
+    }
+    if variant.two_sided {
+        defines.insert("TWO_SIDED".to_string());
+    }
+    if variant.weighted_oit {
+        defines.insert("WEIGHTED_OIT".to_string());
+    }
+    let file = if fallback {
+        defines.extend(ty.error_defines());
+        ERROR_SHADER
+    } else {
+        ty.shader_file()
+    };
+    let shader = shader_cache.get_or_compile(device, file, &defines)?;
+    let buffers = [ModelVertex::desc(), InstanceRaw::desc()];
+    shader
+        .reflection
+        .validate_vertex_buffers("vs_main", &buffers)
+        .map_err(|errors| ShaderError::new(file, errors.join("\n")))?;
+
+    let mut builder = RenderPipelineBuilder::new(ty.label(), layout, &shader.module)
+        .buffers(&buffers)
+        .color_format(config.format)
+        .sample_count(sample_count);
+    if variant.two_sided {
+        builder = builder.cull_mode(None);
+    }
+    match variant.alpha_mode {
+        AlphaMode::Opaque => {}
+        AlphaMode::Mask => builder = builder.alpha_to_coverage(alpha_to_coverage),
+        // Blended surfaces are depth tested against opaques but don't occlude each other
+        AlphaMode::Blend if variant.weighted_oit => {
+            builder = builder.color_targets(&oit::accumulate_targets()).depth_write(false)
+        }
+        AlphaMode::Blend => {
+            builder = builder
+                .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+                .depth_write(false)
+        }
+    }
+
+    Ok(pipeline_cache.get_or_build(device, &builder))
+}
+
+// Every variant of one pipeline type, or the first error. The error scope
+// catches layout/interface mismatches naga can't see on its own.
+#[allow(clippy::too_many_arguments)]
+async fn build_pipelines(
+    device: &wgpu::Device,
+    config: &wgpu::SurfaceConfiguration,
+    layout: &wgpu::PipelineLayout,
+    shader_cache: &mut ShaderCache,
+    pipeline_cache: &mut PipelineCache,
+    ty: PipelineType,
+    sample_count: u32,
+    fallback: bool,
+) -> Result<Vec<(PipelineVariant, wgpu::RenderPipeline)>, ShaderError> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = ty
+        .variants()
+        .into_iter()
+        .map(|variant| {
+            build_pipeline(device, config, layout, shader_cache, pipeline_cache, ty, variant, sample_count, fallback)
+                .map(|pipeline| (variant, pipeline))
+        })
+        .collect::<Result<Vec<_>, _>>();
+    match (result, device.pop_error_scope().await) {
+        (Ok(pipelines), None) => Ok(pipelines),
+        (Ok(_), Some(e)) => Err(ShaderError::new(ty.shader_file(), e.to_string())),
+        (Err(e), _) => Err(e),
+    }
+}
+
 pub struct State {
-    surface: wgpu::Surface<'static>,
+    // `None` when headless, frames are then only drawn by `render_to_image`
+    surface: Option<wgpu::Surface<'static>>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
+    // Supported by the surface, and the mode last asked for which may not be one of them
+    present_modes: Vec<wgpu::PresentMode>,
+    requested_present_mode: wgpu::PresentMode,
     is_surface_configured: bool,
     current_pipeline: PipelineType,
-    pipelines: HashMap<PipelineType, wgpu::RenderPipeline>,
-    pub window: Arc<Window>,
+    pipelines: HashMap<(PipelineType, PipelineVariant), wgpu::RenderPipeline>,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    light_pipeline_layout: wgpu::PipelineLayout,
+    shader_cache: ShaderCache,
+    pipeline_cache: PipelineCache,
+    #[cfg(not(target_arch = "wasm32"))]
+    shader_watcher: Option<FileWatcher>,
+    // Last compile error per shader file, the previous good pipeline stays bound
+    // (or the error pipeline if there never was one)
+    shader_errors: BTreeMap<&'static str, ShaderError>,
+    // Shows `shader_errors` on screen
+    text_overlay: TextOverlay,
+    window_title: String,
+    window: Option<Arc<Window>>,
     camera: Camera,
     projection: Projection,
+    orthographic: OrthographicProjection,
+    use_orthographic: bool,
     camera_uniform: CameraUniform,
     camera_resources: UniformResource<CameraUniform>,
-    camera_controller: CameraController,
+    camera_controller: Box<dyn CameraControl>,
+    controller_kind: ControllerKind,
     light_uniform: LightUniform,
     light_resources: UniformResource<LightUniform>,
-    mouse_pressed: bool,
     instances: Vec<Instance>,
     instance_buffer: wgpu::Buffer,
-    depth_texture: Texture,
-    obj_model: Model,
+    render_graph: RenderGraph<State>,
+    // Swapchain image, bound to the graph every frame
+    surface_target: ResourceId,
+    // Where the scene is drawn and cleared, the surface itself without MSAA
+    scene_target: ResourceId,
+    sample_count: u32,
+    transparency_mode: TransparencyMode,
+    oit_composite: OitComposite,
+    debug_view: DebugView,
+    debug_views: DebugViews,
+    debug_draw: DebugDraw,
+    // Grid, light and bounding boxes drawn with `debug_draw`
+    show_debug_shapes: bool,
+    bookmarks: Bookmarks,
+    // While set the camera follows the path and the controller is ignored
+    camera_playback: Option<PathPlayback>,
+    timestep: FixedTimestep,
+    frame_limiter: FrameLimiter,
+    // Camera and light as of the step before the latest, for interpolation
+    previous_pose: CameraPose,
+    light_position: cgmath::Vector3<f32>,
+    previous_light_position: cgmath::Vector3<f32>,
+    input: InputMap,
+    gamepad: Option<Box<dyn GamepadSource>>,
+    exit_requested: bool,
+    assets: AssetServer,
+    #[cfg(not(target_arch = "wasm32"))]
+    asset_watcher: Option<FileWatcher>,
+    obj_model: Handle<Model>,
 }
 
 const NUM_INSTANCES_PER_ROW: u32 = 10;
 
 impl State {
     pub fn window_size(&self) -> winit::dpi::PhysicalSize<u32> {
-        self.window.inner_size()
+        match &self.window {
+            Some(window) => window.inner_size(),
+            None => winit::dpi::PhysicalSize::new(self.config.width, self.config.height),
+        }
+    }
+
+    pub fn request_redraw(&self) {
+        if let Some(window) = &self.window {
+            window.request_redraw();
+        }
     }
 
     pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
         let size = window.inner_size();
+        // Limiting to the refresh rate is the closest to vsync without it
+        let target_fps = window
+            .current_monitor()
+            .and_then(|monitor| monitor.refresh_rate_millihertz())
+            .map_or(DEFAULT_TARGET_FPS, |millihertz| millihertz as f64 / 1000.0);
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             #[cfg(not(target_arch = "wasm32"))]
             backends: wgpu::Backends::PRIMARY,
@@ -77,19 +341,7 @@ impl State {
             })
             .await?;
 
-        let (device, queue) = 
-            adapter.request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: if cfg!(target_arch = "wasm32") {
-                    wgpu::Limits::downlevel_webgl2_defaults()
-                } else {
-                    wgpu::Limits::default()
-                },
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
@@ -104,48 +356,121 @@ impl State {
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: frame_pacing::choose_present_mode(PRESENT_MODE, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
-       
-        let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 2,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                        },
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
+        let present_modes = surface_caps.present_modes;
+        Self::with_device(adapter, device, queue, config, Some((window, surface)), present_modes, target_fps).await
+    }
+
+    // Draws into textures instead of a window, see `render_to_image`. Used for
+    // frame capture and for replaying recorded input without a display.
+    pub async fn headless(width: u32, height: u32) -> anyhow::Result<Self> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        Self::with_device(adapter, device, queue, config, None, vec![wgpu::PresentMode::Fifo], DEFAULT_TARGET_FPS).await
+    }
+
+    async fn request_device(adapter: &wgpu::Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+        Ok(adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                required_features: adapter.features()
+                    & (crate::texture::COMPRESSION_FEATURES | wgpu::Features::POLYGON_MODE_LINE),
+                required_limits: if cfg!(target_arch = "wasm32") {
+                    wgpu::Limits::downlevel_webgl2_defaults()
+                } else {
+                    wgpu::Limits::default()
+                },
+                memory_hints: Default::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await?)
+    }
+
+    async fn with_device(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        window: Option<(Arc<Window>, wgpu::Surface<'static>)>,
+        present_modes: Vec<wgpu::PresentMode>,
+        target_fps: f64,
+    ) -> anyhow::Result<Self> {
+        let (window, surface) = window.unzip();
+        let scale_factor = window.as_ref().map_or(1.0, |window| window.scale_factor());
+        let transparency_mode = TransparencyMode::Sorted;
+        let debug_view = DebugView::None;
+        let sample_count = Self::choose_sample_count(&adapter, &device, config.format);
+        let (render_graph, surface_target, scene_target) =
+            Self::create_render_graph(&config, sample_count, transparency_mode, debug_view);
+
+        // Layouts come from the WGSL itself, every shader that binds a group
+        // contributes its entries so one layout serves all of them. A shader that
+        // doesn't compile is stood in for by error.wgsl, which declares the same
+        // bindings, so the app still starts and shows the error.
+        let mut shader_cache = ShaderCache::default();
+        let mut shader_errors = BTreeMap::new();
+        let mut reflections: HashMap<PipelineType, Arc<ShaderReflection>> = HashMap::new();
+        for ty in PipelineType::ALL {
+            let shader = match shader_cache.get_or_compile(&device, ty.shader_file(), &ShaderDefines::new()) {
+                Ok(shader) => shader,
+                Err(e) => {
+                    log::error!("Shader compilation failed, drawing with the error shader\n{}", e.report);
+                    shader_errors.insert(ty.shader_file(), e);
+                    shader_cache.get_or_compile(&device, ERROR_SHADER, &ty.error_defines())?
+                }
+            };
+            reflections.insert(ty, shader.reflection);
+        }
+        // Shaders that disagree on a binding are replaced by the error shader too
+        let mut bind_group_layout = |label: &str, sources: &[(PipelineType, u32)]| -> Result<wgpu::BindGroupLayout, ShaderError> {
+            let merge = |reflections: &HashMap<PipelineType, Arc<ShaderReflection>>| {
+                let sources = sources.iter().map(|(ty, group)| (reflections[ty].as_ref(), *group)).collect::<Vec<_>>();
+                reflect::create_bind_group_layout(&device, label, &sources)
+            };
+            match merge(&reflections) {
+                Ok(layout) => Ok(layout),
+                Err(e) => {
+                    log::error!("Unable to create {}, drawing with the error shader: {}", label, e);
+                    for (ty, _) in sources {
+                        shader_errors.entry(ty.shader_file()).or_insert_with(|| ShaderError::new(ty.shader_file(), e.clone()));
+                        let shader = shader_cache.get_or_compile(&device, ERROR_SHADER, &ty.error_defines())?;
+                        reflections.insert(*ty, shader.reflection);
+                    }
+                    merge(&reflections).map_err(|e| ShaderError::new(ERROR_SHADER, e))
+                }
             }
-        );
-       
+        };
+        let texture_bind_group_layout = bind_group_layout(
+            "texture_bind_group_layout",
+            &[(PipelineType::Default, 0), (PipelineType::Experimental, 0)],
+        )?;
+        let camera_bind_group_layout = bind_group_layout(
+            "camera_bind_group_layout",
+            &[(PipelineType::Default, 1), (PipelineType::Experimental, 1), (PipelineType::Light, 0)],
+        )?;
+        let light_bind_group_layout = bind_group_layout(
+            "light_bind_group_layout",
+            &[(PipelineType::Default, 2), (PipelineType::Light, 1)],
+        )?;
+
         let camera = Camera::new(
             (0.0, 5.0, 10.0),
             cgmath::Deg(-90.0),
@@ -157,32 +482,52 @@ impl State {
             cgmath::Deg(45.0), 
             0.1, 
             100.0
-        );
-        let camera_controller = CameraController::new(4.0, 1.0); // [speed, sensitivity]
+        )
+        .reverse_z(REVERSE_Z)
+        .infinite_far(true);
+        let orthographic = OrthographicProjection::new(config.width, config.height, 10.0, 0.1, 100.0)
+            .reverse_z(REVERSE_Z);
+        let previous_pose = CameraPose::capture(&camera, &projection);
+        let controller_kind = ControllerKind::Fly;
+        let camera_controller = controller_kind.create(CAMERA_SPEED, CAMERA_SENSITIVITY);
 
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(&camera, &projection);
-        let camera_resources = UniformResource::new(
+        let camera_resources = UniformResource::with_layout(
             &device,
             "camera",
             &[camera_uniform],
-            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            camera_bind_group_layout,
             0,
         );
 
         let light_uniform = LightUniform::default();
-        let light_resources = UniformResource::new(
+        let light_position = cgmath::Vector3::from(light_uniform.position);
+        let light_resources = UniformResource::with_layout(
             &device,
             "light",
             &[light_uniform],
-            wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            light_bind_group_layout,
             0,
         );
 
-        let obj_model = resources::load_model("cube.obj", &device, &queue, &texture_bind_group_layout)
+        let mut assets = AssetServer::new().texture_settings(TextureSettings {
+            filter: TEXTURE_FILTER.supported_by(&adapter),
+            mipmaps: true,
+        });
+        let obj_model = assets
+            .load_model("cube.obj", &device, &queue, &texture_bind_group_layout)
             .await
             .unwrap();
 
+        // Headless runs render what they were started with
+        #[cfg(not(target_arch = "wasm32"))]
+        let asset_watcher = window.as_ref().and_then(|_| {
+            FileWatcher::new(&resources::res_dir())
+                .inspect_err(|e| log::warn!("Asset hot reloading disabled: {}", e))
+                .ok()
+        });
+
         const SPACE_BETWEEN: f32 = 3.0;
         let instances = (0..NUM_INSTANCES_PER_ROW).flat_map(|z| {
             (0..NUM_INSTANCES_PER_ROW).map(move |x| {
@@ -222,241 +567,1050 @@ impl State {
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = {
-            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("./shaders/shader.wgsl").into()),
-            });
-            pipeline::create_render_pipeline(
-                &device, 
-                &config, 
-                "Shader pipeline",
-                &render_pipeline_layout,
-                &shader,
-                &[ModelVertex::desc(), InstanceRaw::desc()],
-            )
-        };
+        let light_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Light pipeline layout"),
+            bind_group_layouts: &[
+                &camera_resources.layout, 
+                &light_resources.layout
+            ],
+            push_constant_ranges: &[],
+        });
 
-        let bw_pipeline = {
-            let bw_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Experimental shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("./shaders/bw_shader.wgsl").into()),
-            });
-            pipeline::create_render_pipeline(
-                &device, 
-                &config, 
-                "BW Shader pipeline",
-                &render_pipeline_layout,
-                &bw_shader,
-                &[ModelVertex::desc(), InstanceRaw::desc()],
-            )
-        };
+        let mut input = Self::load_input_map(&config);
+        input.set_scale_factor(scale_factor);
+        let mut pipeline_cache = PipelineCache::default();
+        pipeline_cache.set_reverse_z(REVERSE_Z);
+        let mut pipelines = HashMap::new();
+        for ty in PipelineType::ALL {
+            let layout = match ty {
+                PipelineType::Light => &light_pipeline_layout,
+                _ => &render_pipeline_layout,
+            };
+            if let Entry::Vacant(entry) = shader_errors.entry(ty.shader_file()) {
+                match build_pipelines(&device, &config, layout, &mut shader_cache, &mut pipeline_cache, ty, sample_count, false).await {
+                    Ok(built) => {
+                        pipelines.extend(built.into_iter().map(|(variant, pipeline)| ((ty, variant), pipeline)));
+                        continue;
+                    }
+                    Err(e) => {
+                        log::error!("Pipeline creation failed, drawing with the error shader\n{}", e.report);
+                        entry.insert(e);
+                    }
+                }
+            }
+            let built =
+                build_pipelines(&device, &config, layout, &mut shader_cache, &mut pipeline_cache, ty, sample_count, true).await?;
+            pipelines.extend(built.into_iter().map(|(variant, pipeline)| ((ty, variant), pipeline)));
+        }
 
-        let light_pipeline = {
-            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Light pipeline layout"),
-                bind_group_layouts: &[
-                    &camera_resources.layout, 
-                    &light_resources.layout
-                ],
-                push_constant_ranges: &[],
-            });
-            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-                label: Some("Unlit shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("./shaders/unlit.wgsl").into()),
-            });
-            pipeline::create_render_pipeline(
-                &device,
-                &config,
-                "Unlit shader pipeline",
-                &layout,
-                &shader,
-                &[ModelVertex::desc(), InstanceRaw::desc()],
-            )
-        };
+        let oit_composite = OitComposite::new(&device, &mut shader_cache, &mut pipeline_cache, config.format, sample_count)?;
+        let debug_views = DebugViews::new(
+            &device,
+            config.format,
+            sample_count,
+            &render_pipeline_layout,
+            &mut shader_cache,
+            &mut pipeline_cache,
+        )?;
+        let debug_draw = DebugDraw::new(
+            &device,
+            config.format,
+            sample_count,
+            &camera_resources.layout,
+            &mut shader_cache,
+            &mut pipeline_cache,
+        )?;
+        let text_overlay = TextOverlay::new(&device, config.format, &mut shader_cache, &mut pipeline_cache)?;
 
-        let mut pipelines: HashMap<PipelineType, wgpu::RenderPipeline> = HashMap::new();
-        pipelines.insert(PipelineType::Default, render_pipeline);
-        pipelines.insert(PipelineType::Experimental, bw_pipeline);
-        pipelines.insert(PipelineType::Light, light_pipeline);
+        #[cfg(not(target_arch = "wasm32"))]
+        let shader_watcher = shader::shader_dir().filter(|_| window.is_some()).and_then(|dir| {
+            FileWatcher::new(&dir)
+                .inspect_err(|e| log::warn!("Shader hot reloading disabled: {}", e))
+                .ok()
+        });
 
-        Ok(Self{
+        // Replays and captures don't pick up a live controller
+        let gamepad = window.as_ref().and_then(|_| Self::create_gamepad());
+
+        let mut state = Self{
             surface,
             device,
             queue,
             config,
+            present_modes,
+            requested_present_mode: PRESENT_MODE,
             is_surface_configured:false,
             current_pipeline: PipelineType::Default,
             pipelines,
+            render_pipeline_layout,
+            light_pipeline_layout,
+            shader_cache,
+            pipeline_cache,
+            #[cfg(not(target_arch = "wasm32"))]
+            shader_watcher,
+            shader_errors,
+            text_overlay,
+            window_title: window.as_ref().map(|window| window.title()).unwrap_or_default(),
             window,
             camera,
             projection,
+            orthographic,
+            use_orthographic: false,
             camera_uniform,
             camera_resources,
             camera_controller,
+            controller_kind,
             light_uniform,
             light_resources,
-            mouse_pressed: false,
             instances,
             instance_buffer,
-            depth_texture,
+            render_graph,
+            surface_target,
+            scene_target,
+            sample_count,
+            transparency_mode,
+            oit_composite,
+            debug_view,
+            debug_views,
+            debug_draw,
+            show_debug_shapes: false,
+            bookmarks: Self::load_bookmarks(),
+            camera_playback: None,
+            timestep: FixedTimestep::new(SIMULATION_HZ),
+            frame_limiter: FrameLimiter::new(target_fps),
+            previous_pose,
+            light_position,
+            previous_light_position: light_position,
+            input,
+            gamepad,
+            exit_requested: false,
+            assets,
+            #[cfg(not(target_arch = "wasm32"))]
+            asset_watcher,
             obj_model,
-        })
+        };
+        state.show_shader_errors();
+        Ok(state)
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.config.width = width;
             self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
-            self.is_surface_configured = true;
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+                self.is_surface_configured = true;
+            }
         }
 
         self.projection.resize(width, height);
-        self.depth_texture = Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+        self.orthographic.resize(width, height);
+        self.input.resize(width, height);
+        self.render_graph.resize(width, height);
+        // Rewrapped for the new size
+        self.show_shader_errors();
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        self.window.request_redraw();
+    // 4x if every scene target supports it (and resolving, for color), else no MSAA
+    fn choose_sample_count(adapter: &wgpu::Adapter, device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> u32 {
+        let supported = |format: wgpu::TextureFormat, resolve: bool| {
+            // Which of the two applies depends on the backend, so ask both
+            [adapter.get_texture_format_features(format).flags, format.guaranteed_format_features(device.features()).flags]
+                .iter()
+                .all(|flags| {
+                    flags.sample_count_supported(SAMPLE_COUNT)
+                        && (!resolve || flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE))
+                })
+        };
+        let colors = [surface_format, oit::ACCUM_FORMAT, oit::REVEALAGE_FORMAT];
+        if colors.into_iter().all(|format| supported(format, true)) && supported(Texture::DEPTH_FORMAT, false) {
+            log::info!("{}x MSAA", SAMPLE_COUNT);
+            SAMPLE_COUNT
+        } else {
+            log::info!("MSAA is not supported, cutouts discard instead");
+            1
+        }
+    }
 
-        if !self.is_surface_configured {
-            return Ok(());
+    // Passes are declared once, new ones (shadows, post processing) only need
+    // to state which targets they read and write. Returns the graph with the
+    // surface and scene targets.
+    fn create_render_graph(
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        transparency: TransparencyMode,
+        debug_view: DebugView,
+    ) -> (RenderGraph<State>, ResourceId, ResourceId) {
+        let mut graph = RenderGraph::new(config.width, config.height);
+        let surface = graph.import_texture("surface");
+        // Multisampled scene targets are resolved into the surface after the last scene pass
+        let msaa = sample_count > 1;
+        let scene = if msaa {
+            graph.create_texture("scene_color", TextureDesc::new(config.format).sample_count(sample_count))
+        } else {
+            surface
+        };
+        let depth = graph.create_texture("depth", TextureDesc::new(Texture::DEPTH_FORMAT).sample_count(sample_count));
+        if REVERSE_Z {
+            graph.set_clear_depth(depth, 0.0);
         }
 
-        let output = self.surface.get_current_texture()?;
+        graph
+            .add_pass("forward")
+            .color(scene, Load::Clear)
+            .depth(depth, Load::Clear)
+            .render(|render_pass, _, state: &State| state.draw_forward(render_pass));
+        // Debug views that replace shading draw blended meshes in the forward pass
+        match transparency {
+            _ if !debug_view.is_overlay() => {}
+            TransparencyMode::Sorted => {
+                graph
+                    .add_pass("transparent")
+                    .color(scene, Load::Load)
+                    .depth_read_only(depth)
+                    .render(|render_pass, _, state: &State| state.draw_transparent(render_pass));
+            }
+            TransparencyMode::WeightedBlended => {
+                let accum = graph.create_texture("oit_accum", TextureDesc::new(oit::ACCUM_FORMAT));
+                let revealage = graph.create_texture("oit_revealage", TextureDesc::new(oit::REVEALAGE_FORMAT));
+                graph.set_clear_color(revealage, wgpu::Color::WHITE);
+
+                // Accumulated at the scene's sample count to match its depth, the
+                // composite samples the resolved sums
+                let pass = if msaa {
+                    let accum_samples = graph.create_texture(
+                        "oit_accum_samples",
+                        TextureDesc::new(oit::ACCUM_FORMAT).sample_count(sample_count),
+                    );
+                    let revealage_samples = graph.create_texture(
+                        "oit_revealage_samples",
+                        TextureDesc::new(oit::REVEALAGE_FORMAT).sample_count(sample_count),
+                    );
+                    graph.set_clear_color(revealage_samples, wgpu::Color::WHITE);
+                    graph
+                        .add_pass("oit_accumulate")
+                        .color(accum_samples, Load::Clear)
+                        .resolve(accum)
+                        .color(revealage_samples, Load::Clear)
+                        .resolve(revealage)
+                } else {
+                    graph.add_pass("oit_accumulate").color(accum, Load::Clear).color(revealage, Load::Clear)
+                };
+                pass.depth_read_only(depth)
+                    .render(|render_pass, _, state: &State| state.draw_transparent(render_pass));
+                graph
+                    .add_pass("oit_composite")
+                    .read(accum)
+                    .read(revealage)
+                    .color(scene, Load::Load)
+                    .render(move |render_pass, resources, state: &State| {
+                        state.oit_composite.draw(
+                            &state.device,
+                            render_pass,
+                            resources.view(accum),
+                            resources.view(revealage),
+                        );
+                    });
+            }
+        }
+
+        if debug_view == DebugView::Wireframe {
+            graph
+                .add_pass("wireframe")
+                .color(scene, Load::Load)
+                .depth_read_only(depth)
+                .render(|render_pass, _, state: &State| state.draw_wireframe(render_pass));
+        }
+        let mut pass = graph.add_pass("debug_draw").color(scene, Load::Load);
+        if msaa {
+            pass = pass.resolve(surface);
+        }
+        pass.depth_read_only(depth).render(|render_pass, _, state: &State| {
+            state.debug_draw.draw(render_pass, &state.camera_resources.bind_group)
+        });
+        graph
+            .add_pass("text_overlay")
+            .color(surface, Load::Load)
+            .render(|render_pass, _, state: &State| state.text_overlay.draw(render_pass));
+
+        (graph, surface, scene)
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        let Some(surface) = self.surface.as_ref().filter(|_| self.is_surface_configured) else {
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
         let view = output
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
-        let mut encoder = self
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                label: Some("Render encoder"),
-            });
-
-        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Render pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.017,
-                        g: 0.017,
-                        b: 0.022,
-                        a: 1.000,
-                    }),
-                    store: wgpu::StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: &self.depth_texture.view,
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: wgpu::StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),     
-            occlusion_query_set: None,
-            timestamp_writes: None,
+        self.draw_frame(view);
+        output.present();
+
+        Ok(())
+    }
+
+    // Draws the current state into an offscreen texture and reads it back,
+    // works with or without a window
+    pub fn render_to_image(&mut self) -> anyhow::Result<image::RgbaImage> {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture target"),
+            size: wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
         });
+        self.draw_frame(texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        Texture::read_rgba8(&self.device, &self.queue, &texture)
+    }
+
+    fn draw_frame(&mut self, view: wgpu::TextureView) {
+        // Tint the background while a shader fails to compile
+        let clear_color = if self.shader_errors.is_empty() {
+            wgpu::Color { r: 0.017, g: 0.017, b: 0.022, a: 1.000 }
+        } else {
+            wgpu::Color { r: 0.25, g: 0.01, b: 0.01, a: 1.000 }
+        };
+
+        self.debug_draw.prepare(&self.device, &self.queue);
+        let mut graph = std::mem::take(&mut self.render_graph);
+        graph.set_texture(self.surface_target, view);
+        graph.set_clear_color(self.scene_target, clear_color);
+        let result = graph.execute(&self.device, &self.queue, self);
+        self.render_graph = graph;
+        if let Err(e) = result {
+            log::error!("{}", e);
+        }
+    }
+
+    fn pipeline(&self, ty: PipelineType, material: &Material) -> &wgpu::RenderPipeline {
+        &self.pipelines[&(ty, PipelineVariant::of(material, self.transparency_mode))]
+    }
+
+    pub fn set_transparency_mode(&mut self, mode: TransparencyMode) {
+        if mode != self.transparency_mode {
+            log::info!("Transparency mode: {:?}", mode);
+            self.transparency_mode = mode;
+            (self.render_graph, self.surface_target, self.scene_target) =
+                Self::create_render_graph(&self.config, self.sample_count, mode, self.debug_view);
+        }
+    }
+
+    // Lines and wire shapes for the current frame, see `DebugDraw`
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
 
-        let pipeline = self.pipelines.get(&self.current_pipeline).unwrap();
+    pub fn debug_view(&self) -> DebugView {
+        self.debug_view
+    }
+
+    pub fn set_debug_view(&mut self, view: DebugView) {
+        if view != self.debug_view {
+            log::info!("Debug view: {:?}", view);
+            self.debug_view = view;
+            (self.render_graph, self.surface_target, self.scene_target) =
+                Self::create_render_graph(&self.config, self.sample_count, self.transparency_mode, view);
+        }
+    }
+
+    // Opaque and cutout geometry
+    fn draw_forward(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let obj_model = self.assets.models.get(&self.obj_model).unwrap();
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
 
-        render_pass.set_pipeline(self.pipelines.get(&PipelineType::Light).unwrap());
+        render_pass.set_pipeline(&self.pipelines[&(PipelineType::Light, PipelineVariant::OPAQUE)]);
         render_pass.set_bind_group(0, &self.camera_resources.bind_group, &[]);
         render_pass.set_bind_group(1, &self.light_resources.bind_group, &[]);
         render_pass.draw_light_model(
-            &self.obj_model,
+            obj_model,
             &self.camera_resources.bind_group,
             &self.light_resources.bind_group,
         );
 
+        let debug_pipeline = if self.debug_view.is_overlay() {
+            None
+        } else {
+            self.debug_views.pipeline(self.debug_view)
+        };
+        for mesh in &obj_model.meshes {
+            let material = &obj_model.materials[mesh.material];
+            match debug_pipeline {
+                Some(pipeline) => render_pass.set_pipeline(pipeline),
+                None if material.alpha_mode == AlphaMode::Blend => continue,
+                None => render_pass.set_pipeline(self.pipeline(self.current_pipeline, material)),
+            }
+            render_pass.draw_mesh_instanced(
+                mesh,
+                material,
+                0..self.instances.len() as u32,
+                &self.camera_resources.bind_group,
+                &self.light_resources.bind_group,
+            );
+        }
+    }
+
+    fn draw_wireframe(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let Some(pipeline) = self.debug_views.pipeline(DebugView::Wireframe) else {
+            return;
+        };
+        let obj_model = self.assets.models.get(&self.obj_model).unwrap();
+        let instances = 0..self.instances.len() as u32;
+
         render_pass.set_pipeline(pipeline);
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
         render_pass.set_bind_group(1, &self.camera_resources.bind_group, &[]);
-        render_pass.set_bind_group(1, &self.light_resources.bind_group, &[]);
-        
-        render_pass.draw_model_instanced(
-            &self.obj_model, 
-            0..self.instances.len() as u32, 
-            &self.camera_resources.bind_group, 
-            &self.light_resources.bind_group,
-        );
-        drop(render_pass);
+        render_pass.set_bind_group(2, &self.light_resources.bind_group, &[]);
+        for mesh in &obj_model.meshes {
+            render_pass.set_bind_group(0, &obj_model.materials[mesh.material].bind_group, &[]);
+            match (&mesh.wireframe_buffer, self.debug_views.barycentric_wireframe()) {
+                (Some(buffer), true) => {
+                    render_pass.set_vertex_buffer(0, buffer.slice(..));
+                    render_pass.draw(0..mesh.num_elements, instances.clone());
+                }
+                _ => {
+                    render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+                    render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+                    render_pass.draw_indexed(0..mesh.num_elements, 0, instances.clone());
+                }
+            }
+        }
+    }
 
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+    // Blended geometry. Sorted mode issues one draw per mesh instance back to front
+    // by view depth, OIT accumulates them in any order.
+    fn draw_transparent(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let obj_model = self.assets.models.get(&self.obj_model).unwrap();
+        if self.transparency_mode == TransparencyMode::WeightedBlended {
+            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+            for mesh in &obj_model.meshes {
+                let material = &obj_model.materials[mesh.material];
+                if material.alpha_mode != AlphaMode::Blend {
+                    continue;
+                }
+                render_pass.set_pipeline(self.pipeline(self.current_pipeline, material));
+                render_pass.draw_mesh_instanced(
+                    mesh,
+                    material,
+                    0..self.instances.len() as u32,
+                    &self.camera_resources.bind_group,
+                    &self.light_resources.bind_group,
+                );
+            }
+            return;
+        }
 
-        Ok(())
+        // The interpolated view `prepare_render` uploaded, so the order matches what is drawn
+        let view = cgmath::Matrix4::from(self.camera_uniform.view);
+
+        let mut draws = Vec::new();
+        for mesh in &obj_model.meshes {
+            let material = &obj_model.materials[mesh.material];
+            if material.alpha_mode != AlphaMode::Blend {
+                continue;
+            }
+            let center = cgmath::Vector3::from(mesh.center());
+            for (index, instance) in self.instances.iter().enumerate() {
+                let position = instance.position + instance.rotation * center;
+                // View space looks down -z, so the farthest draw has the smallest z
+                let depth = (view * position.extend(1.0)).z;
+                draws.push((depth, mesh, material, index as u32));
+            }
+        }
+        if draws.is_empty() {
+            return;
+        }
+        draws.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for (_, mesh, material, instance) in draws {
+            render_pass.set_pipeline(self.pipeline(self.current_pipeline, material));
+            render_pass.draw_mesh_instanced(
+                mesh,
+                material,
+                instance..instance + 1,
+                &self.camera_resources.bind_group,
+                &self.light_resources.bind_group,
+            );
+        }
     }
 
-    pub fn update(&mut self, dt: instant::Duration) {
-        self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera_uniform.update_view_proj(&self.camera, &self.projection);
+    // One simulation step of `timestep.step()`, independent of the frame rate
+    fn fixed_update(&mut self, dt: instant::Duration) {
+        self.previous_pose = self.camera_pose();
+        self.previous_light_position = self.light_position;
+        match self.camera_playback.as_mut() {
+            Some(playback) => {
+                if let Some(pose) = playback.advance(dt) {
+                    pose.apply(&mut self.camera, &mut self.projection);
+                }
+                if playback.is_finished() {
+                    self.stop_camera_path();
+                }
+            }
+            None => self.camera_controller.update_camera(&mut self.camera, &self.projection, &self.input, dt),
+        }
+        self.input.end_step();
+
+        let angle = cgmath::Deg(LIGHT_DEGREES_PER_SECOND * dt.as_secs_f32());
+        self.light_position = cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_y(), angle) * self.light_position;
+    }
+
+    // Uploads the state `alpha` of the way from the previous to the latest step
+    fn prepare_render(&mut self, alpha: f32) {
+        let pose = self.previous_pose.lerp(&self.camera_pose(), alpha);
+        let camera = Camera::new(pose.position, pose.yaw, pose.pitch);
+        if self.use_orthographic {
+            self.fit_orthographic(&camera, pose.fovy);
+            self.camera_uniform.update_view_proj(&camera, &self.orthographic);
+        } else {
+            // `projection` keeps the fovy of the last step, a path may be zooming
+            let mut projection = self.projection.clone();
+            projection.set_fovy(pose.fovy);
+            self.camera_uniform.update_view_proj(&camera, &projection);
+        }
         self.queue.write_buffer(&self.camera_resources.buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
 
-        let old_position: cgmath::Vector3<_> = self.light_uniform.position.into();
-        self.light_uniform.position =
-            (cgmath::Quaternion::from_axis_angle((0.0, 1.0, 0.0).into(), cgmath::Deg(1.0))
-                * old_position)
-                .into();
-        self.queue.write_buffer(&self.light_resources.buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));    
-    }
-
-    pub fn input(&mut self, event: &WindowEvent) -> bool {
-        match event {
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(_),
-                        state: _,
-                        ..
-                    },
-                ..
-            } => self.camera_controller.process_events(event),
-            WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.handle_mouse_scroll(delta);
-                true
+        let light_position = self.previous_light_position.lerp(self.light_position, alpha);
+        // Keep the radius, a straight line between two points on the circle cuts inside it
+        self.light_uniform.position = light_position.normalize_to(self.light_position.magnitude()).into();
+        self.queue.write_buffer(&self.light_resources.buffer, 0, bytemuck::cast_slice(&[self.light_uniform]));
+    }
+
+    // Skips interpolation after a jump so the camera doesn't slide to its new place
+    fn snap_camera(&mut self) {
+        self.previous_pose = self.camera_pose();
+    }
+
+    pub fn frame_limiter(&mut self) -> &mut FrameLimiter {
+        &mut self.frame_limiter
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    // Falls back to the closest supported mode, see `choose_present_mode`
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) {
+        let chosen = frame_pacing::choose_present_mode(mode, &self.present_modes);
+        if chosen != mode {
+            log::info!("Present mode {:?} is not supported, using {:?}", mode, chosen);
+        } else {
+            log::info!("Present mode: {:?}", chosen);
+        }
+        self.config.present_mode = chosen;
+        if self.is_surface_configured && let Some(surface) = &self.surface {
+            surface.configure(&self.device, &self.config);
+        }
+    }
+
+    pub fn timestep(&mut self) -> &mut FixedTimestep {
+        &mut self.timestep
+    }
+
+    pub fn update(&mut self, dt: instant::Duration) {
+        self.debug_draw.begin_frame(dt);
+        self.handle_actions();
+        self.input.end_frame();
+        let steps = self.timestep.advance(dt);
+        for _ in 0..steps {
+            self.fixed_update(self.timestep.step());
+        }
+        // Motion while paused would all land on the next step
+        if self.timestep.is_paused() {
+            self.input.end_step();
+        }
+        self.prepare_render(self.timestep.alpha());
+
+        if self.show_debug_shapes {
+            self.draw_debug_shapes();
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(changed) = self.shader_watcher.as_ref().map(FileWatcher::changed_files)
+            && !changed.is_empty()
+        {
+            self.reload_shaders(&changed);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(watcher) = &self.asset_watcher {
+            let changed = watcher.changed_files();
+            if !changed.is_empty() {
+                pollster::block_on(self.assets.reload_changed(&changed, &self.device, &self.queue));
             }
-            WindowEvent::MouseInput {
-                button: MouseButton::Left,
-                state,
-                ..
-            } => {
-                self.mouse_pressed = *state == ElementState::Pressed;
-                true
+        }
+        self.assets.free_unused();
+    }
+
+    fn draw_debug_shapes(&mut self) {
+        let light_position = cgmath::Point3::from(self.light_uniform.position);
+        self.debug_draw.grid((0.0, 0.0, 0.0), 40.0, 40, debug_draw::GRAY);
+        self.debug_draw.axes(cgmath::Matrix4::identity(), 2.0).depth_test(false);
+        self.debug_draw.sphere(light_position, 0.5, debug_draw::YELLOW);
+        self.debug_draw.arrow(light_position, (0.0, 0.0, 0.0), debug_draw::YELLOW);
+
+        for (min, max) in self.instance_bounds() {
+            self.debug_draw.aabb(min, max, debug_draw::GREEN);
+        }
+    }
+
+    // World space bounds of every mesh of every instance
+    fn instance_bounds(&self) -> Vec<(cgmath::Point3<f32>, cgmath::Point3<f32>)> {
+        let Some(obj_model) = self.assets.models.get(&self.obj_model) else {
+            return Vec::new();
+        };
+        let mut bounds = Vec::new();
+        for mesh in &obj_model.meshes {
+            for instance in &self.instances {
+                let transform = cgmath::Matrix4::from_translation(instance.position) * cgmath::Matrix4::from(instance.rotation);
+                let mut min = cgmath::Point3::new(f32::MAX, f32::MAX, f32::MAX);
+                let mut max = cgmath::Point3::new(f32::MIN, f32::MIN, f32::MIN);
+                for i in 0..8 {
+                    let corner = cgmath::Point3::new(
+                        if i & 1 == 0 { mesh.bounds_min[0] } else { mesh.bounds_max[0] },
+                        if i & 2 == 0 { mesh.bounds_min[1] } else { mesh.bounds_max[1] },
+                        if i & 4 == 0 { mesh.bounds_min[2] } else { mesh.bounds_max[2] },
+                    );
+                    let corner = transform.transform_point(corner);
+                    min = cgmath::Point3::new(min.x.min(corner.x), min.y.min(corner.y), min.z.min(corner.z));
+                    max = cgmath::Point3::new(max.x.max(corner.x), max.y.max(corner.y), max.z.max(corner.z));
+                }
+                bounds.push((min, max));
             }
-            _ => false,
         }
+        bounds
     }
 
-    pub fn device_input(&mut self, event: &DeviceEvent) -> bool {
-        match event {
-            DeviceEvent::MouseMotion { delta } => { 
-                if self.mouse_pressed {
-                    self.camera_controller.handle_mouse(delta.0, delta.1);
-                    true
-                } else {
-                    false
+    pub fn controller_kind(&self) -> ControllerKind {
+        self.controller_kind
+    }
+
+    // Swaps the camera controller, the new one continues from the current camera
+    pub fn set_controller(&mut self, kind: ControllerKind) {
+        if kind == self.controller_kind {
+            return;
+        }
+        let mut controller = kind.create(CAMERA_SPEED, CAMERA_SENSITIVITY);
+        controller.sync(&self.camera);
+        self.camera_controller = controller;
+        self.controller_kind = kind;
+        log::info!("Camera controller: {:?}", kind);
+    }
+
+    pub fn set_orthographic(&mut self, enabled: bool) {
+        self.use_orthographic = enabled;
+        log::info!("Projection: {}", if enabled { "orthographic" } else { "perspective" });
+    }
+
+    // Shows the scene center at the size the perspective view would, so the
+    // controllers zoom by moving the camera in either projection
+    fn fit_orthographic(&mut self, camera: &Camera, fovy: cgmath::Rad<f32>) {
+        let center = self.scene_sphere().map_or(cgmath::Point3::new(0.0, 0.0, 0.0), |(center, _)| center);
+        let distance = (center - camera.position).dot(camera.forward()).max(0.1);
+        self.orthographic.set_view_height(2.0 * distance * (fovy.0 * 0.5).tan());
+    }
+
+    fn scene_sphere(&self) -> Option<(cgmath::Point3<f32>, f32)> {
+        let bounds = self.instance_bounds();
+        let &(first_min, first_max) = bounds.first()?;
+        let (min, max) = bounds.iter().fold((first_min, first_max), |(min, max), (a, b)| {
+            (
+                cgmath::Point3::new(min.x.min(a.x), min.y.min(a.y), min.z.min(a.z)),
+                cgmath::Point3::new(max.x.max(b.x), max.y.max(b.y), max.z.max(b.z)),
+            )
+        });
+        Some((cgmath::Point3::midpoint(min, max), (max - min).magnitude() * 0.5))
+    }
+
+    // Fits the bounding sphere of everything in the scene into view
+    pub fn frame_scene(&mut self) {
+        let Some((center, radius)) = self.scene_sphere() else {
+            return;
+        };
+        self.camera_controller.frame(&mut self.camera, &self.projection, center, radius);
+        self.snap_camera();
+    }
+
+    fn load_bookmarks() -> Bookmarks {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(text) = std::fs::read_to_string(resources::get_user_path(BOOKMARKS_FILE)) {
+            match Bookmarks::parse(&text) {
+                Ok(bookmarks) => return bookmarks,
+                Err(e) => log::warn!("Ignoring {}: {}", BOOKMARKS_FILE, e),
+            }
+        }
+        Bookmarks::default()
+    }
+
+    pub fn store_bookmark(&mut self, slot: u8) {
+        self.bookmarks.set(slot, CameraPose::capture(&self.camera, &self.projection));
+        log::info!("Stored camera bookmark {}", slot);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Err(e) = resources::save_user_file(BOOKMARKS_FILE, &self.bookmarks.serialize()) {
+            log::warn!("Unable to save {}: {}", BOOKMARKS_FILE, e);
+        }
+    }
+
+    // Where the camera is now, e.g. to check a replayed session against its recording
+    pub fn camera_pose(&self) -> CameraPose {
+        CameraPose::capture(&self.camera, &self.projection)
+    }
+
+    pub fn recall_bookmark(&mut self, slot: u8) {
+        let Some(pose) = self.bookmarks.get(slot).copied() else {
+            log::info!("No camera bookmark {}", slot);
+            return;
+        };
+        self.camera_playback = None;
+        pose.apply(&mut self.camera, &mut self.projection);
+        self.camera_controller.sync(&self.camera);
+        self.snap_camera();
+    }
+
+    pub fn play_camera_path(&mut self, path: CameraPath, looping: bool) {
+        if path.keyframes().is_empty() {
+            return;
+        }
+        self.camera_playback = Some(PathPlayback::new(path, looping));
+    }
+
+    // Renders `path` at `fps` offscreen into `dir` as frame_00000.png,
+    // frame_00001.png, ... and returns the number of frames written
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn capture_path(&mut self, path: &CameraPath, fps: f32, dir: &std::path::Path) -> anyhow::Result<usize> {
+        use anyhow::Context;
+
+        let frames = path.frames(fps)?;
+        std::fs::create_dir_all(dir).with_context(|| format!("Unable to create {:?}", dir))?;
+        self.stop_camera_path();
+        let mut count = 0;
+        for pose in frames {
+            pose.apply(&mut self.camera, &mut self.projection);
+            self.snap_camera();
+            self.prepare_render(1.0);
+            let file = dir.join(format!("frame_{:05}.png", count));
+            self.render_to_image()?
+                .save(&file)
+                .with_context(|| format!("Unable to write {:?}", file))?;
+            count += 1;
+        }
+        self.camera_controller.sync(&self.camera);
+        Ok(count)
+    }
+
+    // Hands the camera back to the controller where the path left it
+    pub fn stop_camera_path(&mut self) {
+        if self.camera_playback.take().is_some() {
+            self.camera_controller.sync(&self.camera);
+        }
+    }
+
+    // Rebuilds pipelines whose WGSL changed on disk. On failure the last good
+    // pipeline keeps rendering and the error is logged and shown on screen.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reload_shaders(&mut self, changed: &std::collections::HashSet<String>) {
+        // Edits to an included module affect every shader that pulls it in
+        let mut affected = self.shader_cache.invalidate(changed);
+        affected.extend(changed.iter().cloned());
+        if affected.is_empty() {
+            return;
+        }
+        self.pipeline_cache.clear();
+        for ty in PipelineType::ALL {
+            if !affected.contains(ty.shader_file()) {
+                continue;
+            }
+            let layout = match ty {
+                PipelineType::Light => &self.light_pipeline_layout,
+                _ => &self.render_pipeline_layout,
+            };
+            let result = pollster::block_on(build_pipelines(
+                &self.device,
+                &self.config,
+                layout,
+                &mut self.shader_cache,
+                &mut self.pipeline_cache,
+                ty,
+                self.sample_count,
+                false,
+            ));
+
+            match result {
+                Ok(pipelines) => {
+                    log::info!("Reloaded {}", ty.shader_file());
+                    for (variant, pipeline) in pipelines {
+                        self.pipelines.insert((ty, variant), pipeline);
+                    }
+                    self.shader_errors.remove(ty.shader_file());
+                }
+                Err(e) => {
+                    log::error!("Shader reload failed, keeping previous pipeline\n{}", e.report);
+                    self.shader_errors.insert(ty.shader_file(), e);
                 }
             }
-            _ => false,
-        }
-    }
-    
-    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-        match (code, is_pressed) {
-            (KeyCode::Escape, true) => event_loop.exit(),
-            (KeyCode::Tab, true) => {
-                self.current_pipeline = match self.current_pipeline {
-                    PipelineType::Default => PipelineType::Experimental,
-                    PipelineType::Experimental => PipelineType::Default,
-                    _ => PipelineType::Default,
-                };
-            },
-            _ => {}
         }
+
+        if let Some(debug_views) = self.reload_standalone(debug_view::SHADER, &affected, |state| {
+            DebugViews::new(
+                &state.device,
+                state.config.format,
+                state.sample_count,
+                &state.render_pipeline_layout,
+                &mut state.shader_cache,
+                &mut state.pipeline_cache,
+            )
+        }) {
+            self.debug_views = debug_views;
+        }
+        if let Some(oit_composite) = self.reload_standalone(oit::COMPOSITE_SHADER, &affected, |state| {
+            OitComposite::new(&state.device, &mut state.shader_cache, &mut state.pipeline_cache, state.config.format, state.sample_count)
+        }) {
+            self.oit_composite = oit_composite;
+        }
+        self.reload_standalone(debug_draw::SHADER, &affected, |state| {
+            state.debug_draw.reload(
+                &state.device,
+                state.config.format,
+                state.sample_count,
+                &state.camera_resources.layout,
+                &mut state.shader_cache,
+                &mut state.pipeline_cache,
+            )
+        });
+        if let Some(text_overlay) = self.reload_standalone(text_overlay::SHADER, &affected, |state| {
+            TextOverlay::new(&state.device, state.config.format, &mut state.shader_cache, &mut state.pipeline_cache)
+        }) {
+            self.text_overlay = text_overlay;
+        }
+
+        self.show_shader_errors();
+    }
+
+    // Rebuilds pipelines that aren't part of the material set when their shader
+    // is affected, `None` leaves the previous ones in place
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reload_standalone<T>(
+        &mut self,
+        file: &'static str,
+        affected: &std::collections::HashSet<String>,
+        build: impl FnOnce(&mut Self) -> Result<T, ShaderError>,
+    ) -> Option<T> {
+        if !affected.contains(file) {
+            return None;
+        }
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let result = build(self);
+        let result = match (result, pollster::block_on(self.device.pop_error_scope())) {
+            (Ok(value), None) => Ok(value),
+            (Ok(_), Some(e)) => Err(ShaderError::new(file, e.to_string())),
+            (Err(e), _) => Err(e),
+        };
+        match result {
+            Ok(value) => {
+                log::info!("Reloaded {}", file);
+                self.shader_errors.remove(file);
+                Some(value)
+            }
+            Err(e) => {
+                log::error!("Shader reload failed, keeping previous pipeline\n{}", e.report);
+                self.shader_errors.insert(file, e);
+                None
+            }
+        }
+    }
+
+    // Puts the current shader errors in the title and on screen
+    fn show_shader_errors(&mut self) {
+        let errors = self.shader_errors.values().collect::<Vec<_>>();
+        let title = match errors.first() {
+            Some(e) => format!("{} - shader error: {}", self.window_title, e),
+            None => self.window_title.clone(),
+        };
+        if let Some(window) = &self.window {
+            window.set_title(&title);
+        }
+
+        let text = errors.iter().map(|e| e.report.trim_end()).collect::<Vec<_>>().join("\n\n");
+        self.text_overlay.set_text(
+            &self.device,
+            &self.queue,
+            &text,
+            self.config.width,
+            self.config.height,
+            self.window.as_ref().map_or(1.0, |window| window.scale_factor()),
+        );
+    }
+
+    pub fn input(&mut self, event: &InputEvent) -> bool {
+        // Give the cursor back when another window takes over
+        if let InputEvent::Focused(false) = event {
+            self.set_mouse_capture(false);
+        }
+        self.input.handle_event(event)
+    }
+
+    // Hides the cursor and keeps it in the window so the mouse looks around
+    // freely. Locking is preferred, platforms without it (X11, Windows) confine.
+    pub fn set_mouse_capture(&mut self, capture: bool) {
+        if capture == self.input.mouse_captured() {
+            return;
+        }
+        let Some(window) = &self.window else {
+            return;
+        };
+        if capture {
+            let grabbed = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = grabbed {
+                log::warn!("Unable to capture the mouse: {}", e);
+                return;
+            }
+        } else if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+            log::warn!("Unable to release the mouse: {}", e);
+        }
+        window.set_cursor_visible(!capture);
+        self.input.set_mouse_captured(capture);
+    }
+
+    // Gamepad events since the last call, to be passed to `input` before the next update
+    pub fn poll_gamepad(&mut self) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        if let Some(gamepad) = self.gamepad.as_mut() {
+            gamepad.poll(&mut events);
+        }
+        events.into_iter().map(InputEvent::from).collect()
+    }
+
+    pub fn input_map(&mut self) -> &mut InputMap {
+        &mut self.input
+    }
+
+    // Replaces the hardware gamepad, e.g. with a `VirtualGamepad` for scripted runs
+    pub fn set_gamepad(&mut self, gamepad: Box<dyn GamepadSource>) {
+        self.gamepad = Some(gamepad);
+    }
+
+    fn create_gamepad() -> Option<Box<dyn GamepadSource>> {
+        #[cfg(all(feature = "gamepad", not(target_arch = "wasm32")))]
+        match crate::gamepad::GilrsGamepad::new() {
+            Ok(gamepad) => return Some(Box::new(gamepad)),
+            Err(e) => log::warn!("{}", e),
+        }
+        None
+    }
+
+    pub fn exit_requested(&self) -> bool {
+        self.exit_requested
     }
-}
 
+    fn load_input_map(config: &wgpu::SurfaceConfiguration) -> InputMap {
+        #[allow(unused_mut)]
+        let mut input = InputMap::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(text) = std::fs::read_to_string(resources::get_user_path(INPUT_FILE)) {
+            match InputMap::from_config(&text) {
+                Ok(loaded) => input = loaded,
+                Err(e) => log::warn!("Ignoring {}: {}", INPUT_FILE, e),
+            }
+        }
+        input.resize(config.width, config.height);
+        input
+    }
 
+    // Writes the current bindings, including ones changed at runtime
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_input_map(&self) -> std::io::Result<()> {
+        resources::save_user_file(INPUT_FILE, &self.input.serialize())
+    }
+
+    fn handle_actions(&mut self) {
+        // The first press only releases a captured mouse
+        if self.input.pressed("exit") {
+            if self.input.mouse_captured() {
+                self.set_mouse_capture(false);
+            } else {
+                self.exit_requested = true;
+            }
+        }
+        if self.input.pressed("toggle_mouse_capture") {
+            self.set_mouse_capture(!self.input.mouse_captured());
+        }
+        if self.input.pressed("toggle_transparency") {
+            self.set_transparency_mode(self.transparency_mode.next());
+        }
+        if self.input.pressed("next_debug_view") {
+            self.set_debug_view(self.debug_view.next());
+        }
+        if self.input.pressed("toggle_debug_shapes") {
+            self.show_debug_shapes = !self.show_debug_shapes;
+        }
+        if self.input.pressed("next_controller") {
+            self.set_controller(self.controller_kind.next());
+        }
+        if self.input.pressed("frame_scene") {
+            self.frame_scene();
+        }
+        if self.input.pressed("toggle_orthographic") {
+            self.set_orthographic(!self.use_orthographic);
+        }
+        // Bookmarks in slot order, looping
+        if self.input.pressed("play_camera_path") {
+            match self.camera_playback {
+                Some(_) => self.stop_camera_path(),
+                None => self.play_camera_path(CameraPath::from_bookmarks(&self.bookmarks, BOOKMARK_PATH_SECONDS), true),
+            }
+        }
+        for slot in 1..=9 {
+            if !self.input.pressed(&format!("bookmark_{}", slot)) {
+                continue;
+            }
+            if self.input.held("store_bookmark") {
+                self.store_bookmark(slot);
+            } else {
+                self.recall_bookmark(slot);
+            }
+        }
+        if self.input.pressed("toggle_pause") {
+            self.timestep.set_paused(!self.timestep.is_paused());
+            log::info!("Simulation {}", if self.timestep.is_paused() { "paused" } else { "resumed" });
+        }
+        if self.input.pressed("single_step") {
+            self.timestep.set_paused(true);
+            self.timestep.single_step();
+        }
+        for (action, factor) in [("slower", 0.5), ("faster", 2.0)] {
+            if self.input.pressed(action) {
+                self.timestep.set_time_scale(self.timestep.time_scale() * factor);
+                log::info!("Time scale: {}", self.timestep.time_scale());
+            }
+        }
+        if self.input.pressed("next_present_mode") {
+            // Unsupported modes fall back, so step from the requested one to get past them
+            self.requested_present_mode = frame_pacing::next_present_mode(self.requested_present_mode);
+            self.set_present_mode(self.requested_present_mode);
+        }
+        if self.input.pressed("toggle_frame_limit") {
+            self.frame_limiter.set_enabled(!self.frame_limiter.is_enabled());
+            match self.frame_limiter.is_enabled() {
+                true => log::info!("Frame limit: {} fps", self.frame_limiter.target_fps()),
+                false => log::info!("Frame limit off"),
+            }
+        }
+        if self.input.pressed("next_pipeline") {
+            self.current_pipeline = match self.current_pipeline {
+                PipelineType::Default => PipelineType::Experimental,
+                PipelineType::Experimental => PipelineType::Default,
+                _ => PipelineType::Default,
+            };
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use crate::pipeline::{PipelineCache, RenderPipelineBuilder};
+use crate::reflect;
+use crate::shader::{ShaderCache, ShaderDefines, ShaderError};
+
+// Text in a translucent panel over the top left of the frame, e.g. shader
+// errors that would otherwise only reach the log and the title bar
+
+pub const SHADER: &str = "text_overlay.wgsl";
+const GLYPH_WIDTH: u32 = 10;
+const GLYPH_HEIGHT: u32 = 20;
+// Panel texels around the text
+const PADDING: u32 = 6;
+// Window pixels between the panel and the window edge
+const MARGIN: u32 = 16;
+const BACKGROUND: [u8; 4] = [48, 0, 0, 224];
+const FOREGROUND: [u8; 4] = [255, 210, 210, 255];
+const TAB_WIDTH: usize = 4;
+
+// Printable ASCII from ' ' to '~', DejaVu Sans Mono Bold rasterized to a 10x20
+// cell. One row per entry, the leftmost pixel in bit 9.
+const GLYPHS: [[u16; GLYPH_HEIGHT as usize]; 95] = [
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // ' '
+    [0x000, 0x000, 0x000, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x020, 0x000, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '!'
+    [0x000, 0x000, 0x000, 0x098, 0x098, 0x098, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '"'
+    [0x000, 0x000, 0x000, 0x06C, 0x048, 0x1FC, 0x1FC, 0x0D8, 0x0D0, 0x3FC, 0x1B0, 0x1B0, 0x120, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '#'
+    [0x000, 0x000, 0x020, 0x020, 0x0F8, 0x1E8, 0x1A0, 0x1E0, 0x0F8, 0x038, 0x02C, 0x1F8, 0x0F0, 0x020, 0x020, 0x000, 0x000, 0x000, 0x000, 0x000], // '$'
+    [0x000, 0x000, 0x000, 0x1C0, 0x340, 0x360, 0x1C4, 0x018, 0x0C0, 0x03C, 0x024, 0x034, 0x01C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '%'
+    [0x000, 0x000, 0x020, 0x0F0, 0x0C0, 0x0C0, 0x0C0, 0x1E0, 0x1F4, 0x33C, 0x31C, 0x1FC, 0x0FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '&'
+    [0x000, 0x000, 0x000, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '\''
+    [0x000, 0x000, 0x010, 0x030, 0x020, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x020, 0x030, 0x010, 0x000, 0x000, 0x000, 0x000, 0x000], // '('
+    [0x000, 0x000, 0x0C0, 0x040, 0x060, 0x020, 0x030, 0x030, 0x030, 0x030, 0x030, 0x020, 0x060, 0x040, 0x0C0, 0x000, 0x000, 0x000, 0x000, 0x000], // ')'
+    [0x000, 0x000, 0x020, 0x020, 0x1F8, 0x070, 0x0F8, 0x128, 0x020, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '*'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x060, 0x060, 0x060, 0x3FC, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '+'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x020, 0x060, 0x060, 0x060, 0x040, 0x000, 0x000, 0x000, 0x000, 0x000], // ','
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x0F0, 0x0F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '-'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x020, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '.'
+    [0x000, 0x000, 0x000, 0x008, 0x018, 0x010, 0x030, 0x030, 0x020, 0x060, 0x040, 0x0C0, 0x080, 0x180, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '/'
+    [0x000, 0x000, 0x020, 0x0F8, 0x1D8, 0x198, 0x18C, 0x1AC, 0x1AC, 0x18C, 0x198, 0x0F8, 0x0F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '0'
+    [0x000, 0x000, 0x000, 0x1F0, 0x0F0, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x1FC, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '1'
+    [0x000, 0x000, 0x040, 0x1F8, 0x118, 0x018, 0x018, 0x038, 0x070, 0x0E0, 0x0C0, 0x1F8, 0x1F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '2'
+    [0x000, 0x000, 0x060, 0x1F8, 0x018, 0x018, 0x038, 0x070, 0x018, 0x00C, 0x01C, 0x1F8, 0x1F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '3'
+    [0x000, 0x000, 0x000, 0x038, 0x078, 0x078, 0x0D8, 0x098, 0x198, 0x1FC, 0x1FC, 0x018, 0x018, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '4'
+    [0x000, 0x000, 0x000, 0x1F8, 0x180, 0x180, 0x1F0, 0x1F8, 0x018, 0x00C, 0x018, 0x1F8, 0x1F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '5'
+    [0x000, 0x000, 0x010, 0x0F8, 0x0C0, 0x180, 0x1B0, 0x1F8, 0x18C, 0x18C, 0x18C, 0x0F8, 0x0F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '6'
+    [0x000, 0x000, 0x000, 0x1FC, 0x018, 0x018, 0x038, 0x030, 0x030, 0x060, 0x060, 0x0E0, 0x0C0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '7'
+    [0x000, 0x000, 0x020, 0x0F8, 0x198, 0x188, 0x0D8, 0x0F0, 0x1D8, 0x18C, 0x18C, 0x1F8, 0x0F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '8'
+    [0x000, 0x000, 0x000, 0x0F0, 0x198, 0x198, 0x19C, 0x19C, 0x1FC, 0x048, 0x018, 0x1B8, 0x1F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '9'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x060, 0x060, 0x000, 0x000, 0x020, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // ':'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x060, 0x060, 0x000, 0x000, 0x020, 0x060, 0x060, 0x060, 0x040, 0x000, 0x000, 0x000, 0x000, 0x000], // ';'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x00C, 0x03C, 0x1E0, 0x180, 0x1E0, 0x03C, 0x00C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '<'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x1FC, 0x1FC, 0x000, 0x1FC, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '='
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x100, 0x1E0, 0x078, 0x01C, 0x07C, 0x1E0, 0x180, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '>'
+    [0x000, 0x000, 0x020, 0x0F8, 0x098, 0x018, 0x038, 0x030, 0x060, 0x060, 0x000, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '?'
+    [0x000, 0x000, 0x000, 0x030, 0x0F8, 0x18C, 0x13C, 0x37C, 0x344, 0x244, 0x36C, 0x33C, 0x100, 0x1C0, 0x07C, 0x000, 0x000, 0x000, 0x000, 0x000], // '@'
+    [0x000, 0x000, 0x000, 0x070, 0x070, 0x0F0, 0x0D8, 0x0D8, 0x1D8, 0x1F8, 0x19C, 0x18C, 0x30C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'A'
+    [0x000, 0x000, 0x000, 0x1F8, 0x19C, 0x18C, 0x198, 0x1F8, 0x19C, 0x18C, 0x18C, 0x1FC, 0x1F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'B'
+    [0x000, 0x000, 0x010, 0x07C, 0x0EC, 0x1C0, 0x180, 0x180, 0x180, 0x180, 0x0C0, 0x0FC, 0x078, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'C'
+    [0x000, 0x000, 0x000, 0x1F0, 0x1B8, 0x19C, 0x18C, 0x18C, 0x18C, 0x18C, 0x198, 0x1F8, 0x1F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'D'
+    [0x000, 0x000, 0x000, 0x1FC, 0x1C0, 0x180, 0x180, 0x1F8, 0x1C0, 0x180, 0x180, 0x1FC, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'E'
+    [0x000, 0x000, 0x000, 0x1FC, 0x1C0, 0x180, 0x1C0, 0x1F8, 0x1C0, 0x180, 0x180, 0x180, 0x180, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'F'
+    [0x000, 0x000, 0x010, 0x0F8, 0x0C8, 0x180, 0x180, 0x19C, 0x19C, 0x18C, 0x1CC, 0x0FC, 0x078, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'G'
+    [0x000, 0x000, 0x000, 0x18C, 0x18C, 0x18C, 0x19C, 0x1FC, 0x19C, 0x18C, 0x18C, 0x18C, 0x18C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'H'
+    [0x000, 0x000, 0x000, 0x1F8, 0x070, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x1F8, 0x1F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'I'
+    [0x000, 0x000, 0x000, 0x0F8, 0x018, 0x018, 0x018, 0x018, 0x018, 0x018, 0x018, 0x1F8, 0x1F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'J'
+    [0x000, 0x000, 0x000, 0x19C, 0x198, 0x1B0, 0x1E0, 0x1F0, 0x1F0, 0x1B8, 0x198, 0x19C, 0x18C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'K'
+    [0x000, 0x000, 0x000, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0FC, 0x0FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'L'
+    [0x000, 0x000, 0x000, 0x19C, 0x1DC, 0x1DC, 0x1FC, 0x17C, 0x16C, 0x10C, 0x10C, 0x10C, 0x10C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'M'
+    [0x000, 0x000, 0x000, 0x18C, 0x1CC, 0x1CC, 0x1EC, 0x1EC, 0x1AC, 0x1BC, 0x19C, 0x19C, 0x19C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'N'
+    [0x000, 0x000, 0x020, 0x0F8, 0x1D8, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x19C, 0x1F8, 0x0F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'O'
+    [0x000, 0x000, 0x000, 0x1F8, 0x19C, 0x18C, 0x18C, 0x1FC, 0x1F8, 0x180, 0x180, 0x180, 0x180, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'P'
+    [0x000, 0x000, 0x020, 0x0F8, 0x1D8, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x19C, 0x1F8, 0x0F8, 0x018, 0x008, 0x000, 0x000, 0x000, 0x000, 0x000], // 'Q'
+    [0x000, 0x000, 0x000, 0x1F8, 0x198, 0x19C, 0x198, 0x1F8, 0x1F0, 0x198, 0x198, 0x18C, 0x18C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'R'
+    [0x000, 0x000, 0x020, 0x0F8, 0x188, 0x180, 0x1C0, 0x0F0, 0x038, 0x01C, 0x01C, 0x1F8, 0x1F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'S'
+    [0x000, 0x000, 0x000, 0x1FC, 0x070, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'T'
+    [0x000, 0x000, 0x000, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x18C, 0x1F8, 0x0F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'U'
+    [0x000, 0x000, 0x000, 0x18C, 0x18C, 0x18C, 0x198, 0x098, 0x0D8, 0x0D8, 0x0F0, 0x070, 0x070, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'V'
+    [0x000, 0x000, 0x000, 0x304, 0x304, 0x324, 0x36C, 0x17C, 0x1FC, 0x1DC, 0x1DC, 0x19C, 0x198, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'W'
+    [0x000, 0x000, 0x000, 0x18C, 0x198, 0x0F8, 0x070, 0x070, 0x070, 0x0F0, 0x0D8, 0x19C, 0x38C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'X'
+    [0x000, 0x000, 0x000, 0x18C, 0x19C, 0x0D8, 0x0F8, 0x070, 0x070, 0x060, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'Y'
+    [0x000, 0x000, 0x000, 0x1FC, 0x01C, 0x018, 0x030, 0x070, 0x060, 0x0C0, 0x1C0, 0x1FC, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'Z'
+    [0x000, 0x000, 0x070, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x070, 0x000, 0x000, 0x000, 0x000, 0x000], // '['
+    [0x000, 0x000, 0x000, 0x180, 0x080, 0x0C0, 0x040, 0x060, 0x020, 0x030, 0x030, 0x010, 0x018, 0x008, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '\\'
+    [0x000, 0x000, 0x0F0, 0x070, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x070, 0x0F0, 0x000, 0x000, 0x000, 0x000, 0x000], // ']'
+    [0x000, 0x000, 0x000, 0x070, 0x0F8, 0x198, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '^'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x3FE, 0x000, 0x000, 0x000, 0x000], // '_'
+    [0x000, 0x000, 0x0C0, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '`'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x0F0, 0x1F8, 0x00C, 0x0FC, 0x1DC, 0x18C, 0x19C, 0x0FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'a'
+    [0x000, 0x000, 0x180, 0x180, 0x180, 0x1B8, 0x1F8, 0x18C, 0x18C, 0x18C, 0x18C, 0x1F8, 0x1B8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'b'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x078, 0x0F8, 0x1C0, 0x180, 0x180, 0x1C0, 0x0F8, 0x078, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'c'
+    [0x000, 0x000, 0x008, 0x00C, 0x00C, 0x0FC, 0x1FC, 0x19C, 0x19C, 0x19C, 0x19C, 0x1FC, 0x0FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'd'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x070, 0x1F8, 0x18C, 0x1FC, 0x1FC, 0x180, 0x1DC, 0x0F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'e'
+    [0x000, 0x000, 0x038, 0x078, 0x060, 0x1F8, 0x1F8, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'f'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x0EC, 0x1FC, 0x19C, 0x19C, 0x18C, 0x19C, 0x1FC, 0x0FC, 0x018, 0x1F8, 0x0F0, 0x000, 0x000, 0x000, 0x000], // 'g'
+    [0x000, 0x000, 0x180, 0x180, 0x180, 0x1B8, 0x1F8, 0x198, 0x198, 0x198, 0x198, 0x198, 0x198, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'h'
+    [0x000, 0x020, 0x030, 0x020, 0x000, 0x0E0, 0x0F0, 0x030, 0x030, 0x030, 0x030, 0x1FC, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'i'
+    [0x000, 0x030, 0x030, 0x030, 0x000, 0x0F0, 0x0F0, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x030, 0x1F0, 0x1E0, 0x000, 0x000, 0x000, 0x000], // 'j'
+    [0x000, 0x000, 0x180, 0x180, 0x180, 0x19C, 0x198, 0x1F0, 0x1E0, 0x1F0, 0x198, 0x198, 0x18C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'k'
+    [0x000, 0x000, 0x1E0, 0x1E0, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x078, 0x03C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'l'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x1D8, 0x1FC, 0x16C, 0x16C, 0x16C, 0x16C, 0x16C, 0x16C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'm'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x1B8, 0x1F8, 0x198, 0x198, 0x198, 0x198, 0x198, 0x198, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'n'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x070, 0x1F8, 0x19C, 0x18C, 0x18C, 0x18C, 0x1F8, 0x0F0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'o'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x1B8, 0x1F8, 0x18C, 0x18C, 0x18C, 0x18C, 0x1F8, 0x1B8, 0x180, 0x180, 0x180, 0x000, 0x000, 0x000, 0x000], // 'p'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x0E8, 0x1FC, 0x19C, 0x19C, 0x19C, 0x19C, 0x1FC, 0x0FC, 0x00C, 0x00C, 0x00C, 0x000, 0x000, 0x000, 0x000], // 'q'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x0DC, 0x0FC, 0x0E0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x0C0, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'r'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x0F8, 0x1F8, 0x180, 0x0F0, 0x078, 0x018, 0x198, 0x1F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 's'
+    [0x000, 0x000, 0x000, 0x060, 0x060, 0x1F8, 0x1F8, 0x060, 0x060, 0x060, 0x060, 0x078, 0x038, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 't'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x198, 0x198, 0x198, 0x198, 0x198, 0x198, 0x1F8, 0x0F8, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'u'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x18C, 0x18C, 0x198, 0x0D8, 0x0D8, 0x0F0, 0x070, 0x070, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'v'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x304, 0x304, 0x324, 0x16C, 0x17C, 0x1DC, 0x1D8, 0x198, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'w'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x19C, 0x0D8, 0x0F0, 0x070, 0x070, 0x0F0, 0x1D8, 0x18C, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'x'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x18C, 0x18C, 0x198, 0x0D8, 0x0D8, 0x0F0, 0x070, 0x060, 0x060, 0x1E0, 0x1C0, 0x000, 0x000, 0x000, 0x000], // 'y'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x1F8, 0x1FC, 0x018, 0x030, 0x060, 0x0C0, 0x1F8, 0x1FC, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // 'z'
+    [0x000, 0x000, 0x038, 0x038, 0x060, 0x060, 0x060, 0x060, 0x1C0, 0x0E0, 0x060, 0x060, 0x060, 0x070, 0x038, 0x000, 0x000, 0x000, 0x000, 0x000], // '{'
+    [0x000, 0x000, 0x020, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x060, 0x000, 0x000, 0x000, 0x000], // '|'
+    [0x000, 0x000, 0x1C0, 0x0E0, 0x060, 0x060, 0x060, 0x070, 0x038, 0x030, 0x060, 0x060, 0x060, 0x060, 0x1E0, 0x000, 0x000, 0x000, 0x000, 0x000], // '}'
+    [0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x0C0, 0x1FC, 0x018, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000, 0x000], // '~'
+];
+
+struct Panel {
+    bind_group: wgpu::BindGroup,
+    // In texels, drawn at `scale` window pixels per texel
+    width: u32,
+    height: u32,
+}
+
+pub struct TextOverlay {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    panel: Option<Panel>,
+    scale: u32,
+}
+
+impl TextOverlay {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        shader_cache: &mut ShaderCache,
+        pipeline_cache: &mut PipelineCache,
+    ) -> Result<Self, ShaderError> {
+        let shader = shader_cache.get_or_compile(device, SHADER, &ShaderDefines::new())?;
+        let layout = reflect::create_bind_group_layout(device, "Text overlay bind group layout", &[(&shader.reflection, 0)])
+            .map_err(|e| ShaderError::new(SHADER, e))?;
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text overlay pipeline layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let builder = RenderPipelineBuilder::new("Text overlay pipeline", &pipeline_layout, &shader.module)
+            .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+            .color_format(format)
+            .cull_mode(None)
+            .depth_format(None);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Text overlay sampler"),
+            ..Default::default()
+        });
+
+        Ok(Self {
+            pipeline: pipeline_cache.get_or_build(device, &builder),
+            layout,
+            sampler,
+            panel: None,
+            scale: 1,
+        })
+    }
+
+    // Lays `text` out for a `width` x `height` window, wrapping long lines and
+    // dropping the ones that don't fit. Empty text hides the panel.
+    pub fn set_text(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        text: &str,
+        width: u32,
+        height: u32,
+        scale_factor: f64,
+    ) {
+        self.panel = None;
+        if text.is_empty() {
+            return;
+        }
+        self.scale = (scale_factor.round() as u32).max(1);
+        let columns = (width.saturating_sub(2 * MARGIN) / self.scale).saturating_sub(2 * PADDING) / GLYPH_WIDTH;
+        let rows = (height.saturating_sub(2 * MARGIN) / self.scale).saturating_sub(2 * PADDING) / GLYPH_HEIGHT;
+        let lines = wrap(text, columns as usize, rows as usize);
+        let Some(longest) = lines.iter().map(Vec::len).max().filter(|&len| len > 0) else {
+            return;
+        };
+
+        let panel_width = longest as u32 * GLYPH_WIDTH + 2 * PADDING;
+        let panel_height = lines.len() as u32 * GLYPH_HEIGHT + 2 * PADDING;
+        let mut pixels = BACKGROUND.repeat((panel_width * panel_height) as usize);
+        for (row, line) in lines.iter().enumerate() {
+            for (column, &c) in line.iter().enumerate() {
+                let glyph = &GLYPHS[(c - b' ') as usize];
+                let left = PADDING + column as u32 * GLYPH_WIDTH;
+                let top = PADDING + row as u32 * GLYPH_HEIGHT;
+                for (y, bits) in glyph.iter().enumerate() {
+                    for x in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+                            let i = (((top + y as u32) * panel_width + left + x) * 4) as usize;
+                            pixels[i..i + 4].copy_from_slice(&FOREGROUND);
+                        }
+                    }
+                }
+            }
+        }
+
+        let size = wgpu::Extent3d {
+            width: panel_width,
+            height: panel_height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text overlay"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * panel_width),
+                rows_per_image: Some(panel_height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text overlay bind group"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        self.panel = Some(Panel {
+            bind_group,
+            width: panel_width,
+            height: panel_height,
+        });
+    }
+
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>) {
+        let Some(panel) = &self.panel else { return };
+        // The viewport scales the panel up, nearest sampling keeps the pixels sharp
+        render_pass.set_viewport(
+            MARGIN as f32,
+            MARGIN as f32,
+            (panel.width * self.scale) as f32,
+            (panel.height * self.scale) as f32,
+            0.0,
+            1.0,
+        );
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &panel.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+// Splits `text` into at most `rows` lines of at most `columns` printable ASCII
+// characters, anything else becomes '?'
+fn wrap(text: &str, columns: usize, rows: usize) -> Vec<Vec<u8>> {
+    if columns == 0 {
+        return Vec::new();
+    }
+    let mut lines = Vec::new();
+    for line in text.lines() {
+        let mut chars = Vec::new();
+        for c in line.chars() {
+            match c {
+                '\t' => chars.extend(std::iter::repeat_n(b' ', TAB_WIDTH)),
+                ' '..='~' => chars.push(c as u8),
+                _ => chars.push(b'?'),
+            }
+        }
+        let trimmed = chars.len() - chars.iter().rev().take_while(|&&c| c == b' ').count();
+        chars.truncate(trimmed);
+        if chars.is_empty() {
+            lines.push(chars);
+        } else {
+            lines.extend(chars.chunks(columns).map(<[u8]>::to_vec));
+        }
+    }
+    lines.truncate(rows);
+    lines
+}
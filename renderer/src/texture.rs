@@ -1,5 +1,73 @@
-use image::GenericImageView;
 use anyhow::*;
+use std::collections::HashMap;
+
+const KTX2_MAGIC: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Compressed formats the device may support, requested at device creation
+pub const COMPRESSION_FEATURES: wgpu::Features = wgpu::Features::TEXTURE_COMPRESSION_BC
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ETC2)
+    .union(wgpu::Features::TEXTURE_COMPRESSION_ASTC);
+
+// How texel values are interpreted when sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    // Color data (albedo, emissive), decoded from sRGB on sample
+    Srgb,
+    // Non-color data (normal, roughness, metallic, AO), sampled as stored
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn rgba8_format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SamplerFilter {
+    // Pixel art and UI, no filtering between texels or mips
+    Nearest,
+    Bilinear,
+    Trilinear,
+    // Trilinear plus anisotropic filtering, sample count clamped to 1..=16
+    Anisotropic(u16),
+}
+
+impl SamplerFilter {
+    // Falls back to trilinear on adapters without anisotropic filtering (some
+    // GLES/WebGL devices). Those that have it support 16 samples.
+    pub fn supported_by(self, adapter: &wgpu::Adapter) -> Self {
+        let anisotropic = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+        match self {
+            SamplerFilter::Anisotropic(_) if !anisotropic => SamplerFilter::Trilinear,
+            SamplerFilter::Anisotropic(samples) => SamplerFilter::Anisotropic(samples.clamp(1, 16)),
+            filter => filter,
+        }
+    }
+}
+
+// How model textures are uploaded and sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureSettings {
+    pub filter: SamplerFilter,
+    // Generate mips for images that don't bring their own (KTX2/DDS keep theirs)
+    pub mipmaps: bool,
+}
+
+impl Default for TextureSettings {
+    fn default() -> Self {
+        Self {
+            filter: SamplerFilter::Anisotropic(16),
+            mipmaps: true,
+        }
+    }
+}
 
 pub struct Texture {
     #[allow(unused)]
@@ -15,35 +83,265 @@ impl Texture {
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         bytes: &[u8], 
-        label: &str
+        label: &str,
+        color_space: ColorSpace,
+        mipmaps: Option<&mut MipmapGenerator>,
     ) -> Result<Self> {
+        if bytes.starts_with(&KTX2_MAGIC) {
+            return Self::from_ktx2(device, queue, bytes, label);
+        }
+        if bytes.starts_with(b"DDS ") {
+            return Self::from_dds(device, queue, bytes, label, color_space);
+        }
         let img = image::load_from_memory(bytes)?;
-        Self::from_image(device, queue, &img, Some(label))
+        Self::from_image(device, queue, &img, Some(label), color_space, mipmaps)
+    }
+
+    // KTX2 stores its color space in the format, prebuilt mips are uploaded as-is
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> Result<Self> {
+        let reader = ktx2::Reader::new(bytes).map_err(|e| anyhow!("Invalid KTX2 file {label}: {e:?}"))?;
+        let header = reader.header();
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            bail!("KTX2 file {label} is not a single 2D texture");
+        }
+        // Basis Universal files have no format of their own, they'd need a transcoder
+        if header.supercompression_scheme == Some(ktx2::SupercompressionScheme::BasisLZ) {
+            bail!("KTX2 file {label} is Basis Universal (BasisLZ), which isn't supported. Re-encode it with zstd or without supercompression");
+        }
+        let format = header
+            .format
+            .and_then(ktx2_to_wgpu_format)
+            .ok_or_else(|| anyhow!("KTX2 file {label} has unsupported format {:?}", header.format))?;
+
+        // Each level is supercompressed on its own
+        let levels = reader
+            .levels()
+            .enumerate()
+            .map(|(mip, level)| {
+                let data = match header.supercompression_scheme {
+                    None => return Ok(level.data.to_vec()),
+                    Some(ktx2::SupercompressionScheme::Zstandard) => {
+                        let mut data = Vec::new();
+                        ruzstd::decoding::StreamingDecoder::new(level.data)
+                            .map_err(|e| anyhow!("{e}"))
+                            .and_then(|mut decoder| Ok(std::io::Read::read_to_end(&mut decoder, &mut data)?))
+                            .with_context(|| format!("KTX2 file {label} has invalid zstd data at mip {mip}"))?;
+                        data
+                    }
+                    Some(ktx2::SupercompressionScheme::ZLIB) => miniz_oxide::inflate::decompress_to_vec_zlib(level.data)
+                        .map_err(|e| anyhow!("KTX2 file {label} has invalid zlib data at mip {mip}: {e}"))?,
+                    Some(scheme) => bail!("KTX2 file {label} uses unsupported supercompression {scheme:?}"),
+                };
+                if data.len() as u64 != level.uncompressed_byte_length {
+                    bail!("KTX2 file {label} mip {mip} decompressed to {} bytes instead of {}", data.len(), level.uncompressed_byte_length);
+                }
+                Ok(data)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let levels = levels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        Self::from_mip_levels(device, queue, label, format, header.pixel_width, header.pixel_height, &levels)
+    }
+
+    // Legacy DXT files carry no color space, so `color_space` decides for those
+    pub fn from_dds(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        label: &str,
+        color_space: ColorSpace,
+    ) -> Result<Self> {
+        let dds = ddsfile::Dds::read(bytes)?;
+        if dds.get_depth() > 1 || dds.get_num_array_layers() > 1 {
+            bail!("DDS file {label} is not a single 2D texture");
+        }
+        let format = dds_to_wgpu_format(&dds, color_space)
+            .ok_or_else(|| anyhow!("DDS file {label} has unsupported format"))?;
+
+        let (width, height) = (dds.get_width(), dds.get_height());
+        let mut data = dds.get_data(0)?;
+        let mut levels = Vec::new();
+        for mip in 0..dds.get_num_mipmap_levels().max(1) {
+            let level_size = mip_level_byte_size(format, width, height, mip);
+            if data.len() < level_size {
+                bail!("DDS file {label} is truncated at mip {mip}");
+            }
+            let (level, rest) = data.split_at(level_size);
+            levels.push(level);
+            data = rest;
+        }
+        Self::from_mip_levels(device, queue, label, format, width, height, &levels)
+    }
+
+    // Uploads a prebuilt mip chain. Compressed formats the device can't sample
+    // are decompressed to RGBA8 on the CPU when a decoder exists.
+    fn from_mip_levels(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        label: &str,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        levels: &[&[u8]],
+    ) -> Result<Self> {
+        let mut format = format;
+        let decompressed;
+        let mut levels = levels.to_vec();
+        if !device.features().contains(format.required_features()) {
+            let target = if format.is_srgb() {
+                wgpu::TextureFormat::Rgba8UnormSrgb
+            } else if is_signed(format) {
+                wgpu::TextureFormat::Rgba8Snorm
+            } else {
+                wgpu::TextureFormat::Rgba8Unorm
+            };
+            decompressed = levels
+                .iter()
+                .enumerate()
+                .map(|(mip, data)| {
+                    let size = mip_level_size(width, height, mip as u32);
+                    decompress_rgba8(format, size.width, size.height, data)
+                        .with_context(|| format!("{label}: {format:?} is not supported by this adapter"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            log::warn!("{label}: {format:?} is not supported by this adapter, decompressed to {target:?}");
+            levels = decompressed.iter().map(Vec::as_slice).collect();
+            format = target;
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(
+            &wgpu::TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: levels.len() as u32,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            }
+        );
+
+        let (block_width, block_height) = format.block_dimensions();
+        let block_size = format.block_copy_size(None).unwrap_or(4);
+        for (mip, data) in levels.iter().enumerate() {
+            let mip_size = mip_level_size(width, height, mip as u32);
+            let blocks_wide = mip_size.width.div_ceil(block_width);
+            let blocks_high = mip_size.height.div_ceil(block_height);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfo {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: mip as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(blocks_wide * block_size),
+                    rows_per_image: Some(blocks_high),
+                },
+                mip_size.physical_size(format),
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: None,
+        })
     }
 
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
-        label: Option<&str>
+        label: Option<&str>,
+        color_space: ColorSpace,
+        mipmaps: Option<&mut MipmapGenerator>,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        Ok(Self::from_rgba8(device, queue, &img.to_rgba8(), label, color_space, mipmaps))
+    }
+
+    // 1x1 texture filled with a single color
+    pub fn from_color(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        color: [u8; 4],
+        label: &str,
+        color_space: ColorSpace,
+    ) -> Self {
+        let img = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        Self::from_rgba8(device, queue, &img, Some(label), color_space, None)
+    }
+
+    // Fallback albedo, multiplied by the material's diffuse color in the shader
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_color(device, queue, [255, 255, 255, 255], "white_texture", ColorSpace::Srgb)
+    }
+
+    // Fallback normal map, tangent space (0.5, 0.5, 1.0) points straight out of the surface
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        Self::from_color(device, queue, [128, 128, 255, 255], "flat_normal_texture", ColorSpace::Linear)
+    }
+
+    // Magenta/black checker used when a referenced texture could not be loaded
+    pub fn missing(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        const SIZE: u32 = 8;
+        let img = image::RgbaImage::from_fn(SIZE, SIZE, |x, y| {
+            if (x + y) % 2 == 0 {
+                image::Rgba([255, 0, 255, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+        Self::from_rgba8(device, queue, &img, Some("missing_texture"), ColorSpace::Srgb, None)
+    }
+
+    fn from_rgba8(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: Option<&str>,
+        color_space: ColorSpace,
+        mipmaps: Option<&mut MipmapGenerator>,
+    ) -> Self {
+        let dimensions = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
+        let mip_level_count = if mipmaps.is_some() {
+            size.max_mips(wgpu::TextureDimension::D2)
+        } else {
+            1
+        };
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
         let texture = device.create_texture(
             &wgpu::TextureDescriptor {
                 label,
                 size,
-                mip_level_count: 1,
+                mip_level_count,
                 sample_count: 1,
                 dimension: wgpu::TextureDimension::D2,
-                format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                format: color_space.rgba8_format(),
+                usage,
                 view_formats: &[],
             }
         );
@@ -55,7 +353,7 @@ impl Texture {
                 mip_level: 0,
                 origin: wgpu::Origin3d::ZERO,
             },
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * dimensions.0),
@@ -64,12 +362,44 @@ impl Texture {
             size,
         );
 
+        if let Some(mipmaps) = mipmaps
+            && mip_level_count > 1
+        {
+            mipmaps.generate(device, queue, &texture);
+        }
+
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        Ok(Self { 
+        Self { 
             texture, 
             view,
             sampler: None,
+        }
+    }
+
+    pub fn create_sampler(
+        device: &wgpu::Device,
+        filter: SamplerFilter,
+        address_mode: wgpu::AddressMode,
+    ) -> wgpu::Sampler {
+        let (mag_filter, min_filter, mipmap_filter, anisotropy_clamp) = match filter {
+            SamplerFilter::Nearest => (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest, 1),
+            SamplerFilter::Bilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest, 1),
+            SamplerFilter::Trilinear => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, 1),
+            // Anisotropy requires every filter to be linear
+            SamplerFilter::Anisotropic(clamp) => (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, wgpu::FilterMode::Linear, clamp.clamp(1, 16)),
+        };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(&format!("{filter:?}_sampler")),
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
         })
     }
     
@@ -94,10 +424,326 @@ impl Texture {
         let texture = device.create_texture(&desc);
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        Self { 
-            texture, 
+        Self {
+            texture,
             view,
             sampler: None,
         }
     }
+
+    // Copies mip 0 of an RGBA8 texture (created with COPY_SRC) back to the CPU,
+    // blocks until the GPU is done
+    pub fn read_rgba8(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<image::RgbaImage> {
+        let format = texture.format();
+        // Swapchain formats are often BGRA, those are swizzled after the copy
+        let bgra = match format.remove_srgb_suffix() {
+            wgpu::TextureFormat::Rgba8Unorm => false,
+            wgpu::TextureFormat::Bgra8Unorm => true,
+            _ => bail!("Can't read back {format:?} textures, only RGBA8 and BGRA8"),
+        };
+        let (width, height) = (texture.width(), texture.height());
+        let row_bytes = width * 4;
+        // Copies have to be done in whole rows of COPY_BYTES_PER_ROW_ALIGNMENT
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback_buffer"),
+            size: (padded_row_bytes * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("readback_encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::Wait)?;
+        receiver.recv()??;
+
+        let mapped = slice.get_mapped_range();
+        let mut pixels = mapped
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| &row[..row_bytes as usize])
+            .copied()
+            .collect::<Vec<_>>();
+        drop(mapped);
+        if bgra {
+            pixels.chunks_exact_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        buffer.unmap();
+        image::RgbaImage::from_raw(width, height, pixels).context("Readback size mismatch")
+    }
+}
+
+// Fills mip levels 1.. by repeatedly blitting the previous level at half size.
+// The shader and sampler are shared, pipelines are built once per format.
+pub struct MipmapGenerator {
+    shader: wgpu::ShaderModule,
+    sampler: wgpu::Sampler,
+    pipelines: HashMap<wgpu::TextureFormat, wgpu::RenderPipeline>,
+}
+
+impl MipmapGenerator {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./shaders/blit.wgsl").into()),
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Mipmap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            shader,
+            sampler,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    fn pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) -> &wgpu::RenderPipeline {
+        let shader = &self.shader;
+        self.pipelines.entry(format).or_insert_with(|| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&format!("Mipmap pipeline {format:?}")),
+                layout: None,
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(format.into())],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                cache: None,
+            })
+        })
+    }
+
+    // The texture must be renderable and have RENDER_ATTACHMENT usage
+    pub fn generate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        let pipeline = self.pipeline(device, texture.format()).clone();
+        let bind_group_layout = pipeline.get_bind_group_layout(0);
+
+        let views = (0..texture.mip_level_count())
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Mip view"),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap encoder"),
+        });
+        for target in 1..views.len() {
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[target - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &views[target],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+// CPU fallback for compressed formats the adapter can't sample
+fn decompress_rgba8(format: wgpu::TextureFormat, width: u32, height: u32, data: &[u8]) -> Result<Vec<u8>> {
+    use wgpu::TextureFormat::*;
+
+    let decompressed = match format {
+        Bc6hRgbUfloat | Bc6hRgbFloat => bail!("BC6H is HDR and can't be decompressed to RGBA8"),
+        Astc { .. } => bail!("ASTC has no CPU decoder, ship the texture as BC7 or ETC2 for this adapter"),
+        Etc2Rgb8Unorm | Etc2Rgb8UnormSrgb | Etc2Rgb8A1Unorm | Etc2Rgb8A1UnormSrgb | Etc2Rgba8Unorm
+        | Etc2Rgba8UnormSrgb | EacR11Unorm | EacRg11Unorm | EacR11Snorm | EacRg11Snorm => {
+            crate::etc2::decompress(format, width, height, data)
+        }
+        _ => crate::bcn::decompress(format, width, height, data),
+    };
+    decompressed.ok_or_else(|| anyhow!("mip data is truncated or {format:?} has no CPU decoder"))
+}
+
+// Signed formats decompress to snorm bytes
+fn is_signed(format: wgpu::TextureFormat) -> bool {
+    use wgpu::TextureFormat::*;
+    matches!(format, Bc4RSnorm | Bc5RgSnorm | EacR11Snorm | EacRg11Snorm)
+}
+
+fn mip_level_size(width: u32, height: u32, mip: u32) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: (width >> mip).max(1),
+        height: (height >> mip).max(1),
+        depth_or_array_layers: 1,
+    }
+}
+
+fn mip_level_byte_size(format: wgpu::TextureFormat, width: u32, height: u32, mip: u32) -> usize {
+    let size = mip_level_size(width, height, mip);
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format.block_copy_size(None).unwrap_or(4);
+    (size.width.div_ceil(block_width) * size.height.div_ceil(block_height) * block_size) as usize
+}
+
+fn ktx2_to_wgpu_format(format: ktx2::Format) -> Option<wgpu::TextureFormat> {
+    use ktx2::Format as K;
+    use wgpu::{AstcBlock, AstcChannel, TextureFormat as W};
+
+    let astc = |block, srgb: bool| W::Astc {
+        block,
+        channel: if srgb { AstcChannel::UnormSrgb } else { AstcChannel::Unorm },
+    };
+
+    Some(match format {
+        K::R8G8B8A8_UNORM => W::Rgba8Unorm,
+        K::R8G8B8A8_SRGB => W::Rgba8UnormSrgb,
+        K::BC1_RGB_UNORM_BLOCK | K::BC1_RGBA_UNORM_BLOCK => W::Bc1RgbaUnorm,
+        K::BC1_RGB_SRGB_BLOCK | K::BC1_RGBA_SRGB_BLOCK => W::Bc1RgbaUnormSrgb,
+        K::BC2_UNORM_BLOCK => W::Bc2RgbaUnorm,
+        K::BC2_SRGB_BLOCK => W::Bc2RgbaUnormSrgb,
+        K::BC3_UNORM_BLOCK => W::Bc3RgbaUnorm,
+        K::BC3_SRGB_BLOCK => W::Bc3RgbaUnormSrgb,
+        K::BC4_UNORM_BLOCK => W::Bc4RUnorm,
+        K::BC4_SNORM_BLOCK => W::Bc4RSnorm,
+        K::BC5_UNORM_BLOCK => W::Bc5RgUnorm,
+        K::BC5_SNORM_BLOCK => W::Bc5RgSnorm,
+        K::BC6H_UFLOAT_BLOCK => W::Bc6hRgbUfloat,
+        K::BC6H_SFLOAT_BLOCK => W::Bc6hRgbFloat,
+        K::BC7_UNORM_BLOCK => W::Bc7RgbaUnorm,
+        K::BC7_SRGB_BLOCK => W::Bc7RgbaUnormSrgb,
+        K::ETC2_R8G8B8_UNORM_BLOCK => W::Etc2Rgb8Unorm,
+        K::ETC2_R8G8B8_SRGB_BLOCK => W::Etc2Rgb8UnormSrgb,
+        K::ETC2_R8G8B8A1_UNORM_BLOCK => W::Etc2Rgb8A1Unorm,
+        K::ETC2_R8G8B8A1_SRGB_BLOCK => W::Etc2Rgb8A1UnormSrgb,
+        K::ETC2_R8G8B8A8_UNORM_BLOCK => W::Etc2Rgba8Unorm,
+        K::ETC2_R8G8B8A8_SRGB_BLOCK => W::Etc2Rgba8UnormSrgb,
+        K::EAC_R11_UNORM_BLOCK => W::EacR11Unorm,
+        K::EAC_R11_SNORM_BLOCK => W::EacR11Snorm,
+        K::EAC_R11G11_UNORM_BLOCK => W::EacRg11Unorm,
+        K::EAC_R11G11_SNORM_BLOCK => W::EacRg11Snorm,
+        K::ASTC_4x4_UNORM_BLOCK => astc(AstcBlock::B4x4, false),
+        K::ASTC_4x4_SRGB_BLOCK => astc(AstcBlock::B4x4, true),
+        K::ASTC_5x4_UNORM_BLOCK => astc(AstcBlock::B5x4, false),
+        K::ASTC_5x4_SRGB_BLOCK => astc(AstcBlock::B5x4, true),
+        K::ASTC_5x5_UNORM_BLOCK => astc(AstcBlock::B5x5, false),
+        K::ASTC_5x5_SRGB_BLOCK => astc(AstcBlock::B5x5, true),
+        K::ASTC_6x5_UNORM_BLOCK => astc(AstcBlock::B6x5, false),
+        K::ASTC_6x5_SRGB_BLOCK => astc(AstcBlock::B6x5, true),
+        K::ASTC_6x6_UNORM_BLOCK => astc(AstcBlock::B6x6, false),
+        K::ASTC_6x6_SRGB_BLOCK => astc(AstcBlock::B6x6, true),
+        K::ASTC_8x5_UNORM_BLOCK => astc(AstcBlock::B8x5, false),
+        K::ASTC_8x5_SRGB_BLOCK => astc(AstcBlock::B8x5, true),
+        K::ASTC_8x6_UNORM_BLOCK => astc(AstcBlock::B8x6, false),
+        K::ASTC_8x6_SRGB_BLOCK => astc(AstcBlock::B8x6, true),
+        K::ASTC_8x8_UNORM_BLOCK => astc(AstcBlock::B8x8, false),
+        K::ASTC_8x8_SRGB_BLOCK => astc(AstcBlock::B8x8, true),
+        K::ASTC_10x5_UNORM_BLOCK => astc(AstcBlock::B10x5, false),
+        K::ASTC_10x5_SRGB_BLOCK => astc(AstcBlock::B10x5, true),
+        K::ASTC_10x6_UNORM_BLOCK => astc(AstcBlock::B10x6, false),
+        K::ASTC_10x6_SRGB_BLOCK => astc(AstcBlock::B10x6, true),
+        K::ASTC_10x8_UNORM_BLOCK => astc(AstcBlock::B10x8, false),
+        K::ASTC_10x8_SRGB_BLOCK => astc(AstcBlock::B10x8, true),
+        K::ASTC_10x10_UNORM_BLOCK => astc(AstcBlock::B10x10, false),
+        K::ASTC_10x10_SRGB_BLOCK => astc(AstcBlock::B10x10, true),
+        K::ASTC_12x10_UNORM_BLOCK => astc(AstcBlock::B12x10, false),
+        K::ASTC_12x10_SRGB_BLOCK => astc(AstcBlock::B12x10, true),
+        K::ASTC_12x12_UNORM_BLOCK => astc(AstcBlock::B12x12, false),
+        K::ASTC_12x12_SRGB_BLOCK => astc(AstcBlock::B12x12, true),
+        _ => return None,
+    })
+}
+
+fn dds_to_wgpu_format(dds: &ddsfile::Dds, color_space: ColorSpace) -> Option<wgpu::TextureFormat> {
+    use ddsfile::{D3DFormat, DxgiFormat};
+    use wgpu::TextureFormat as W;
+
+    let srgb = color_space == ColorSpace::Srgb;
+    if let Some(format) = dds.get_dxgi_format() {
+        return Some(match format {
+            DxgiFormat::R8G8B8A8_UNorm => W::Rgba8Unorm,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => W::Rgba8UnormSrgb,
+            DxgiFormat::BC1_UNorm => W::Bc1RgbaUnorm,
+            DxgiFormat::BC1_UNorm_sRGB => W::Bc1RgbaUnormSrgb,
+            DxgiFormat::BC2_UNorm => W::Bc2RgbaUnorm,
+            DxgiFormat::BC2_UNorm_sRGB => W::Bc2RgbaUnormSrgb,
+            DxgiFormat::BC3_UNorm => W::Bc3RgbaUnorm,
+            DxgiFormat::BC3_UNorm_sRGB => W::Bc3RgbaUnormSrgb,
+            DxgiFormat::BC4_UNorm => W::Bc4RUnorm,
+            DxgiFormat::BC4_SNorm => W::Bc4RSnorm,
+            DxgiFormat::BC5_UNorm => W::Bc5RgUnorm,
+            DxgiFormat::BC5_SNorm => W::Bc5RgSnorm,
+            DxgiFormat::BC6H_UF16 => W::Bc6hRgbUfloat,
+            DxgiFormat::BC6H_SF16 => W::Bc6hRgbFloat,
+            DxgiFormat::BC7_UNorm => W::Bc7RgbaUnorm,
+            DxgiFormat::BC7_UNorm_sRGB => W::Bc7RgbaUnormSrgb,
+            _ => return None,
+        });
+    }
+
+    Some(match dds.get_d3d_format()? {
+        D3DFormat::A8B8G8R8 => color_space.rgba8_format(),
+        D3DFormat::DXT1 if srgb => W::Bc1RgbaUnormSrgb,
+        D3DFormat::DXT1 => W::Bc1RgbaUnorm,
+        D3DFormat::DXT3 if srgb => W::Bc2RgbaUnormSrgb,
+        D3DFormat::DXT3 => W::Bc2RgbaUnorm,
+        D3DFormat::DXT5 if srgb => W::Bc3RgbaUnormSrgb,
+        D3DFormat::DXT5 => W::Bc3RgbaUnorm,
+        _ => return None,
+    })
 }
@@ -0,0 +1,120 @@
+use instant::Duration;
+
+// Turns variable frame times into a whole number of fixed simulation steps.
+// Leftover time stays in the accumulator, `alpha` says how far the frame is
+// between the last two steps so rendering can interpolate.
+#[derive(Debug, Clone)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    // Frames longer than this (breakpoints, window drags) don't have to be caught up
+    max_frame_time: Duration,
+    // Beyond this many steps per frame the simulation falls behind instead of
+    // spending ever longer frames catching up
+    max_steps: u32,
+    time_scale: f64,
+    paused: bool,
+    pending_steps: u32,
+    // Simulated time, scaled and without pauses
+    elapsed: Duration,
+}
+
+impl FixedTimestep {
+    pub const MIN_TIME_SCALE: f64 = 1.0 / 16.0;
+    pub const MAX_TIME_SCALE: f64 = 16.0;
+
+    pub fn new(hz: f64) -> Self {
+        Self {
+            step: Self::hz_to_step(hz),
+            accumulator: Duration::ZERO,
+            max_frame_time: Duration::from_millis(250),
+            max_steps: 8,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    fn hz_to_step(hz: f64) -> Duration {
+        Duration::from_secs_f64(1.0 / hz.max(1.0))
+    }
+
+    pub fn max_frame_time(mut self, max_frame_time: Duration) -> Self {
+        self.max_frame_time = max_frame_time;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn hz(&self) -> f64 {
+        1.0 / self.step.as_secs_f64()
+    }
+
+    pub fn set_hz(&mut self, hz: f64) {
+        self.step = Self::hz_to_step(hz);
+        self.accumulator = self.accumulator.min(self.step);
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.clamp(Self::MIN_TIME_SCALE, Self::MAX_TIME_SCALE);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // Runs exactly one step on the next `advance`, meant for stepping while paused
+    pub fn single_step(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    // Steps to simulate for a frame that took `dt`
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        if self.paused {
+            let steps = std::mem::take(&mut self.pending_steps);
+            self.elapsed += self.step * steps;
+            return steps;
+        }
+        self.accumulator += dt.min(self.max_frame_time).mul_f64(self.time_scale);
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        if steps > self.max_steps {
+            log::debug!("Simulation fell behind, dropping {} steps", steps - self.max_steps);
+            steps = self.max_steps;
+        }
+        steps += std::mem::take(&mut self.pending_steps);
+        self.elapsed += self.step * steps;
+        steps
+    }
+
+    // How far between the previous and the latest step the frame is, 0..1
+    pub fn alpha(&self) -> f32 {
+        if self.paused {
+            return 1.0;
+        }
+        (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+    }
+}
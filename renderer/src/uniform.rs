@@ -15,12 +15,6 @@ impl<T: bytemuck::Pod> UniformResource<T> {
         binding: u32,
     ) -> Self 
     {
-        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{label}_buffer")),
-            contents: bytemuck::cast_slice(data),
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-        });
-
         let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some(&format!("{label}_bind_group_layout")),
             entries: &[wgpu::BindGroupLayoutEntry {
@@ -35,6 +29,23 @@ impl<T: bytemuck::Pod> UniformResource<T> {
             }],
         });
 
+        Self::with_layout(device, label, data, layout, binding)
+    }
+
+    // Binds to a layout created elsewhere, e.g. reflected from the shaders
+    pub fn with_layout(
+        device: &wgpu::Device,
+        label: &str,
+        data: &[T],
+        layout: wgpu::BindGroupLayout,
+        binding: u32,
+    ) -> Self {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{label}_buffer")),
+            contents: bytemuck::cast_slice(data),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             label: Some(&format!("{label}_bind_group")),
             layout: &layout,
@@ -0,0 +1,56 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use notify::{RecursiveMode, Watcher};
+
+// Watches a directory tree and reports modified files relative to its root.
+// Native only, events are drained once per frame so bursts of writes from an
+// editor collapse into a single change.
+pub struct FileWatcher {
+    root: PathBuf,
+    rx: Receiver<notify::Result<notify::Event>>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new(root: &Path) -> anyhow::Result<Self> {
+        let root = root.canonicalize()?;
+        let (tx, rx) = channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        log::info!("Watching {:?} for changes", root);
+
+        Ok(Self {
+            root,
+            rx,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // Paths use forward slashes so they match the names assets were loaded with
+    pub fn changed_files(&self) -> HashSet<String> {
+        let mut changed = HashSet::new();
+        for event in self.rx.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    log::warn!("File watcher error: {}", e);
+                    continue;
+                }
+            };
+            if !(event.kind.is_modify() || event.kind.is_create()) {
+                continue;
+            }
+            for path in event.paths {
+                if let Ok(relative) = path.strip_prefix(&self.root) {
+                    changed.insert(relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+        changed
+    }
+}
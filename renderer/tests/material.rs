@@ -0,0 +1,302 @@
+// Renders a lit quad offscreen with shader.wgsl and checks the center pixel
+// against the same lighting done on the CPU
+
+use cgmath::{Quaternion, Vector3, Zero};
+use renderer::{
+    assets::Assets,
+    camera::CameraUniform,
+    instance::Instance,
+    light::LightUniform,
+    model::{Material, MaterialTextures, MaterialUniform, ModelVertex, Vertex},
+    pipeline::RenderPipelineBuilder,
+    reflect,
+    shader::{ShaderCache, ShaderDefines},
+    texture::{ColorSpace, SamplerFilter, Texture},
+};
+use wgpu::util::DeviceExt;
+
+// Odd so the center pixel sits exactly on the view axis
+const SIZE: u32 = 5;
+const TOLERANCE: i32 = 2;
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+}
+
+struct Setup {
+    diffuse: ([u8; 4], ColorSpace),
+    orm: [u8; 4],
+    opacity: u8,
+    uniform: MaterialUniform,
+    defines: &'static [&'static str],
+    // Above 1 the quad is drawn multisampled and resolved
+    sample_count: u32,
+    alpha_to_coverage: bool,
+}
+
+impl Default for Setup {
+    fn default() -> Self {
+        Self {
+            diffuse: ([255; 4], ColorSpace::Srgb),
+            orm: [255; 4],
+            opacity: 255,
+            uniform: MaterialUniform {
+                diffuse: [0.5, 0.5, 0.5, 1.0],
+                specular: [0.2, 0.2, 0.2],
+                shininess: 32.0,
+                ..Default::default()
+            },
+            defines: &[],
+            sample_count: 1,
+            alpha_to_coverage: false,
+        }
+    }
+}
+
+// Red channel of the center pixel, `None` without a usable adapter
+fn render(setup: Setup) -> Option<u8> {
+    let Some((device, queue)) = device() else {
+        eprintln!("No adapter available, skipping GPU test");
+        return None;
+    };
+
+    let mut shader_cache = ShaderCache::default();
+    let defines = setup.defines.iter().map(|define| define.to_string()).collect::<ShaderDefines>();
+    let shader = shader_cache.get_or_compile(&device, "shader.wgsl", &defines).unwrap();
+    let layout = |label, group| reflect::create_bind_group_layout(&device, label, &[(&shader.reflection, group)]).unwrap();
+    let material_layout = layout("material", 0);
+    let camera_layout = layout("camera", 1);
+    let light_layout = layout("light", 2);
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&material_layout, &camera_layout, &light_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = RenderPipelineBuilder::new("material_test", &pipeline_layout, &shader.module)
+        .buffers(&[ModelVertex::desc(), renderer::instance::InstanceRaw::desc()])
+        .color_format(wgpu::TextureFormat::Rgba8Unorm)
+        .depth_format(None)
+        .sample_count(setup.sample_count)
+        .alpha_to_coverage(setup.alpha_to_coverage)
+        .build(&device);
+
+    let mut assets = Assets::default();
+    let (diffuse, diffuse_space) = setup.diffuse;
+    let diffuse = assets.insert(Texture::from_color(&device, &queue, diffuse, "diffuse", diffuse_space));
+    let normal = assets.insert(Texture::flat_normal(&device, &queue));
+    let orm = assets.insert(Texture::from_color(&device, &queue, setup.orm, "orm", ColorSpace::Linear));
+    let opacity = [setup.opacity, setup.opacity, setup.opacity, 255];
+    let opacity = assets.insert(Texture::from_color(&device, &queue, opacity, "opacity", ColorSpace::Linear));
+    let textures = MaterialTextures {
+        diffuse,
+        normal,
+        occlusion: orm.clone(),
+        roughness: orm.clone(),
+        metallic: orm,
+        opacity,
+    };
+    let sampler = Texture::create_sampler(&device, SamplerFilter::Nearest, wgpu::AddressMode::ClampToEdge);
+    let material = Material::new(&device, "material", &assets, textures, sampler, setup.uniform, &material_layout);
+
+    // View space is world space, the projection keeps x and y and puts every
+    // fragment at depth 0.5
+    let camera = CameraUniform {
+        view_proj: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 0.0, 0.0], [0.0, 0.0, 0.5, 1.0]],
+        view: [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]],
+    };
+    // White light at the eye, straight in front of the quad
+    let light = LightUniform::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]);
+    let uniform_bind_group = |layout: &wgpu::BindGroupLayout, contents: &[u8]| {
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        })
+    };
+    let camera_bind_group = uniform_bind_group(&camera_layout, bytemuck::cast_slice(&[camera]));
+    let light_bind_group = uniform_bind_group(&light_layout, bytemuck::cast_slice(&[light]));
+
+    let vertex = |x: f32, y: f32| ModelVertex {
+        position: [x, y, -0.5],
+        tex_coords: [0.5, 0.5],
+        normal: [0.0, 0.0, 1.0],
+        tangent: [1.0, 0.0, 0.0, 1.0],
+    };
+    let vertices = [
+        vertex(-1.0, -1.0), vertex(1.0, -1.0), vertex(1.0, 1.0),
+        vertex(-1.0, -1.0), vertex(1.0, 1.0), vertex(-1.0, 1.0),
+    ];
+    let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&vertices),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+    let instance = Instance {
+        position: Vector3::zero(),
+        rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+    };
+    let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&[instance.to_raw()]),
+        usage: wgpu::BufferUsages::VERTEX,
+    });
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("material_test_target"),
+        size: wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    let multisampled = (setup.sample_count > 1).then(|| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("material_test_samples"),
+                size: wgpu::Extent3d { width: SIZE, height: SIZE, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: setup.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: multisampled.as_ref().unwrap_or(&view),
+                resolve_target: multisampled.as_ref().map(|_| &view),
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &material.bind_group, &[]);
+        pass.set_bind_group(1, &camera_bind_group, &[]);
+        pass.set_bind_group(2, &light_bind_group, &[]);
+        pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.slice(..));
+        pass.draw(0..vertices.len() as u32, 0..1);
+    }
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let pixels = Texture::read_rgba8(&device, &queue, &target).unwrap();
+    Some(pixels.get_pixel(SIZE / 2, SIZE / 2)[0])
+}
+
+fn assert_pixel(actual: Option<u8>, expected: f32) {
+    let Some(actual) = actual else { return };
+    let expected = (expected.clamp(0.0, 1.0) * 255.0).round() as i32;
+    assert!(
+        (actual as i32 - expected).abs() <= TOLERANCE,
+        "center pixel is {actual}, expected {expected}"
+    );
+}
+
+// The flat normal map stores 128 rather than 127.5, so the shading normal is
+// slightly off the light direction
+fn n_dot_l() -> f32 {
+    let n = 128.0 / 255.0 * 2.0 - 1.0;
+    1.0 / (2.0 * n * n + 1.0f32).sqrt()
+}
+
+#[test]
+fn blinn_phong_without_pbr_maps() {
+    // Ambient + diffuse + specular, tinted by Kd
+    let expected = (0.15 + n_dot_l() + 0.2 * n_dot_l().powf(32.0)) * 0.5;
+    assert_pixel(render(Setup::default()), expected);
+}
+
+#[test]
+fn occlusion_and_roughness() {
+    let mut setup = Setup {
+        orm: [0, 255, 0, 255],
+        ..Default::default()
+    };
+    setup.uniform.roughness = 1.0;
+    setup.uniform.metallic = 1.0;
+    // No ambient, fully rough kills the highlight, no metal in the blue channel
+    let expected = n_dot_l() * 0.5;
+    assert_pixel(render(setup), expected);
+}
+
+#[test]
+fn metallic_from_packed_map() {
+    let mut setup = Setup {
+        orm: [255, 0, 255, 255],
+        ..Default::default()
+    };
+    setup.uniform.roughness = 1.0;
+    setup.uniform.metallic = 1.0;
+    // No diffuse, the highlight is white before the base color tint
+    let expected = (0.15 + n_dot_l().powf(32.0)) * 0.5;
+    assert_pixel(render(setup), expected);
+}
+
+#[test]
+fn diffuse_color_space() {
+    let setup = |space| {
+        let mut setup = Setup {
+            diffuse: ([128, 128, 128, 255], space),
+            ..Default::default()
+        };
+        setup.uniform.diffuse = [1.0; 4];
+        setup.uniform.specular = [0.0; 3];
+        setup
+    };
+    // sRGB 128 decodes to about 0.216, linear is sampled as stored
+    let srgb = ((128.0 / 255.0 + 0.055) / 1.055f32).powf(2.4);
+    assert_pixel(render(setup(ColorSpace::Srgb)), (0.15 + n_dot_l()) * srgb);
+    assert_pixel(render(setup(ColorSpace::Linear)), (0.15 + n_dot_l()) * 128.0 / 255.0);
+}
+
+#[test]
+fn cutouts() {
+    let setup = |alpha: u8, opacity: u8, defines, sample_count| {
+        let mut setup = Setup {
+            diffuse: ([255, 255, 255, alpha], ColorSpace::Srgb),
+            opacity,
+            defines,
+            sample_count,
+            alpha_to_coverage: sample_count > 1,
+            ..Default::default()
+        };
+        setup.uniform.alpha_cutoff = 0.5;
+        setup
+    };
+    let lit = (0.15 + n_dot_l() + 0.2 * n_dot_l().powf(32.0)) * 0.5;
+    // Discarded without MSAA, no coverage with it. The alpha is constant over
+    // the quad, so coverage is all or nothing. The opacity map cuts the same
+    // way as the diffuse alpha.
+    for (defines, sample_count) in [(&["ALPHA_MASK"], 1), (&["ALPHA_TO_COVERAGE"], 4)] {
+        assert_pixel(render(setup(64, 255, defines, sample_count)), 0.0);
+        assert_pixel(render(setup(192, 255, defines, sample_count)), lit);
+        assert_pixel(render(setup(255, 64, defines, sample_count)), 0.0);
+        assert_pixel(render(setup(255, 192, defines, sample_count)), lit);
+    }
+}
@@ -0,0 +1,51 @@
+// Loads OBJ files with missing vertex attributes, which have to fall back
+// instead of indexing past the end of the attribute arrays
+
+use renderer::{assets::AssetServer, reflect, shader::ShaderCache};
+
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())).ok()?;
+    pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default())).ok()
+}
+
+// Writes `obj` to a temporary file and loads it, `None` without a usable adapter
+fn load(name: &str, obj: &str) -> Option<usize> {
+    let Some((device, queue)) = device() else {
+        eprintln!("No adapter available, skipping GPU test");
+        return None;
+    };
+    let shader = ShaderCache::default()
+        .get_or_compile(&device, "shader.wgsl", &Default::default())
+        .unwrap();
+    let layout = reflect::create_bind_group_layout(&device, "material", &[(&shader.reflection, 0)]).unwrap();
+
+    // Absolute paths are read as they are rather than from the res directory
+    let path = std::env::temp_dir().join(format!("renderer-{}-{}.obj", name, std::process::id()));
+    std::fs::write(&path, obj).unwrap();
+    let mut assets = AssetServer::new();
+    let model = pollster::block_on(assets.load_model(path.to_str().unwrap(), &device, &queue, &layout));
+    std::fs::remove_file(&path).unwrap();
+
+    let model = model.unwrap();
+    Some(assets.models.get(&model).unwrap().meshes.len())
+}
+
+#[test]
+fn mesh_without_texture_coordinates() {
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n";
+    if let Some(meshes) = load("no-texcoords", obj) {
+        assert_eq!(meshes, 1);
+    }
+}
+
+#[test]
+fn mesh_with_positions_only() {
+    let obj = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+    if let Some(meshes) = load("positions-only", obj) {
+        assert_eq!(meshes, 1);
+    }
+}
@@ -0,0 +1,55 @@
+// Records a short session on a headless state and replays it through the
+// windowless driver, which has to land on the same poses
+
+use instant::Duration;
+use renderer::{
+    input::{Binding, InputEvent},
+    replay::{InputRecorder, InputReplay},
+    state::State,
+};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+fn headless() -> Option<State> {
+    match pollster::block_on(State::headless(64, 64)) {
+        Ok(state) => Some(state),
+        Err(e) => {
+            eprintln!("No adapter available, skipping GPU test: {}", e);
+            None
+        }
+    }
+}
+
+#[test]
+fn headless_replay_matches_the_recording() {
+    let Some(mut state) = headless() else {
+        return;
+    };
+    let path = std::env::temp_dir().join(format!("renderer-replay-{}.txt", std::process::id()));
+    let mut recorder = InputRecorder::create(&path).unwrap();
+
+    let start = state.camera_pose();
+    let frames: [(Vec<InputEvent>, u64); 4] = [
+        (vec![InputEvent::Button(Binding::Key(KeyCode::KeyW), true)], 16),
+        (vec![InputEvent::Button(Binding::Mouse(MouseButton::Left), true), InputEvent::MouseMotion(40.0, -10.0)], 7),
+        (vec![InputEvent::Button(Binding::Key(KeyCode::KeyW), false)], 33),
+        (vec![InputEvent::Button(Binding::Mouse(MouseButton::Left), false)], 250),
+    ];
+    for (events, millis) in &frames {
+        for event in events {
+            recorder.record(event);
+            state.input(event);
+        }
+        let dt = Duration::from_millis(*millis);
+        state.update(dt);
+        recorder.end_frame(dt, state.camera_pose());
+    }
+    drop(recorder);
+    assert_ne!(state.camera_pose().position, start.position, "the session should move the camera");
+
+    let mut replay = InputReplay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let mut state = headless().unwrap();
+    assert_eq!(replay.run(&mut state), 0);
+    assert_eq!(replay.frame(), frames.len());
+    assert!(replay.is_finished());
+}